@@ -0,0 +1,16 @@
+alter table assignments
+    add column encoded_input  bytea not null default '',
+    add column encoded_output bytea not null default '';
+
+update assignments
+set encoded_input  = test_cases.encoded_input,
+    encoded_output = test_cases.encoded_output
+from test_cases
+where test_cases.assignment_id = assignments.id
+  and test_cases.position = 0;
+
+alter table assignments
+    alter column encoded_input drop default,
+    alter column encoded_output drop default;
+
+drop table test_cases;
@@ -0,0 +1,22 @@
+create table test_cases
+(
+    id             uuid primary key   default uuid_generate_v4(),
+    assignment_id  uuid      not null,
+    position       smallint  not null default 0 check ( position >= 0 ),
+    name           text      not null,
+    encoded_input  bytea     not null,
+    encoded_output bytea     not null,
+    updated        timestamp not null default now(),
+    constraint fk_assignments
+        foreign key (assignment_id)
+            references assignments (id)
+            on delete cascade
+);
+
+insert into test_cases (assignment_id, position, name, encoded_input, encoded_output, updated)
+select id, 0, 'default', encoded_input, encoded_output, updated
+from assignments;
+
+alter table assignments
+    drop column encoded_input,
+    drop column encoded_output;
@@ -0,0 +1,11 @@
+alter table files
+    add column encoded_output bytea;
+
+update files
+set encoded_output = test_case_results.encoded_output
+from test_case_results
+         inner join test_cases on test_cases.id = test_case_results.test_case_id
+where test_case_results.file_id = files.id
+  and test_cases.position = 0;
+
+drop table test_case_results;
@@ -0,0 +1,28 @@
+create table test_case_results
+(
+    id             uuid primary key   default uuid_generate_v4(),
+    file_id        uuid      not null,
+    test_case_id   uuid      not null,
+    encoded_output bytea     not null,
+    passed         bool      not null default false,
+    updated        timestamp not null default now(),
+    unique (file_id, test_case_id),
+    constraint fk_files
+        foreign key (file_id)
+            references files (id)
+            on delete cascade,
+    constraint fk_test_cases
+        foreign key (test_case_id)
+            references test_cases (id)
+            on delete cascade
+);
+
+insert into test_case_results (file_id, test_case_id, encoded_output, passed, updated)
+select files.id, test_cases.id, files.encoded_output, files.validated, files.updated
+from files
+         inner join submissions on submissions.id = files.submission_id
+         inner join test_cases on test_cases.assignment_id = submissions.assignment_id
+where files.encoded_output is not null;
+
+alter table files
+    drop column encoded_output;
@@ -0,0 +1,4 @@
+alter table assignments
+    drop column comparison_mode,
+    drop column absolute_tolerance,
+    drop column relative_tolerance;
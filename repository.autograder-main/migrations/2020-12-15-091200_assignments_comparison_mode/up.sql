@@ -0,0 +1,6 @@
+alter table assignments
+    add column comparison_mode    text not null default 'exact'
+        check ( comparison_mode in
+                ('exact', 'whitespace_insensitive', 'line_order_insensitive', 'numeric', 'regex') ),
+    add column absolute_tolerance double precision check ( absolute_tolerance >= 0 ),
+    add column relative_tolerance double precision check ( relative_tolerance >= 0 );
@@ -0,0 +1,6 @@
+alter table files
+    drop column score,
+    drop column max_score;
+
+alter table test_cases
+    drop column weight;
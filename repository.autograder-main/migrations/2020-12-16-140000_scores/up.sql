@@ -0,0 +1,18 @@
+alter table test_cases
+    add column weight double precision not null default 1 check ( weight >= 0 );
+
+alter table files
+    add column score     double precision not null default 0 check ( score >= 0 ),
+    add column max_score double precision not null default 0 check ( max_score >= 0 );
+
+update files
+set max_score = weights.max_score
+from (select submissions.id as submission_id, coalesce(sum(test_cases.weight), 0) as max_score
+      from submissions
+               left join test_cases on test_cases.assignment_id = submissions.assignment_id
+      group by submissions.id) as weights
+where weights.submission_id = files.submission_id;
+
+update files
+set score = max_score
+where validated;
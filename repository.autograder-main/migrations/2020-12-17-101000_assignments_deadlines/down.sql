@@ -0,0 +1,11 @@
+alter table files
+    drop column late;
+
+alter table assignments
+    drop constraint opens_before_close,
+    drop constraint due_before_close,
+    drop constraint opens_before_due,
+    drop column opens_at,
+    drop column due_at,
+    drop column closes_at,
+    drop column late_penalty;
@@ -0,0 +1,12 @@
+alter table assignments
+    add column opens_at     timestamp,
+    add column due_at       timestamp,
+    add column closes_at    timestamp,
+    add column late_penalty double precision not null default 0
+        check ( late_penalty >= 0 and late_penalty <= 1 ),
+    add constraint opens_before_due check ( opens_at <= due_at ),
+    add constraint due_before_close check ( due_at <= closes_at ),
+    add constraint opens_before_close check ( opens_at <= closes_at );
+
+alter table files
+    add column late bool not null default false;
@@ -0,0 +1,12 @@
+create table extensions
+(
+    assignment_id uuid      not null,
+    user_id       uuid      not null,
+    due_at        timestamp not null,
+    updated       timestamp not null default now(),
+    primary key (assignment_id, user_id),
+    constraint fk_assignments
+        foreign key (assignment_id)
+            references assignments (id)
+            on delete cascade
+);
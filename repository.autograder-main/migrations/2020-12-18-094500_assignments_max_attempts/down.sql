@@ -0,0 +1,2 @@
+alter table assignments
+    drop column max_attempts;
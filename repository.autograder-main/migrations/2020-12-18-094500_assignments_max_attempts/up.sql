@@ -0,0 +1,2 @@
+alter table assignments
+    add column max_attempts smallint check ( max_attempts > 0 );
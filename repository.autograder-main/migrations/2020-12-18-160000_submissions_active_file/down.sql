@@ -0,0 +1,3 @@
+alter table submissions
+    drop constraint fk_active_file,
+    drop column active_file_id;
@@ -0,0 +1,13 @@
+alter table submissions
+    add column active_file_id uuid,
+    add constraint fk_active_file
+        foreign key (active_file_id)
+            references files (id)
+            on delete set null;
+
+update submissions
+set active_file_id = latest.id
+from (select distinct on (submission_id) id, submission_id
+      from files
+      order by submission_id, updated desc) as latest
+where latest.submission_id = submissions.id;
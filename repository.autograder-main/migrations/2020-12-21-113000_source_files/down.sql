@@ -0,0 +1 @@
+drop table source_files;
@@ -0,0 +1,12 @@
+create table source_files
+(
+    id           uuid primary key default uuid_generate_v4(),
+    file_id      uuid  not null,
+    path         text  not null,
+    encoded_text bytea not null,
+    unique (file_id, path),
+    constraint fk_files
+        foreign key (file_id)
+            references files (id)
+            on delete cascade
+);
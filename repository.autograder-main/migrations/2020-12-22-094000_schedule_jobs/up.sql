@@ -0,0 +1,21 @@
+create table schedule_jobs
+(
+    id              uuid primary key   default uuid_generate_v4(),
+    file_id         uuid      not null,
+    status          text      not null default 'pending'
+        check (status in ('pending', 'delivered', 'cancelled', 'dead')),
+    attempts        smallint  not null default 0,
+    next_attempt_at timestamp not null default now(),
+    last_error      text,
+    token           text      not null,
+    created         timestamp not null default now(),
+    updated         timestamp not null default now(),
+    delivered_at    timestamp,
+    constraint fk_files
+        foreign key (file_id)
+            references files (id)
+            on delete cascade
+);
+
+create index schedule_jobs_pending_idx on schedule_jobs (next_attempt_at) where status = 'pending';
+create index schedule_jobs_file_id_idx on schedule_jobs (file_id, created);
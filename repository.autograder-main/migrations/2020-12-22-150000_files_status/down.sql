@@ -0,0 +1,13 @@
+alter table files
+    add column scheduled bool not null default false,
+    add column validated bool not null default false;
+
+update files
+set scheduled = status <> 'submitted',
+    validated = status = 'passed';
+
+alter table files
+    drop column status,
+    drop column queued_at,
+    drop column started_at,
+    drop column finished_at;
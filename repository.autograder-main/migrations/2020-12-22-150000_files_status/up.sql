@@ -0,0 +1,28 @@
+alter table files
+    add column status      text not null default 'submitted'
+        check (status in ('submitted', 'queued', 'running', 'passed', 'failed', 'compile_error',
+                          'runtime_error', 'timeout', 'internal_error')),
+    add column queued_at   timestamp,
+    add column started_at  timestamp,
+    add column finished_at timestamp;
+
+update files
+set status      = case
+                      when validated then 'passed'
+                      when exists(select 1 from test_case_results where test_case_results.file_id = files.id)
+                          then 'failed'
+                      -- scheduled files have no job to deliver them, so they would stay queued
+                      when scheduled then 'internal_error'
+                      else 'submitted'
+    end,
+    queued_at   = case when scheduled or validated then updated end,
+    finished_at = case
+                      when scheduled or validated or exists(select 1
+                                                            from test_case_results
+                                                            where test_case_results.file_id = files.id)
+                          then updated
+        end;
+
+alter table files
+    drop column scheduled,
+    drop column validated;
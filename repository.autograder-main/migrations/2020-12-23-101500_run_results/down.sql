@@ -0,0 +1,8 @@
+alter table test_case_results
+    drop column encoded_stderr,
+    drop column exit_code,
+    drop column signal,
+    drop column duration_ms,
+    drop column memory_kb;
+
+drop table run_results;
@@ -0,0 +1,22 @@
+create table run_results
+(
+    file_id        uuid primary key,
+    encoded_stdout bytea     not null default '',
+    encoded_stderr bytea     not null default '',
+    exit_code      integer,
+    signal         integer,
+    duration_ms    bigint,
+    memory_kb      bigint,
+    updated        timestamp not null default now(),
+    constraint fk_files
+        foreign key (file_id)
+            references files (id)
+            on delete cascade
+);
+
+alter table test_case_results
+    add column encoded_stderr bytea not null default '',
+    add column exit_code      integer,
+    add column signal         integer,
+    add column duration_ms    bigint,
+    add column memory_kb      bigint;
@@ -0,0 +1,7 @@
+alter table files
+    drop column run_retries,
+    drop column status_reason;
+
+alter table assignments
+    drop column run_timeout_seconds,
+    drop column max_run_retries;
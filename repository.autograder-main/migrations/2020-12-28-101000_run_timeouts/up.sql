@@ -0,0 +1,7 @@
+alter table assignments
+    add column run_timeout_seconds integer check ( run_timeout_seconds > 0 ),
+    add column max_run_retries     smallint not null default 0 check ( max_run_retries >= 0 );
+
+alter table files
+    add column run_retries   smallint not null default 0,
+    add column status_reason text;
@@ -0,0 +1,2 @@
+drop trigger files_notify_event on files;
+drop function notify_file_event();
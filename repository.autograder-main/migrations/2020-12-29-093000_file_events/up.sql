@@ -0,0 +1,18 @@
+create function notify_file_event() returns trigger as
+$$
+begin
+    perform pg_notify('file_events', json_build_object(
+            'file_id', new.id,
+            'status', new.status,
+            'updated', new.updated
+        )::text);
+    return new;
+end;
+$$ language plpgsql;
+
+create trigger files_notify_event
+    after update
+    on files
+    for each row
+    when (old.status is distinct from new.status or old.queued_at is distinct from new.queued_at)
+execute procedure notify_file_event();
@@ -0,0 +1,8 @@
+delete from schedule_jobs where scratch_run_id is not null;
+
+alter table schedule_jobs
+    drop constraint schedule_jobs_target,
+    drop column scratch_run_id,
+    alter column file_id set not null;
+
+drop table scratch_runs;
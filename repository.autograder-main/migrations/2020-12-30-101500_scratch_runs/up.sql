@@ -0,0 +1,39 @@
+create table scratch_runs
+(
+    id             uuid primary key   default uuid_generate_v4(),
+    user_id        uuid      not null,
+    assignment_id  uuid      not null,
+    extension      text      not null,
+    encoded_text   bytea     not null,
+    encoded_input  bytea     not null default '',
+    status         text      not null default 'queued'
+        check ( status in ('queued', 'running', 'passed', 'compile_error', 'runtime_error', 'timeout',
+                           'internal_error') ),
+    status_reason  text,
+    encoded_stdout bytea     not null default '',
+    encoded_stderr bytea     not null default '',
+    exit_code      integer,
+    signal         integer,
+    duration_ms    bigint,
+    memory_kb      bigint,
+    created        timestamp not null default now(),
+    updated        timestamp not null default now(),
+    finished_at    timestamp,
+    constraint fk_assignments
+        foreign key (assignment_id)
+            references assignments (id)
+            on delete cascade
+);
+
+create index scratch_runs_user_id_idx on scratch_runs (user_id, created);
+
+alter table schedule_jobs
+    alter column file_id drop not null,
+    add column scratch_run_id uuid,
+    add constraint fk_scratch_runs
+        foreign key (scratch_run_id)
+            references scratch_runs (id)
+            on delete cascade,
+    add constraint schedule_jobs_target check ( num_nonnulls(file_id, scratch_run_id) = 1 );
+
+create index schedule_jobs_scratch_run_id_idx on schedule_jobs (scratch_run_id, created);
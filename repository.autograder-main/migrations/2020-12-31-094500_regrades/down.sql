@@ -0,0 +1,2 @@
+drop table regrade_files;
+drop table regrades;
@@ -0,0 +1,37 @@
+create table regrades
+(
+    id            uuid primary key   default uuid_generate_v4(),
+    assignment_id uuid      not null,
+    user_id       uuid      not null,
+    mode          text      not null check ( mode in ('revalidate', 'rerun') ),
+    token         text      not null,
+    total         integer   not null,
+    processed     integer   not null default 0,
+    failed        integer   not null default 0,
+    last_error    text,
+    created       timestamp not null default now(),
+    updated       timestamp not null default now(),
+    finished_at   timestamp,
+    constraint fk_assignments
+        foreign key (assignment_id)
+            references assignments (id)
+            on delete cascade
+);
+
+create index regrades_assignment_id_idx on regrades (assignment_id, created);
+
+create table regrade_files
+(
+    regrade_id uuid    not null,
+    file_id    uuid    not null,
+    done       boolean not null default false,
+    primary key (regrade_id, file_id),
+    constraint fk_regrades
+        foreign key (regrade_id)
+            references regrades (id)
+            on delete cascade,
+    constraint fk_files
+        foreign key (file_id)
+            references files (id)
+            on delete cascade
+);
@@ -0,0 +1 @@
+drop table assignment_collaborators;
@@ -0,0 +1,17 @@
+create table assignment_collaborators
+(
+    assignment_id uuid      not null,
+    user_id       uuid      not null,
+    role          text      not null
+        constraint assignment_collaborators_role_check
+            check (role in ('co_owner', 'grader', 'viewer')),
+    created       timestamp not null default now(),
+    updated       timestamp not null default now(),
+    primary key (assignment_id, user_id),
+    constraint fk_assignments
+        foreign key (assignment_id)
+            references assignments (id)
+            on delete cascade
+);
+
+create index assignment_collaborators_user_id_idx on assignment_collaborators (user_id);
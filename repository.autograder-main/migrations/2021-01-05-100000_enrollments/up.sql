@@ -0,0 +1,18 @@
+create table enrollments
+(
+    assignment_id uuid      not null,
+    user_id       uuid      not null,
+    created       timestamp not null default now(),
+    primary key (assignment_id, user_id),
+    constraint fk_assignments
+        foreign key (assignment_id)
+            references assignments (id)
+            on delete cascade
+);
+
+create index enrollments_user_id_idx on enrollments (user_id);
+
+-- students who submitted before enrollments were mirrored keep access to their submissions
+insert into enrollments (assignment_id, user_id)
+select distinct assignment_id, user_id
+from submissions;
@@ -0,0 +1,2 @@
+drop table audit_events;
+drop function reject_audit_event_change();
@@ -0,0 +1,36 @@
+create table audit_events
+(
+    id            uuid primary key   default uuid_generate_v4(),
+    actor_kind    text      not null check ( actor_kind in ('user', 'runner', 'system') ),
+    actor_id      uuid,
+    action        text      not null,
+    resource_type text      not null,
+    resource_id   uuid      not null,
+    before_digest text,
+    after_digest  text,
+    request_id    text,
+    created       timestamp not null default now()
+);
+
+create index audit_events_actor_id_idx on audit_events (actor_id, created);
+create index audit_events_resource_idx on audit_events (resource_type, resource_id, created);
+create index audit_events_created_idx on audit_events (created);
+
+create function reject_audit_event_change() returns trigger as
+$$
+begin
+    raise exception 'audit events are append-only';
+end;
+$$ language plpgsql;
+
+create trigger audit_events_append_only
+    before update or delete
+    on audit_events
+    for each row
+execute procedure reject_audit_event_change();
+
+create trigger audit_events_no_truncate
+    before truncate
+    on audit_events
+    for each statement
+execute procedure reject_audit_event_change();
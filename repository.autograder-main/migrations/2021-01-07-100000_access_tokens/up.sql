@@ -0,0 +1,18 @@
+create table access_tokens
+(
+    id           uuid primary key   default uuid_generate_v4(),
+    user_id      uuid      not null,
+    name         text      not null,
+    token_hash   text      not null unique,
+    scopes       text[]    not null,
+    is_superuser boolean   not null,
+    is_teacher   boolean   not null,
+    is_student   boolean   not null,
+    email        text      not null,
+    created      timestamp not null default now(),
+    expires_at   timestamp,
+    last_used_at timestamp,
+    revoked_at   timestamp
+);
+
+create index access_tokens_user_id_idx on access_tokens (user_id, created);
@@ -0,0 +1,4 @@
+drop index files_submission_id_created_idx;
+
+alter table files
+    drop column created;
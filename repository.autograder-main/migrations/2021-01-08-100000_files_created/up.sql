@@ -0,0 +1,7 @@
+alter table files
+    add column created timestamp not null default now();
+
+update files
+set created = updated;
+
+create index files_submission_id_created_idx on files (submission_id, created);
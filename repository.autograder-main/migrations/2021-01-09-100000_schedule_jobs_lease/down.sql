@@ -0,0 +1,2 @@
+alter table schedule_jobs
+    drop column lease_token;
@@ -0,0 +1,2 @@
+alter table schedule_jobs
+    add column lease_token uuid;
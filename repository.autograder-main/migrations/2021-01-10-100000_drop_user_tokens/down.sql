@@ -0,0 +1,4 @@
+alter table schedule_jobs
+    add column token text not null default '';
+alter table regrades
+    add column token text not null default '';
@@ -0,0 +1,4 @@
+alter table schedule_jobs
+    drop column token;
+alter table regrades
+    drop column token;
@@ -0,0 +1,4 @@
+alter table access_tokens
+    alter column expires_at drop not null;
+
+drop table users;
@@ -0,0 +1,23 @@
+create table users
+(
+    user_id      uuid primary key,
+    is_superuser boolean   not null,
+    is_teacher   boolean   not null,
+    is_student   boolean   not null,
+    email        text      not null,
+    updated      timestamp not null default now()
+);
+
+-- the roles of the users who issued access tokens, until they use a token of the core service again
+insert into users (user_id, is_superuser, is_teacher, is_student, email)
+select distinct on (user_id) user_id, is_superuser, is_teacher, is_student, email
+from access_tokens
+order by user_id, created desc;
+
+-- tokens without an expiry expire like newly issued ones
+update access_tokens
+set expires_at = now() + interval '90 days'
+where expires_at is null;
+
+alter table access_tokens
+    alter column expires_at set not null;
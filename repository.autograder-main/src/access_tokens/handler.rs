@@ -0,0 +1,49 @@
+use std::str::FromStr;
+
+use rocket::http::Status;
+use rocket::response::status;
+use rocket_contrib::json::Json;
+use uuid::Uuid;
+
+use crate::access_tokens;
+use crate::access_tokens::{AccessToken, AccessTokenRequest, IssuedAccessToken};
+use crate::audit::{AuditContext, RequestId};
+use crate::auth::Auth;
+use crate::connection::{to_status_created, DbConn};
+
+#[post("/", format = "application/json", data = "<access_token_request>")]
+pub fn insert(
+    auth: Auth,
+    request_id: RequestId,
+    access_token_request: Json<AccessTokenRequest>,
+    connection: DbConn,
+) -> Result<status::Created<Json<IssuedAccessToken>>, rocket::http::Status> {
+    let audit = AuditContext::user(&auth, request_id);
+    let result: Result<Json<IssuedAccessToken>, rocket::http::Status> =
+        access_tokens::repository::insert(
+            access_token_request.into_inner(),
+            auth,
+            &audit,
+            &connection,
+        )
+        .into();
+    result.map(|issued| to_status_created(issued.access_token.id, "/access_tokens/", issued))
+}
+
+#[get("/")]
+pub fn all(auth: Auth, connection: DbConn) -> Result<Json<Vec<AccessToken>>, rocket::http::Status> {
+    access_tokens::repository::all(auth, &connection).into()
+}
+
+#[delete("/<id>")]
+pub fn revoke(
+    auth: Auth,
+    request_id: RequestId,
+    id: String,
+    connection: DbConn,
+) -> Result<Json<AccessToken>, rocket::http::Status> {
+    let audit = AuditContext::user(&auth, request_id);
+    Uuid::from_str(&id)
+        .map_err(|_| Status::BadRequest)
+        .and_then(|uuid| access_tokens::repository::revoke(uuid, auth, &audit, &connection).into())
+}
@@ -0,0 +1,149 @@
+use std::io::Write;
+
+use chrono::NaiveDateTime;
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Text;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use super::schema::access_tokens;
+
+pub mod handler;
+pub mod repository;
+pub mod router;
+
+/// Personal access tokens start with this prefix, which tells them apart from
+/// the tokens of the core service.
+pub const TOKEN_PREFIX: &str = "agpat_";
+/// Longest time an access token can be valid for.
+pub const MAX_LIFETIME_DAYS: i64 = 90;
+
+/// What a personal access token can be used for. Write access includes read
+/// access.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow)]
+#[sql_type = "Text"]
+pub enum Scope {
+    /// Assignments with their test cases, extensions, regrades, collaborators
+    /// and students.
+    #[serde(rename = "assignments:read")]
+    AssignmentsRead,
+    #[serde(rename = "assignments:write")]
+    AssignmentsWrite,
+    /// Submissions and their files.
+    #[serde(rename = "submissions:read")]
+    SubmissionsRead,
+    #[serde(rename = "submissions:write")]
+    SubmissionsWrite,
+    #[serde(rename = "scratch_runs:read")]
+    ScratchRunsRead,
+    #[serde(rename = "scratch_runs:write")]
+    ScratchRunsWrite,
+    #[serde(rename = "audit_events:read")]
+    AuditEventsRead,
+}
+
+impl Scope {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Scope::AssignmentsRead => "assignments:read",
+            Scope::AssignmentsWrite => "assignments:write",
+            Scope::SubmissionsRead => "submissions:read",
+            Scope::SubmissionsWrite => "submissions:write",
+            Scope::ScratchRunsRead => "scratch_runs:read",
+            Scope::ScratchRunsWrite => "scratch_runs:write",
+            Scope::AuditEventsRead => "audit_events:read",
+        }
+    }
+}
+
+impl ToSql<Text, Pg> for Scope {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        <str as ToSql<Text, Pg>>::to_sql(self.as_str(), out)
+    }
+}
+
+impl FromSql<Text, Pg> for Scope {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match <String as FromSql<Text, Pg>>::from_sql(bytes)?.as_str() {
+            "assignments:read" => Ok(Scope::AssignmentsRead),
+            "assignments:write" => Ok(Scope::AssignmentsWrite),
+            "submissions:read" => Ok(Scope::SubmissionsRead),
+            "submissions:write" => Ok(Scope::SubmissionsWrite),
+            "scratch_runs:read" => Ok(Scope::ScratchRunsRead),
+            "scratch_runs:write" => Ok(Scope::ScratchRunsWrite),
+            "audit_events:read" => Ok(Scope::AuditEventsRead),
+            other => Err(format!("unknown scope: {}", other).into()),
+        }
+    }
+}
+
+/// Long-lived token a user issued for scripts, like a CLI or CI job. It acts
+/// as the user with the roles they had when they issued it and still have,
+/// limited to its scopes. Only the hash of the token is stored.
+#[derive(Identifiable, Queryable, PartialEq, Debug, Serialize)]
+pub struct AccessToken {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    pub scopes: Vec<Scope>,
+    #[serde(skip_serializing)]
+    pub is_superuser: bool,
+    #[serde(skip_serializing)]
+    pub is_teacher: bool,
+    #[serde(skip_serializing)]
+    pub is_student: bool,
+    #[serde(skip_serializing)]
+    pub email: String,
+    pub created: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub revoked_at: Option<NaiveDateTime>,
+}
+
+#[derive(Deserialize)]
+pub struct AccessTokenRequest {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "access_tokens"]
+pub struct InsertableAccessToken {
+    pub user_id: Uuid,
+    pub name: String,
+    pub token_hash: String,
+    pub scopes: Vec<Scope>,
+    pub is_superuser: bool,
+    pub is_teacher: bool,
+    pub is_student: bool,
+    pub email: String,
+    pub expires_at: NaiveDateTime,
+}
+
+/// Newly issued token, the only time the token itself is returned.
+#[derive(Serialize)]
+pub struct IssuedAccessToken {
+    #[serde(flatten)]
+    pub access_token: AccessToken,
+    pub token: String,
+}
+
+/// New random token with 244 random bits.
+pub fn generate() -> String {
+    format!(
+        "{}{}{}",
+        TOKEN_PREFIX,
+        Uuid::new_v4().to_simple(),
+        Uuid::new_v4().to_simple()
+    )
+}
+
+/// Hex SHA-256 of a token. The tokens are random, so they need no salt.
+pub fn hash(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
@@ -0,0 +1,173 @@
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::access_tokens;
+use crate::access_tokens::{
+    AccessToken, AccessTokenRequest, InsertableAccessToken, IssuedAccessToken,
+};
+use crate::audit;
+use crate::audit::AuditContext;
+use crate::auth::policy::{Action, Relation, Resource};
+use crate::auth::{policy, Auth};
+use crate::connection::RepositoryError::{NotFound, Rejected};
+use crate::connection::{RepositoryError, RepositoryQueryResult};
+use crate::schema::access_tokens as access_tokens_schema;
+use crate::users;
+
+/// Issue a token for the user, which expires within `MAX_LIFETIME_DAYS`. The
+/// token is returned once and only its hash is stored.
+pub fn insert(
+    access_token_request: AccessTokenRequest,
+    auth: Auth,
+    audit: &AuditContext,
+    connection: &PgConnection,
+) -> RepositoryQueryResult<IssuedAccessToken> {
+    if let Err(error) = policy::authorize(
+        &auth,
+        Relation::Unrelated,
+        Action::Create,
+        Resource::AccessToken,
+    ) {
+        return RepositoryQueryResult::Err(error);
+    }
+    if access_token_request.scopes.is_empty() {
+        return RepositoryQueryResult::Err(Rejected(
+            "an access token needs at least one scope".to_owned(),
+        ));
+    }
+    let now = Utc::now().naive_utc();
+    if access_token_request.expires_at <= now {
+        return RepositoryQueryResult::Err(Rejected(
+            "an access token can't expire in the past".to_owned(),
+        ));
+    }
+    if access_token_request.expires_at > now + Duration::days(access_tokens::MAX_LIFETIME_DAYS) {
+        return RepositoryQueryResult::Err(Rejected(format!(
+            "an access token can't be valid for more than {} days",
+            access_tokens::MAX_LIFETIME_DAYS
+        )));
+    }
+    let token = access_tokens::generate();
+    connection
+        .transaction::<_, diesel::result::Error, _>(|| {
+            let access_token: AccessToken = diesel::insert_into(access_tokens_schema::table)
+                .values(&InsertableAccessToken {
+                    user_id: auth.user_id,
+                    name: access_token_request.name,
+                    token_hash: access_tokens::hash(&token),
+                    scopes: access_token_request.scopes,
+                    is_superuser: auth.is_superuser,
+                    is_teacher: auth.is_teacher,
+                    is_student: auth.is_student,
+                    email: auth.email.clone(),
+                    expires_at: access_token_request.expires_at,
+                })
+                .get_result(connection)?;
+            audit::repository::record(
+                audit,
+                "create",
+                Resource::AccessToken,
+                access_token.id,
+                None,
+                Some(&access_token),
+                connection,
+            )?;
+            Ok(IssuedAccessToken {
+                access_token,
+                token,
+            })
+        })
+        .into()
+}
+
+/// Tokens of the user, including revoked and expired ones, newest first.
+pub fn all(auth: Auth, connection: &PgConnection) -> RepositoryQueryResult<Vec<AccessToken>> {
+    if let Err(error) =
+        policy::authorize(&auth, Relation::Author, Action::List, Resource::AccessToken)
+    {
+        return RepositoryQueryResult::Err(error);
+    }
+    access_tokens_schema::table
+        .filter(access_tokens_schema::user_id.eq(auth.user_id))
+        .order(access_tokens_schema::created.desc())
+        .load(connection)
+        .into()
+}
+
+/// Revoke a token, which can't be used from then on. Superusers can revoke the
+/// tokens of all users.
+pub fn revoke(
+    id: Uuid,
+    auth: Auth,
+    audit: &AuditContext,
+    connection: &PgConnection,
+) -> RepositoryQueryResult<AccessToken> {
+    connection
+        .transaction::<_, RepositoryError<diesel::result::Error>, _>(|| {
+            let before: AccessToken = access_tokens_schema::table
+                .find(id)
+                .for_update()
+                .first(connection)
+                .optional()?
+                .ok_or(NotFound)?;
+            let relation = if before.user_id == auth.user_id {
+                Relation::Author
+            } else {
+                Relation::Unrelated
+            };
+            policy::authorize(&auth, relation, Action::Delete, Resource::AccessToken)?;
+            if before.revoked_at.is_some() {
+                return Ok(before);
+            }
+            let after: AccessToken = diesel::update(access_tokens_schema::table.find(id))
+                .set(access_tokens_schema::revoked_at.eq(Utc::now().naive_utc()))
+                .get_result(connection)?;
+            audit::repository::record(
+                audit,
+                "revoke",
+                Resource::AccessToken,
+                id,
+                Some(&before),
+                Some(&after),
+                connection,
+            )?;
+            Ok(after)
+        })
+        .into()
+}
+
+/// User of a token that is neither revoked nor expired, limited to the scopes
+/// of the token. The user keeps only the roles they had when they issued the
+/// token and still had in their last token of the core service. Marks the
+/// token as used.
+pub fn authenticate(token: &str, connection: &PgConnection) -> QueryResult<Option<Auth>> {
+    let now = Utc::now().naive_utc();
+    let access_token: Option<AccessToken> = diesel::update(
+        access_tokens_schema::table
+            .filter(access_tokens_schema::token_hash.eq(access_tokens::hash(token)))
+            .filter(access_tokens_schema::revoked_at.is_null())
+            .filter(access_tokens_schema::expires_at.gt(now)),
+    )
+    .set(access_tokens_schema::last_used_at.eq(now))
+    .get_result(connection)
+    .optional()?;
+    let access_token = match access_token {
+        Some(access_token) => access_token,
+        None => return Ok(None),
+    };
+    let user = match users::repository::find(access_token.user_id, connection)? {
+        Some(user) => user,
+        None => return Ok(None),
+    };
+    Ok(Some(Auth {
+        user_id: access_token.user_id,
+        is_superuser: access_token.is_superuser && user.is_superuser,
+        is_teacher: access_token.is_teacher && user.is_teacher,
+        // students are the least privileged, so a user who became one acts as one
+        is_student: access_token.is_student || user.is_student,
+        email: user.email,
+        exp: access_token.expires_at.timestamp() as u32,
+        scopes: Some(access_token.scopes),
+    }))
+}
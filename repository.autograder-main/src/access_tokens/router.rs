@@ -0,0 +1,10 @@
+use rocket::Rocket;
+
+use crate::access_tokens::handler;
+
+pub fn create_routes(rocket: Rocket) -> Rocket {
+    rocket.mount(
+        "/access_tokens",
+        routes![handler::insert, handler::all, handler::revoke],
+    )
+}
@@ -0,0 +1,293 @@
+use std::io::Write;
+
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Text;
+use regex::Regex;
+
+use crate::assignments::Assignment;
+
+const DEFAULT_ABSOLUTE_TOLERANCE: f64 = 1e-9;
+const DEFAULT_RELATIVE_TOLERANCE: f64 = 0.0;
+
+/// How the output of a run is compared to the expected output of a test case.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, AsExpression, FromSqlRow)]
+#[serde(rename_all = "snake_case")]
+#[sql_type = "Text"]
+pub enum ComparisonMode {
+    /// Byte by byte equality.
+    Exact,
+    /// Equality of the whitespace separated tokens, so line endings and
+    /// trailing newlines are ignored.
+    WhitespaceInsensitive,
+    /// Equality of the lines regardless of their order.
+    LineOrderInsensitive,
+    /// Tokens that parse as numbers are equal within the assignment's absolute
+    /// or relative tolerance, all other tokens have to be equal.
+    Numeric,
+    /// The expected output is a regular expression the whole output has to match.
+    Regex,
+}
+
+impl Default for ComparisonMode {
+    fn default() -> Self {
+        ComparisonMode::Exact
+    }
+}
+
+impl ComparisonMode {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ComparisonMode::Exact => "exact",
+            ComparisonMode::WhitespaceInsensitive => "whitespace_insensitive",
+            ComparisonMode::LineOrderInsensitive => "line_order_insensitive",
+            ComparisonMode::Numeric => "numeric",
+            ComparisonMode::Regex => "regex",
+        }
+    }
+}
+
+impl ToSql<Text, Pg> for ComparisonMode {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        <str as ToSql<Text, Pg>>::to_sql(self.as_str(), out)
+    }
+}
+
+impl FromSql<Text, Pg> for ComparisonMode {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match <String as FromSql<Text, Pg>>::from_sql(bytes)?.as_str() {
+            "exact" => Ok(ComparisonMode::Exact),
+            "whitespace_insensitive" => Ok(ComparisonMode::WhitespaceInsensitive),
+            "line_order_insensitive" => Ok(ComparisonMode::LineOrderInsensitive),
+            "numeric" => Ok(ComparisonMode::Numeric),
+            "regex" => Ok(ComparisonMode::Regex),
+            other => Err(format!("unknown comparison mode: {}", other).into()),
+        }
+    }
+}
+
+/// Check that the expected outputs of test cases can be compared with the
+/// mode, so that an invalid pattern is rejected when it is saved instead of
+/// failing every run.
+pub fn check_expected_outputs<'a, I>(
+    mode: ComparisonMode,
+    expected_outputs: I,
+) -> Result<(), String>
+where
+    I: IntoIterator<Item = &'a [u8]>,
+{
+    if mode != ComparisonMode::Regex {
+        return Ok(());
+    }
+    for expected in expected_outputs {
+        if let Err(err) = expected_regex(&String::from_utf8_lossy(expected)) {
+            return Err(format!(
+                "expected output is no valid regular expression: {}",
+                err
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Regular expression the whole output has to match.
+fn expected_regex(expected: &str) -> Result<Regex, regex::Error> {
+    Regex::new(&format!(r"\A(?:{})\z", expected))
+}
+
+/// Comparison settings of an assignment.
+pub struct Comparison {
+    pub mode: ComparisonMode,
+    pub absolute_tolerance: f64,
+    pub relative_tolerance: f64,
+}
+
+impl From<&Assignment> for Comparison {
+    fn from(assignment: &Assignment) -> Self {
+        Comparison {
+            mode: assignment.comparison_mode,
+            absolute_tolerance: assignment
+                .absolute_tolerance
+                .unwrap_or(DEFAULT_ABSOLUTE_TOLERANCE),
+            relative_tolerance: assignment
+                .relative_tolerance
+                .unwrap_or(DEFAULT_RELATIVE_TOLERANCE),
+        }
+    }
+}
+
+impl Comparison {
+    pub fn matches(&self, expected: &[u8], actual: &[u8]) -> bool {
+        if self.mode == ComparisonMode::Exact {
+            return expected.eq(actual);
+        }
+        self.text_matches(
+            &String::from_utf8_lossy(expected),
+            &String::from_utf8_lossy(actual),
+        )
+    }
+
+    fn text_matches(&self, expected: &str, actual: &str) -> bool {
+        match self.mode {
+            ComparisonMode::Exact => expected == actual,
+            ComparisonMode::WhitespaceInsensitive => {
+                expected.split_whitespace().eq(actual.split_whitespace())
+            }
+            ComparisonMode::LineOrderInsensitive => sorted_lines(expected) == sorted_lines(actual),
+            ComparisonMode::Numeric => {
+                let expected_tokens: Vec<&str> = expected.split_whitespace().collect();
+                let actual_tokens: Vec<&str> = actual.split_whitespace().collect();
+                expected_tokens.len() == actual_tokens.len()
+                    && expected_tokens
+                        .iter()
+                        .zip(actual_tokens.iter())
+                        .all(|(expected, actual)| self.tokens_match(expected, actual))
+            }
+            ComparisonMode::Regex => expected_regex(expected)
+                .map(|regex| regex.is_match(actual))
+                .unwrap_or(false),
+        }
+    }
+
+    fn tokens_match(&self, expected: &str, actual: &str) -> bool {
+        match (expected.parse::<f64>(), actual.parse::<f64>()) {
+            (Ok(expected), Ok(actual)) => {
+                let tolerance = self
+                    .absolute_tolerance
+                    .max(self.relative_tolerance * expected.abs().max(actual.abs()));
+                expected == actual || (expected - actual).abs() <= tolerance
+            }
+            _ => expected == actual,
+        }
+    }
+}
+
+/// Lines without trailing whitespace and without empty lines, so that line
+/// endings and trailing newlines don't matter.
+fn sorted_lines(output: &str) -> Vec<&str> {
+    let mut lines: Vec<&str> = output
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.is_empty())
+        .collect();
+    lines.sort_unstable();
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comparison(mode: ComparisonMode) -> Comparison {
+        Comparison {
+            mode,
+            absolute_tolerance: DEFAULT_ABSOLUTE_TOLERANCE,
+            relative_tolerance: DEFAULT_RELATIVE_TOLERANCE,
+        }
+    }
+
+    /// Expected output, actual output and whether they match.
+    type Case = (&'static str, &'static str, bool);
+
+    fn assert_cases(comparison: &Comparison, cases: &[Case]) {
+        for (expected, actual, matches) in cases {
+            assert_eq!(
+                comparison.matches(expected.as_bytes(), actual.as_bytes()),
+                *matches,
+                "{:?}: {:?} against {:?}",
+                comparison.mode,
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn exact() {
+        assert_cases(
+            &comparison(ComparisonMode::Exact),
+            &[
+                ("1 2\n", "1 2\n", true),
+                ("1 2\n", "1 2", false),
+                ("1 2\n", "1  2\n", false),
+                ("1 2\n", "1 2\r\n", false),
+                ("", "", true),
+            ],
+        );
+    }
+
+    #[test]
+    fn whitespace_insensitive() {
+        assert_cases(
+            &comparison(ComparisonMode::WhitespaceInsensitive),
+            &[
+                ("1 2\n", "1 2", true),
+                ("1 2\n", "1\t 2\r\n\n", true),
+                ("1 2\n", "12\n", false),
+                ("1 2\n", "2 1\n", false),
+                ("", "\n", true),
+            ],
+        );
+    }
+
+    #[test]
+    fn line_order_insensitive() {
+        assert_cases(
+            &comparison(ComparisonMode::LineOrderInsensitive),
+            &[
+                ("a\nb\n", "b\na\n", true),
+                ("a\nb\n", "b\r\na", true),
+                ("a\nb\n", "a\n\nb  \n", true),
+                ("a\nb\n", "a\na\n", false),
+                ("a\nb\n", "a\nb\nc\n", false),
+                ("a b\n", "b a\n", false),
+            ],
+        );
+    }
+
+    #[test]
+    fn numeric() {
+        let mut comparison = comparison(ComparisonMode::Numeric);
+        comparison.absolute_tolerance = 0.01;
+        assert_cases(
+            &comparison,
+            &[
+                ("3.14 ok\n", "3.141 ok", true),
+                ("3.14 ok\n", "3.16 ok", false),
+                ("3.14 ok\n", "3.14 OK", false),
+                ("1e3\n", "1000.0\n", true),
+                ("1 2\n", "1\n", false),
+            ],
+        );
+        comparison.absolute_tolerance = 0.0;
+        comparison.relative_tolerance = 0.01;
+        assert_cases(
+            &comparison,
+            &[("1000\n", "1009\n", true), ("1000\n", "1011\n", false)],
+        );
+    }
+
+    #[test]
+    fn regex() {
+        assert_cases(
+            &comparison(ComparisonMode::Regex),
+            &[
+                (r"\d+\n", "42\n", true),
+                (r"\d+", "42\n", false),
+                (r"\d+", "x42", false),
+                (r"a|b", "b", true),
+                (r"a|b", "ab", false),
+                (r"(", "(", false),
+            ],
+        );
+    }
+
+    #[test]
+    fn invalid_patterns_are_rejected_in_regex_mode_only() {
+        let outputs: Vec<&[u8]> = vec![b"\\d+", b"("];
+        assert!(check_expected_outputs(ComparisonMode::Regex, outputs.clone()).is_err());
+        assert!(check_expected_outputs(ComparisonMode::Regex, vec![&b"\\d+"[..]]).is_ok());
+        assert!(check_expected_outputs(ComparisonMode::Exact, outputs).is_ok());
+    }
+}
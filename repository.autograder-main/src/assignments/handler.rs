@@ -7,18 +7,22 @@ use uuid::Uuid;
 
 use crate::assignments;
 use crate::assignments::{Assignment, InsertableAssignment, UpdatableAssignment};
+use crate::audit::{AuditContext, RequestId};
 use crate::auth::Auth;
 use crate::connection::{to_status_created, DbConn};
 
 #[post("/", format = "application/json", data = "<insertable_assignment>")]
 pub fn insert(
     auth: Auth,
+    request_id: RequestId,
     insertable_assignment: Json<InsertableAssignment>,
     connection: DbConn,
 ) -> Result<status::Created<Json<Assignment>>, rocket::http::Status> {
+    let audit = AuditContext::user(&auth, request_id);
     let result = assignments::repository::insert(
-        insertable_assignment.into_inner().into(),
+        insertable_assignment.into_inner(),
         auth,
+        &audit,
         &connection,
     );
     let result: Result<Json<Assignment>, rocket::http::Status> = result.into();
@@ -28,10 +32,12 @@ pub fn insert(
 #[patch("/<id>", format = "application/json", data = "<updatable_assignment>")]
 pub fn update(
     auth: Auth,
+    request_id: RequestId,
     id: String,
     updatable_assignment: Json<UpdatableAssignment>,
     connection: DbConn,
 ) -> Result<Json<Assignment>, rocket::http::Status> {
+    let audit = AuditContext::user(&auth, request_id);
     Uuid::from_str(&id)
         .map_err(|_| Status::BadRequest)
         .and_then(|uuid| {
@@ -39,6 +45,7 @@ pub fn update(
                 uuid,
                 auth,
                 updatable_assignment.into_inner(),
+                &audit,
                 &connection,
             )
             .into()
@@ -1,10 +1,14 @@
 use chrono::{NaiveDateTime, Utc};
 use uuid::Uuid;
 
-use crate::connection::{deserialize_base64, deserialize_optional_base64, serialize_base64};
+use crate::assignments::comparison::ComparisonMode;
+use crate::connection::deserialize_nullable;
+use crate::extensions::Extension;
+use crate::test_cases::InsertableTestCase;
 
 use super::schema::assignments;
 
+pub mod comparison;
 pub mod handler;
 pub mod repository;
 pub mod router;
@@ -13,42 +17,402 @@ pub mod router;
 pub struct Assignment {
     pub id: Uuid,
     pub user_id: Uuid,
-    #[serde(serialize_with = "serialize_base64")]
-    pub encoded_input: Vec<u8>,
-    #[serde(serialize_with = "serialize_base64")]
-    pub encoded_output: Vec<u8>,
     pub updated: NaiveDateTime,
+    pub comparison_mode: ComparisonMode,
+    pub absolute_tolerance: Option<f64>,
+    pub relative_tolerance: Option<f64>,
+    pub opens_at: Option<NaiveDateTime>,
+    pub due_at: Option<NaiveDateTime>,
+    pub closes_at: Option<NaiveDateTime>,
+    pub late_penalty: f64,
+    pub max_attempts: Option<i16>,
+    pub run_timeout_seconds: Option<i32>,
+    pub max_run_retries: i16,
 }
 
-#[derive(Deserialize, Insertable)]
-#[table_name = "assignments"]
+#[derive(Deserialize)]
 pub struct InsertableAssignment {
     pub id: Uuid,
     pub user_id: Uuid,
-    #[serde(deserialize_with = "deserialize_base64")]
-    pub encoded_input: Vec<u8>,
-    #[serde(deserialize_with = "deserialize_base64")]
-    pub encoded_output: Vec<u8>,
+    #[serde(default)]
+    pub comparison_mode: ComparisonMode,
+    #[serde(default)]
+    pub absolute_tolerance: Option<f64>,
+    #[serde(default)]
+    pub relative_tolerance: Option<f64>,
+    #[serde(default)]
+    pub opens_at: Option<NaiveDateTime>,
+    #[serde(default)]
+    pub due_at: Option<NaiveDateTime>,
+    #[serde(default)]
+    pub closes_at: Option<NaiveDateTime>,
+    #[serde(default)]
+    pub late_penalty: f64,
+    #[serde(default)]
+    pub max_attempts: Option<i16>,
+    #[serde(default)]
+    pub run_timeout_seconds: Option<i32>,
+    #[serde(default)]
+    pub max_run_retries: i16,
+    #[serde(default)]
+    pub test_cases: Vec<InsertableTestCase>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, AsChangeset)]
+#[table_name = "assignments"]
 pub struct UpdatableAssignment {
-    #[serde(deserialize_with = "deserialize_optional_base64")]
     #[serde(default)]
-    pub encoded_input: Option<Vec<u8>>,
-    #[serde(deserialize_with = "deserialize_optional_base64")]
+    pub comparison_mode: Option<ComparisonMode>,
+    #[serde(default)]
+    pub absolute_tolerance: Option<f64>,
+    #[serde(default)]
+    pub relative_tolerance: Option<f64>,
+    #[serde(deserialize_with = "deserialize_nullable")]
+    #[serde(default)]
+    pub opens_at: Option<Option<NaiveDateTime>>,
+    #[serde(deserialize_with = "deserialize_nullable")]
+    #[serde(default)]
+    pub due_at: Option<Option<NaiveDateTime>>,
+    #[serde(deserialize_with = "deserialize_nullable")]
+    #[serde(default)]
+    pub closes_at: Option<Option<NaiveDateTime>>,
     #[serde(default)]
-    pub encoded_output: Option<Vec<u8>>,
+    pub late_penalty: Option<f64>,
+    #[serde(deserialize_with = "deserialize_nullable")]
+    #[serde(default)]
+    pub max_attempts: Option<Option<i16>>,
+    #[serde(deserialize_with = "deserialize_nullable")]
+    #[serde(default)]
+    pub run_timeout_seconds: Option<Option<i32>>,
+    #[serde(default)]
+    pub max_run_retries: Option<i16>,
 }
 
-impl From<InsertableAssignment> for Assignment {
-    fn from(insertable_assignment: InsertableAssignment) -> Assignment {
+impl From<&InsertableAssignment> for Assignment {
+    fn from(insertable_assignment: &InsertableAssignment) -> Assignment {
         Assignment {
             id: insertable_assignment.id,
-            encoded_output: insertable_assignment.encoded_output,
-            encoded_input: insertable_assignment.encoded_input,
             updated: Utc::now().naive_utc(),
             user_id: insertable_assignment.user_id,
+            comparison_mode: insertable_assignment.comparison_mode,
+            absolute_tolerance: insertable_assignment.absolute_tolerance,
+            relative_tolerance: insertable_assignment.relative_tolerance,
+            opens_at: insertable_assignment.opens_at,
+            due_at: insertable_assignment.due_at,
+            closes_at: insertable_assignment.closes_at,
+            late_penalty: insertable_assignment.late_penalty,
+            max_attempts: insertable_assignment.max_attempts,
+            run_timeout_seconds: insertable_assignment.run_timeout_seconds,
+            max_run_retries: insertable_assignment.max_run_retries,
+        }
+    }
+}
+
+impl UpdatableAssignment {
+    /// Assignment as it will be after the update, to validate it before it is
+    /// stored.
+    pub fn apply_to(&self, assignment: &Assignment) -> Assignment {
+        Assignment {
+            id: assignment.id,
+            user_id: assignment.user_id,
+            updated: assignment.updated,
+            comparison_mode: self.comparison_mode.unwrap_or(assignment.comparison_mode),
+            absolute_tolerance: self.absolute_tolerance.or(assignment.absolute_tolerance),
+            relative_tolerance: self.relative_tolerance.or(assignment.relative_tolerance),
+            opens_at: self.opens_at.unwrap_or(assignment.opens_at),
+            due_at: self.due_at.unwrap_or(assignment.due_at),
+            closes_at: self.closes_at.unwrap_or(assignment.closes_at),
+            late_penalty: self.late_penalty.unwrap_or(assignment.late_penalty),
+            max_attempts: self.max_attempts.unwrap_or(assignment.max_attempts),
+            run_timeout_seconds: self
+                .run_timeout_seconds
+                .unwrap_or(assignment.run_timeout_seconds),
+            max_run_retries: self.max_run_retries.unwrap_or(assignment.max_run_retries),
         }
     }
 }
+
+impl Assignment {
+    /// Reason why the settings of an assignment are invalid, if any. The
+    /// deadlines have to be in order and the limits positive.
+    pub fn check(&self) -> Result<(), String> {
+        let deadlines = [
+            ("opens_at", self.opens_at, "due_at", self.due_at),
+            ("due_at", self.due_at, "closes_at", self.closes_at),
+            ("opens_at", self.opens_at, "closes_at", self.closes_at),
+        ];
+        for (first_name, first, second_name, second) in deadlines.iter() {
+            if let (Some(first), Some(second)) = (first, second) {
+                if first > second {
+                    return Err(format!("{} is after {}", first_name, second_name));
+                }
+            }
+        }
+        if !(0.0..=1.0).contains(&self.late_penalty) {
+            return Err("late_penalty must be between 0 and 1".to_owned());
+        }
+        let tolerances = [
+            ("absolute_tolerance", self.absolute_tolerance),
+            ("relative_tolerance", self.relative_tolerance),
+        ];
+        for (name, tolerance) in tolerances.iter() {
+            if tolerance.map_or(false, |tolerance| tolerance < 0.0) {
+                return Err(format!("{} must not be negative", name));
+            }
+        }
+        if self
+            .max_attempts
+            .map_or(false, |max_attempts| max_attempts <= 0)
+        {
+            return Err("max_attempts must be positive".to_owned());
+        }
+        if self
+            .run_timeout_seconds
+            .map_or(false, |run_timeout_seconds| run_timeout_seconds <= 0)
+        {
+            return Err("run_timeout_seconds must be positive".to_owned());
+        }
+        if self.max_run_retries < 0 {
+            return Err("max_run_retries must not be negative".to_owned());
+        }
+        Ok(())
+    }
+
+    /// Due date of a student, which is postponed if an extension is due after
+    /// it. An extension never makes a student late earlier.
+    pub fn due_at_with(&self, extension: Option<&Extension>) -> Option<NaiveDateTime> {
+        match (self.due_at, extension) {
+            (Some(due_at), Some(extension)) => Some(due_at.max(extension.due_at)),
+            (due_at, extension) => due_at.or_else(|| extension.map(|extension| extension.due_at)),
+        }
+    }
+
+    /// Hard deadline of a student, which is postponed if an extension is due
+    /// after it.
+    pub fn closes_at_with(&self, extension: Option<&Extension>) -> Option<NaiveDateTime> {
+        match (self.closes_at, extension) {
+            (Some(closes_at), Some(extension)) => Some(closes_at.max(extension.due_at)),
+            (closes_at, _) => closes_at,
+        }
+    }
+
+    /// Reason why a submission at the given time is not accepted, if any.
+    pub fn rejects_submission_at(
+        &self,
+        time: NaiveDateTime,
+        extension: Option<&Extension>,
+    ) -> Option<String> {
+        match (self.opens_at, self.closes_at_with(extension)) {
+            (Some(opens_at), _) if time < opens_at => {
+                Some(format!("assignment opens at {}", opens_at))
+            }
+            (_, Some(closes_at)) if time > closes_at => {
+                Some(format!("assignment closed at {}", closes_at))
+            }
+            _ => None,
+        }
+    }
+
+    /// Reason why a student who already used `update_count + 1` attempts can't
+    /// submit again, if any.
+    pub fn rejects_resubmission(&self, update_count: i16) -> Option<String> {
+        match self.max_attempts {
+            Some(max_attempts) if update_count + 1 >= max_attempts => {
+                Some(format!("all {} attempts are used", max_attempts))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn is_late_at(&self, time: NaiveDateTime, extension: Option<&Extension>) -> bool {
+        self.due_at_with(extension)
+            .map_or(false, |due_at| time > due_at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::NaiveDate;
+
+    fn day(day: u32) -> Option<NaiveDateTime> {
+        Some(NaiveDate::from_ymd(2021, 1, day).and_hms(12, 0, 0))
+    }
+
+    fn assignment() -> Assignment {
+        Assignment {
+            id: Uuid::nil(),
+            user_id: Uuid::nil(),
+            updated: NaiveDate::from_ymd(2021, 1, 1).and_hms(0, 0, 0),
+            comparison_mode: ComparisonMode::Exact,
+            absolute_tolerance: None,
+            relative_tolerance: None,
+            opens_at: day(1),
+            due_at: day(2),
+            closes_at: day(3),
+            late_penalty: 0.5,
+            max_attempts: Some(3),
+            run_timeout_seconds: Some(60),
+            max_run_retries: 0,
+        }
+    }
+
+    fn assert_invalid(assignment: Assignment, field: &str) {
+        match assignment.check() {
+            Err(message) => assert!(message.contains(field), "{}", message),
+            Ok(()) => panic!("{} is not checked", field),
+        }
+    }
+
+    #[test]
+    fn valid() {
+        assert_eq!(assignment().check(), Ok(()));
+        assert_eq!(
+            Assignment {
+                opens_at: None,
+                due_at: day(2),
+                closes_at: day(2),
+                late_penalty: 1.0,
+                max_attempts: None,
+                run_timeout_seconds: None,
+                ..assignment()
+            }
+            .check(),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn deadlines_in_order() {
+        assert_invalid(
+            Assignment {
+                opens_at: day(3),
+                due_at: day(2),
+                closes_at: None,
+                ..assignment()
+            },
+            "opens_at",
+        );
+        assert_invalid(
+            Assignment {
+                due_at: day(4),
+                ..assignment()
+            },
+            "due_at",
+        );
+        assert_invalid(
+            Assignment {
+                opens_at: day(4),
+                due_at: None,
+                ..assignment()
+            },
+            "opens_at",
+        );
+    }
+
+    #[test]
+    fn late_penalty_between_0_and_1() {
+        assert_invalid(
+            Assignment {
+                late_penalty: -0.1,
+                ..assignment()
+            },
+            "late_penalty",
+        );
+        assert_invalid(
+            Assignment {
+                late_penalty: 1.5,
+                ..assignment()
+            },
+            "late_penalty",
+        );
+    }
+
+    #[test]
+    fn tolerances_not_negative() {
+        assert_invalid(
+            Assignment {
+                absolute_tolerance: Some(-1.0),
+                ..assignment()
+            },
+            "absolute_tolerance",
+        );
+        assert_invalid(
+            Assignment {
+                relative_tolerance: Some(-1.0),
+                ..assignment()
+            },
+            "relative_tolerance",
+        );
+    }
+
+    #[test]
+    fn limits_positive() {
+        assert_invalid(
+            Assignment {
+                max_attempts: Some(0),
+                ..assignment()
+            },
+            "max_attempts",
+        );
+        assert_invalid(
+            Assignment {
+                run_timeout_seconds: Some(0),
+                ..assignment()
+            },
+            "run_timeout_seconds",
+        );
+        assert_invalid(
+            Assignment {
+                max_run_retries: -1,
+                ..assignment()
+            },
+            "max_run_retries",
+        );
+    }
+
+    #[test]
+    fn extensions_only_postpone_deadlines() {
+        let extension = |due_at: Option<NaiveDateTime>| Extension {
+            assignment_id: Uuid::nil(),
+            user_id: Uuid::nil(),
+            due_at: due_at.unwrap(),
+            updated: due_at.unwrap(),
+        };
+        let assignment = assignment();
+        assert_eq!(assignment.due_at_with(None), day(2));
+        assert_eq!(assignment.due_at_with(Some(&extension(day(1)))), day(2));
+        assert_eq!(assignment.due_at_with(Some(&extension(day(4)))), day(4));
+        assert_eq!(assignment.closes_at_with(Some(&extension(day(1)))), day(3));
+        assert_eq!(assignment.closes_at_with(Some(&extension(day(4)))), day(4));
+        assert!(!assignment.is_late_at(day(2).unwrap(), Some(&extension(day(1)))));
+        let without_due_at = Assignment {
+            due_at: None,
+            ..assignment
+        };
+        assert_eq!(without_due_at.due_at_with(Some(&extension(day(4)))), day(4));
+    }
+
+    #[test]
+    fn update_is_checked_as_applied() {
+        let update = UpdatableAssignment {
+            comparison_mode: None,
+            absolute_tolerance: None,
+            relative_tolerance: None,
+            opens_at: None,
+            due_at: Some(day(4)),
+            closes_at: None,
+            late_penalty: None,
+            max_attempts: None,
+            run_timeout_seconds: None,
+            max_run_retries: None,
+        };
+        assert_invalid(update.apply_to(&assignment()), "due_at");
+        let update = UpdatableAssignment {
+            closes_at: Some(None),
+            ..update
+        };
+        assert_eq!(update.apply_to(&assignment()).check(), Ok(()));
+    }
+}
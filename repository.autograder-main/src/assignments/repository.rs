@@ -2,38 +2,72 @@ use chrono::Utc;
 use diesel::prelude::*;
 use uuid::Uuid;
 
-use crate::assignments::{Assignment, UpdatableAssignment};
-use crate::auth::Auth;
+use crate::assignments::comparison::{check_expected_outputs, ComparisonMode};
+use crate::assignments::{Assignment, InsertableAssignment, UpdatableAssignment};
+use crate::audit;
+use crate::audit::AuditContext;
+use crate::auth::policy::{Action, Relation, Resource};
+use crate::auth::{policy, Auth};
+use crate::collaborators;
 use crate::connection::{RepositoryError, RepositoryQueryResult};
-use crate::schema::assignments;
+use crate::enrollments;
+use crate::schema::{assignments, test_cases as test_cases_schema};
+use crate::test_cases;
 
 pub fn insert(
-    assignment: Assignment,
+    insertable_assignment: InsertableAssignment,
     auth: Auth,
+    audit: &AuditContext,
     connection: &PgConnection,
 ) -> RepositoryQueryResult<Assignment> {
-    if auth.is_student {
-        return RepositoryQueryResult::Err(RepositoryError::Unauthorized(
-            "student can't create assignment".to_owned(),
-        ));
+    if let Err(error) = policy::authorize(
+        &auth,
+        Relation::Unrelated,
+        Action::Create,
+        Resource::Assignment,
+    ) {
+        return RepositoryQueryResult::Err(error);
     }
-    diesel::insert_into(assignments::table)
-        .values(&assignment)
-        .get_result(connection)
+    if let Err(message) = check_expected_outputs(
+        insertable_assignment.comparison_mode,
+        insertable_assignment
+            .test_cases
+            .iter()
+            .map(|test_case| test_case.encoded_output.as_slice()),
+    ) {
+        return RepositoryQueryResult::Err(RepositoryError::Invalid(message));
+    }
+    let assignment = Assignment::from(&insertable_assignment);
+    if let Err(message) = assignment.check() {
+        return RepositoryQueryResult::Err(RepositoryError::Invalid(message));
+    }
+    connection
+        .transaction::<_, diesel::result::Error, _>(|| {
+            let assignment: Assignment = diesel::insert_into(assignments::table)
+                .values(&assignment)
+                .get_result(connection)?;
+            test_cases::repository::insert_all(
+                assignment.id,
+                insertable_assignment.test_cases,
+                connection,
+            )?;
+            audit::repository::record(
+                audit,
+                "create",
+                Resource::Assignment,
+                assignment.id,
+                None,
+                Some(&assignment),
+                connection,
+            )?;
+            Ok(assignment)
+        })
         .into()
 }
 
 pub fn get(id: Uuid, auth: Auth, connection: &PgConnection) -> RepositoryQueryResult<Assignment> {
-    // TODO this can be refactored with a call to the assignment service. If an assignment is public,
-    // it can be viewed by anyone. If not, by the teacher, superuser and the affiliated students.
-    match is_allowed_to_change(id, auth, connection) {
-        Ok(false) => {
-            return RepositoryQueryResult::Err(RepositoryError::Unauthorized(
-                "user can't see assignment".to_owned(),
-            ))
-        }
-        Err(_) => return RepositoryQueryResult::Err(RepositoryError::NotFound),
-        _ => {}
+    if let Err(error) = authorize(id, &auth, Action::Read, Resource::Assignment, connection) {
+        return RepositoryQueryResult::Err(error);
     }
     assignments::table
         .find(id)
@@ -41,57 +75,105 @@ pub fn get(id: Uuid, auth: Auth, connection: &PgConnection) -> RepositoryQueryRe
         .into()
 }
 
-pub fn is_allowed_to_change(id: Uuid, auth: Auth, connection: &PgConnection) -> QueryResult<bool> {
-    if auth.is_superuser {
-        return QueryResult::Ok(true);
+/// Relation of the user to an assignment and everything that belongs to it,
+/// as its owner, one of its collaborators or one of its students.
+pub fn relation_of(id: Uuid, auth: &Auth, connection: &PgConnection) -> QueryResult<Relation> {
+    if owner_id(id, connection)? == auth.user_id {
+        return Ok(Relation::AssignmentOwner);
+    }
+    if let Some(role) = collaborators::repository::find_role(id, auth.user_id, connection)? {
+        return Ok(Relation::from(role));
     }
+    if enrollments::repository::is_enrolled(id, auth.user_id, connection)? {
+        Ok(Relation::Enrolled)
+    } else {
+        Ok(Relation::Unrelated)
+    }
+}
+
+pub fn owner_id(id: Uuid, connection: &PgConnection) -> QueryResult<Uuid> {
     assignments::table
         .find(id)
-        .get_result::<Assignment>(connection)
-        .map(|assignment| assignment.user_id == auth.user_id)
+        .select(assignments::user_id)
+        .get_result(connection)
+}
+
+/// Check with the policy that the user can do the action on a resource of the
+/// assignment. Fails with `NotFound` if the assignment doesn't exist.
+pub fn authorize(
+    id: Uuid,
+    auth: &Auth,
+    action: Action,
+    resource: Resource,
+    connection: &PgConnection,
+) -> Result<(), RepositoryError<diesel::result::Error>> {
+    match relation_of(id, auth, connection) {
+        Ok(relation) => policy::authorize(auth, relation, action, resource),
+        Err(_) => Err(RepositoryError::NotFound),
+    }
+}
+
+pub fn comparison_mode(id: Uuid, connection: &PgConnection) -> QueryResult<ComparisonMode> {
+    assignments::table
+        .find(id)
+        .select(assignments::comparison_mode)
+        .get_result(connection)
+}
+
+pub fn max_attempts(id: Uuid, connection: &PgConnection) -> QueryResult<Option<i16>> {
+    assignments::table
+        .find(id)
+        .select(assignments::max_attempts)
+        .get_result(connection)
 }
 
 pub fn update(
     id: Uuid,
     auth: Auth,
     updatable_assignment: UpdatableAssignment,
+    audit: &AuditContext,
     connection: &PgConnection,
 ) -> RepositoryQueryResult<Assignment> {
-    match is_allowed_to_change(id, auth, connection) {
-        Ok(false) => {
-            return RepositoryQueryResult::Err(RepositoryError::Unauthorized(
-                "user can't update assignment".to_owned(),
-            ))
-        }
-        Err(_) => return RepositoryQueryResult::Err(RepositoryError::NotFound),
-        _ => {}
+    if let Err(error) = authorize(id, &auth, Action::Update, Resource::Assignment, connection) {
+        return RepositoryQueryResult::Err(error);
     }
-    let entry = diesel::update(assignments::table.find(id));
-    let time = Utc::now().naive_utc();
-    match (
-        updatable_assignment.encoded_input,
-        updatable_assignment.encoded_output,
-    ) {
-        (Some(encoded_input), Some(encoded_output)) => entry
-            .set((
-                assignments::encoded_output.eq(encoded_output),
-                assignments::encoded_input.eq(encoded_input),
-                assignments::updated.eq(time),
-            ))
-            .get_result(connection),
-        (Some(encoded_input), None) => entry
-            .set((
-                assignments::encoded_input.eq(encoded_input),
-                assignments::updated.eq(time),
-            ))
-            .get_result(connection),
-        (None, Some(encoded_output)) => entry
-            .set((
-                assignments::encoded_output.eq(encoded_output),
-                assignments::updated.eq(time),
-            ))
-            .get_result(connection),
-        _ => assignments::table.find(id).get_result(connection),
-    }
-    .into()
+    connection
+        .transaction::<_, RepositoryError<diesel::result::Error>, _>(|| {
+            let before: Assignment = assignments::table
+                .find(id)
+                .for_update()
+                .get_result(connection)?;
+            updatable_assignment
+                .apply_to(&before)
+                .check()
+                .map_err(RepositoryError::Invalid)?;
+            let after: Assignment = diesel::update(assignments::table.find(id))
+                .set((
+                    &updatable_assignment,
+                    assignments::updated.eq(Utc::now().naive_utc()),
+                ))
+                .get_result(connection)?;
+            if after.comparison_mode != before.comparison_mode {
+                let expected_outputs: Vec<Vec<u8>> = test_cases_schema::table
+                    .filter(test_cases_schema::assignment_id.eq(id))
+                    .select(test_cases_schema::encoded_output)
+                    .load(connection)?;
+                check_expected_outputs(
+                    after.comparison_mode,
+                    expected_outputs.iter().map(Vec::as_slice),
+                )
+                .map_err(RepositoryError::Invalid)?;
+            }
+            audit::repository::record(
+                audit,
+                "update",
+                Resource::Assignment,
+                id,
+                Some(&before),
+                Some(&after),
+                connection,
+            )?;
+            Ok(after)
+        })
+        .into()
 }
@@ -0,0 +1,58 @@
+use std::str::FromStr;
+
+use rocket::http::Status;
+use rocket::request::Form;
+use rocket_contrib::json::Json;
+
+use crate::audit;
+use crate::audit::{AuditEvent, AuditEventFilter};
+use crate::auth::Auth;
+use crate::connection::{DbConn, Page, PageRequest};
+
+/// Query of the audit log. Times are UTC, like `2021-01-06T10:00:00`.
+#[derive(FromForm)]
+pub struct AuditEventQuery {
+    actor_id: Option<String>,
+    resource_type: Option<String>,
+    resource_id: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    page: Option<i64>,
+    size: Option<i64>,
+}
+
+impl AuditEventQuery {
+    fn filter(&self) -> Result<AuditEventFilter, Status> {
+        Ok(AuditEventFilter {
+            actor_id: parse(&self.actor_id)?,
+            resource_type: self.resource_type.clone(),
+            resource_id: parse(&self.resource_id)?,
+            from: parse(&self.from)?,
+            to: parse(&self.to)?,
+        })
+    }
+}
+
+fn parse<T: FromStr>(value: &Option<String>) -> Result<Option<T>, Status> {
+    match value {
+        Some(value) => T::from_str(value).map(Some).map_err(|_| Status::BadRequest),
+        None => Ok(None),
+    }
+}
+
+#[get("/?<query..>")]
+pub fn all(
+    auth: Auth,
+    query: Form<AuditEventQuery>,
+    connection: DbConn,
+) -> Result<Json<Page<AuditEvent>>, rocket::http::Status> {
+    query.filter().and_then(|filter| {
+        audit::repository::all(
+            filter,
+            auth,
+            PageRequest::new(query.page, query.size),
+            &connection,
+        )
+        .into()
+    })
+}
@@ -0,0 +1,167 @@
+use std::io::Write;
+
+use chrono::NaiveDateTime;
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Text;
+use rocket::request::{self, FromRequest, Request};
+use rocket::Outcome;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::auth::Auth;
+
+use super::schema::audit_events;
+
+pub mod handler;
+pub mod repository;
+pub mod router;
+
+const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// Who made a change.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, AsExpression, FromSqlRow)]
+#[serde(rename_all = "snake_case")]
+#[sql_type = "Text"]
+pub enum ActorKind {
+    /// User of a token of the core service, identified by the actor id.
+    User,
+    /// Runner reporting a run with its callback token.
+    Runner,
+    /// Background work of this service, like ending overdue runs.
+    System,
+}
+
+impl ActorKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ActorKind::User => "user",
+            ActorKind::Runner => "runner",
+            ActorKind::System => "system",
+        }
+    }
+}
+
+impl ToSql<Text, Pg> for ActorKind {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        <str as ToSql<Text, Pg>>::to_sql(self.as_str(), out)
+    }
+}
+
+impl FromSql<Text, Pg> for ActorKind {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match <String as FromSql<Text, Pg>>::from_sql(bytes)?.as_str() {
+            "user" => Ok(ActorKind::User),
+            "runner" => Ok(ActorKind::Runner),
+            "system" => Ok(ActorKind::System),
+            other => Err(format!("unknown actor kind: {}", other).into()),
+        }
+    }
+}
+
+/// Id of the request a change was made in, from the `X-Request-Id` header
+/// set by the gateway, or a new one.
+pub struct RequestId(pub String);
+
+impl<'a, 'r> FromRequest<'a, 'r> for RequestId {
+    type Error = ();
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<RequestId, Self::Error> {
+        let request_id = request
+            .headers()
+            .get_one(REQUEST_ID_HEADER)
+            .map(str::to_owned)
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+        Outcome::Success(RequestId(request_id))
+    }
+}
+
+/// Actor and request the changes of a repository call are recorded with.
+#[derive(Debug, Clone)]
+pub struct AuditContext {
+    pub actor_kind: ActorKind,
+    pub actor_id: Option<Uuid>,
+    pub request_id: Option<String>,
+}
+
+impl AuditContext {
+    pub fn user(auth: &Auth, request_id: RequestId) -> Self {
+        AuditContext {
+            actor_kind: ActorKind::User,
+            actor_id: Some(auth.user_id),
+            request_id: Some(request_id.0),
+        }
+    }
+
+    pub fn runner(request_id: Option<RequestId>) -> Self {
+        AuditContext {
+            actor_kind: ActorKind::Runner,
+            actor_id: None,
+            request_id: request_id.map(|request_id| request_id.0),
+        }
+    }
+
+    /// Changes made in the background on behalf of a user, like a regrade.
+    pub fn on_behalf_of(user_id: Uuid) -> Self {
+        AuditContext {
+            actor_kind: ActorKind::User,
+            actor_id: Some(user_id),
+            request_id: None,
+        }
+    }
+
+    pub fn system() -> Self {
+        AuditContext {
+            actor_kind: ActorKind::System,
+            actor_id: None,
+            request_id: None,
+        }
+    }
+}
+
+/// Recorded change of a resource. The resource is recorded by the digests of
+/// its state before and after the change, which tell whether and when it
+/// changed without copying submitted code into the log.
+#[derive(Identifiable, Queryable, PartialEq, Debug, Serialize)]
+pub struct AuditEvent {
+    pub id: Uuid,
+    pub actor_kind: ActorKind,
+    pub actor_id: Option<Uuid>,
+    pub action: String,
+    pub resource_type: String,
+    pub resource_id: Uuid,
+    pub before_digest: Option<String>,
+    pub after_digest: Option<String>,
+    pub request_id: Option<String>,
+    pub created: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "audit_events"]
+pub struct InsertableAuditEvent {
+    pub actor_kind: ActorKind,
+    pub actor_id: Option<Uuid>,
+    pub action: String,
+    pub resource_type: String,
+    pub resource_id: Uuid,
+    pub before_digest: Option<String>,
+    pub after_digest: Option<String>,
+    pub request_id: Option<String>,
+}
+
+/// Filters of the audit log, which are all optional.
+#[derive(Default)]
+pub struct AuditEventFilter {
+    pub actor_id: Option<Uuid>,
+    pub resource_type: Option<String>,
+    pub resource_id: Option<Uuid>,
+    pub from: Option<NaiveDateTime>,
+    pub to: Option<NaiveDateTime>,
+}
+
+/// Hex SHA-256 of the JSON of a resource as the API returns it.
+pub fn digest<T: Serialize>(value: &T) -> String {
+    let json = serde_json::to_vec(value).expect("resource can't be serialized");
+    format!("{:x}", Sha256::digest(&json))
+}
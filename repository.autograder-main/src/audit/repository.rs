@@ -0,0 +1,85 @@
+use diesel::prelude::*;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::audit::{digest, AuditContext, AuditEvent, AuditEventFilter, InsertableAuditEvent};
+use crate::auth::policy::{Action, Relation, Resource};
+use crate::auth::{policy, Auth};
+use crate::connection::{Page, PageRequest, RepositoryQueryResult};
+use crate::schema::audit_events;
+
+/// Record a change of a resource with the digests of its state before and
+/// after it. Called in the transaction of the change, so a change is only
+/// stored together with its event.
+pub fn record<T: Serialize>(
+    context: &AuditContext,
+    action: &str,
+    resource: Resource,
+    resource_id: Uuid,
+    before: Option<&T>,
+    after: Option<&T>,
+    connection: &PgConnection,
+) -> QueryResult<()> {
+    diesel::insert_into(audit_events::table)
+        .values(&InsertableAuditEvent {
+            actor_kind: context.actor_kind,
+            actor_id: context.actor_id,
+            action: action.to_owned(),
+            resource_type: resource.as_str().to_owned(),
+            resource_id,
+            before_digest: before.map(digest),
+            after_digest: after.map(digest),
+            request_id: context.request_id.clone(),
+        })
+        .execute(connection)
+        .map(|_| ())
+}
+
+/// Events matching the filter, newest first. Only superusers can read the
+/// audit log.
+pub fn all(
+    filter: AuditEventFilter,
+    auth: Auth,
+    page_request: PageRequest,
+    connection: &PgConnection,
+) -> RepositoryQueryResult<Page<AuditEvent>> {
+    if let Err(error) = policy::authorize(
+        &auth,
+        Relation::Unrelated,
+        Action::List,
+        Resource::AuditEvent,
+    ) {
+        return RepositoryQueryResult::Err(error);
+    }
+    let total = filtered(&filter).count().get_result(connection);
+    total
+        .and_then(|total| {
+            filtered(&filter)
+                .order(audit_events::created.desc())
+                .limit(page_request.size)
+                .offset(page_request.offset())
+                .load(connection)
+                .map(|events| page_request.to_page(events, total))
+        })
+        .into()
+}
+
+fn filtered(filter: &AuditEventFilter) -> audit_events::BoxedQuery<'static, diesel::pg::Pg> {
+    let mut query = audit_events::table.into_boxed();
+    if let Some(actor_id) = filter.actor_id {
+        query = query.filter(audit_events::actor_id.eq(actor_id));
+    }
+    if let Some(resource_type) = &filter.resource_type {
+        query = query.filter(audit_events::resource_type.eq(resource_type.clone()));
+    }
+    if let Some(resource_id) = filter.resource_id {
+        query = query.filter(audit_events::resource_id.eq(resource_id));
+    }
+    if let Some(from) = filter.from {
+        query = query.filter(audit_events::created.ge(from));
+    }
+    if let Some(to) = filter.to {
+        query = query.filter(audit_events::created.lt(to));
+    }
+    query
+}
@@ -0,0 +1,7 @@
+use rocket::Rocket;
+
+use crate::audit::handler;
+
+pub fn create_routes(rocket: Rocket) -> Rocket {
+    rocket.mount("/audit_events", routes![handler::all])
+}
@@ -0,0 +1,261 @@
+use std::env;
+use std::fs;
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use jsonwebtoken as jwt;
+use jsonwebtoken::{DecodingKey, Validation};
+use reqwest::blocking::Client;
+use serde::de::DeserializeOwned;
+
+const PUBLIC_KEY_ENV: &str = "CORE_PUBLIC_KEY";
+const JWKS_ENV: &str = "CORE_JWKS";
+const JWKS_REFRESH_ENV: &str = "CORE_JWKS_REFRESH_SECONDS";
+const JWKS_GRACE_ENV: &str = "CORE_JWKS_GRACE_SECONDS";
+const DEFAULT_JWKS_REFRESH_SECONDS: u64 = 5 * 60;
+/// How long a key removed from the key set is still accepted.
+const DEFAULT_JWKS_GRACE_SECONDS: u64 = 60 * 60;
+const JWKS_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Public keys the tokens of the core service are verified with. The keys are
+/// parsed once, from the PEM in `CORE_PUBLIC_KEY` and the JWKS at `CORE_JWKS`,
+/// which is read again periodically. A key that is removed from the JWKS is
+/// still accepted during a grace period, so the core service can rotate its
+/// signing key without a redeploy of this service.
+#[derive(Clone)]
+pub struct KeyStore {
+    keys: Arc<RwLock<Vec<VerificationKey>>>,
+    source: Option<JwksSource>,
+    refresh_interval: Duration,
+    grace_period: Duration,
+}
+
+struct VerificationKey {
+    kid: Option<String>,
+    key: DecodingKey<'static>,
+    /// Whether the key comes from the JWKS and can be rotated.
+    rotating: bool,
+    /// When the key was removed from the JWKS.
+    retired_at: Option<Instant>,
+}
+
+/// Location of the JWKS. A local file can stand in for the JWKS endpoint of
+/// the core service, for example in development.
+#[derive(Clone)]
+enum JwksSource {
+    Url(String),
+    File(String),
+}
+
+#[derive(Deserialize)]
+struct Jwks {
+    keys: Vec<Jwk>,
+}
+
+#[derive(Deserialize)]
+struct Jwk {
+    kid: Option<String>,
+    kty: String,
+    #[serde(rename = "use")]
+    key_use: Option<String>,
+    alg: Option<String>,
+    n: Option<String>,
+    e: Option<String>,
+}
+
+impl KeyStore {
+    pub fn from_env() -> Self {
+        let mut keys = Vec::new();
+        if let Ok(pem) = env::var(PUBLIC_KEY_ENV) {
+            let key = DecodingKey::from_rsa_pem(pem.as_bytes())
+                .unwrap_or_else(|err| panic!("couldn't generate public key: {}", err))
+                .into_static();
+            keys.push(VerificationKey {
+                kid: None,
+                key,
+                rotating: false,
+                retired_at: None,
+            });
+        }
+        let source = env::var(JWKS_ENV).ok().map(|location| {
+            if location.starts_with("http://") || location.starts_with("https://") {
+                JwksSource::Url(location)
+            } else {
+                JwksSource::File(location.trim_start_matches("file://").to_owned())
+            }
+        });
+        if keys.is_empty() && source.is_none() {
+            panic!(
+                "No {} or {} environment variable found",
+                PUBLIC_KEY_ENV, JWKS_ENV
+            );
+        }
+        let key_store = KeyStore {
+            keys: Arc::new(RwLock::new(keys)),
+            source,
+            refresh_interval: Duration::from_secs(seconds_from_env(
+                JWKS_REFRESH_ENV,
+                DEFAULT_JWKS_REFRESH_SECONDS,
+            )),
+            grace_period: Duration::from_secs(seconds_from_env(
+                JWKS_GRACE_ENV,
+                DEFAULT_JWKS_GRACE_SECONDS,
+            )),
+        };
+        key_store
+            .refresh()
+            .unwrap_or_else(|err| panic!("JWKS could not be read: {}", err));
+        key_store
+    }
+
+    /// Decode a token with the keys of its `kid`. Tokens with an unknown or no
+    /// `kid` are tried with the keys without one, tokens without a `kid` also
+    /// with all others.
+    pub fn decode<T: DeserializeOwned>(
+        &self,
+        token: &str,
+        validation: &Validation,
+    ) -> Result<T, String> {
+        let header = jwt::decode_header(token).map_err(|err| format!("{:?}", err))?;
+        let keys = self.keys.read().expect("key store lock poisoned");
+        let now = Instant::now();
+        let usable: Vec<&VerificationKey> = keys
+            .iter()
+            .filter(|key| match key.retired_at {
+                Some(retired_at) => now.duration_since(retired_at) < self.grace_period,
+                None => true,
+            })
+            .collect();
+        let mut candidates: Vec<&VerificationKey> = usable
+            .iter()
+            .copied()
+            .filter(|key| header.kid.is_some() && key.kid == header.kid)
+            .collect();
+        if candidates.is_empty() {
+            candidates = usable
+                .iter()
+                .copied()
+                .filter(|key| key.kid.is_none() || header.kid.is_none())
+                .collect();
+        }
+        if candidates.is_empty() {
+            return Err(format!("no key for kid {:?}", header.kid));
+        }
+        let mut last_error = String::new();
+        for candidate in candidates {
+            match jwt::decode(token, &candidate.key, validation) {
+                Ok(token_data) => return Ok(token_data.claims),
+                Err(err) => last_error = format!("{:?}", err),
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Read the JWKS again. Keys that are no longer in it are retired and
+    /// dropped after the grace period. The keys are kept if the JWKS can't be
+    /// read.
+    pub fn refresh(&self) -> Result<(), String> {
+        let source = match &self.source {
+            Some(source) => source,
+            None => return Ok(()),
+        };
+        let mut fresh = parse_keys(source.load()?)?;
+        let now = Instant::now();
+        let mut keys = self.keys.write().expect("key store lock poisoned");
+        let previous: Vec<VerificationKey> = keys.drain(..).collect();
+        for mut key in previous {
+            if !key.rotating {
+                fresh.push(key);
+                continue;
+            }
+            if fresh
+                .iter()
+                .any(|fresh_key| fresh_key.kid == key.kid && fresh_key.key == key.key)
+            {
+                continue;
+            }
+            let retired_at = *key.retired_at.get_or_insert(now);
+            if now.duration_since(retired_at) < self.grace_period {
+                fresh.push(key);
+            }
+        }
+        *keys = fresh;
+        Ok(())
+    }
+}
+
+impl JwksSource {
+    fn load(&self) -> Result<Jwks, String> {
+        match self {
+            JwksSource::Url(url) => Client::builder()
+                .timeout(JWKS_REQUEST_TIMEOUT)
+                .build()
+                .and_then(|client| client.get(url).send())
+                .and_then(|response| response.error_for_status())
+                .and_then(|response| response.json())
+                .map_err(|err| format!("{}: {}", url, err)),
+            JwksSource::File(path) => fs::read_to_string(path)
+                .map_err(|err| err.to_string())
+                .and_then(|text| serde_json::from_str(&text).map_err(|err| err.to_string()))
+                .map_err(|err| format!("{}: {}", path, err)),
+        }
+    }
+}
+
+/// RSA signing keys of the JWKS. A JWKS without one is rejected, so a broken
+/// key set doesn't retire the keys in use.
+fn parse_keys(jwks: Jwks) -> Result<Vec<VerificationKey>, String> {
+    let keys: Vec<VerificationKey> = jwks
+        .keys
+        .into_iter()
+        .filter(|jwk| jwk.kty == "RSA")
+        .filter(|jwk| {
+            jwk.key_use
+                .as_deref()
+                .map_or(true, |key_use| key_use == "sig")
+        })
+        .filter(|jwk| jwk.alg.as_deref().map_or(true, |alg| alg == "RS256"))
+        .filter_map(|jwk| match (jwk.n, jwk.e) {
+            (Some(n), Some(e)) => Some(VerificationKey {
+                kid: jwk.kid,
+                key: DecodingKey::from_rsa_components(&n, &e).into_static(),
+                rotating: true,
+                retired_at: None,
+            }),
+            _ => None,
+        })
+        .collect();
+    if keys.is_empty() {
+        Err("JWKS contains no RSA signing key".to_owned())
+    } else {
+        Ok(keys)
+    }
+}
+
+fn seconds_from_env(name: &str, default: u64) -> u64 {
+    env::var(name)
+        .ok()
+        .map(|value| {
+            value
+                .parse()
+                .unwrap_or_else(|_| panic!("{} has to be a number of seconds", name))
+        })
+        .unwrap_or(default)
+}
+
+/// Start the background thread that reads the JWKS again periodically.
+pub fn spawn_refresh(key_store: KeyStore) {
+    if key_store.source.is_none() {
+        return;
+    }
+    thread::Builder::new()
+        .name("jwks-refresh".to_owned())
+        .spawn(move || loop {
+            thread::sleep(key_store.refresh_interval);
+            if let Err(err) = key_store.refresh() {
+                error!("Could not refresh JWKS: {}", err);
+            }
+        })
+        .expect("could not start JWKS refresh");
+}
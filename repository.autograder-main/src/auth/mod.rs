@@ -0,0 +1,195 @@
+use chrono::{Duration, Utc};
+use jsonwebtoken as jwt;
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Validation};
+use rocket::http::Status;
+use rocket::request::{self, FromRequest, Request};
+use rocket::{Outcome, State};
+use uuid::Uuid;
+
+use crate::access_tokens;
+use crate::access_tokens::Scope;
+use crate::config;
+use crate::config::AppState;
+use crate::connection::DbConn;
+use crate::users;
+
+use self::jwks::KeyStore;
+
+pub mod jwks;
+pub mod policy;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Auth {
+    #[serde(rename(serialize = "sub", deserialize = "sub"))]
+    pub user_id: Uuid,
+    pub is_superuser: bool,
+    pub is_teacher: bool,
+    pub is_student: bool,
+    pub email: String,
+    pub exp: u32,
+    /// Scopes of a personal access token. Tokens of the core service are not
+    /// limited to scopes.
+    #[serde(skip)]
+    pub scopes: Option<Vec<Scope>>,
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for Auth {
+    type Error = ();
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Auth, Self::Error> {
+        let state: State<AppState> = request.guard()?;
+        let token = match request
+            .headers()
+            .get_one("authorization")
+            .and_then(extract_token_from_header)
+        {
+            Some(token) => token,
+            None => return Outcome::Failure((Status::Forbidden, ())),
+        };
+        let auth = if token.starts_with(access_tokens::TOKEN_PREFIX) {
+            let connection: DbConn = request.guard()?;
+            access_tokens::repository::authenticate(token, &connection)
+                .map_err(|err| {
+                    error!("Access token lookup error: {}", err);
+                })
+                .ok()
+                .flatten()
+        } else {
+            let auth = decode_token(token, &state.key_store);
+            if let Some(auth) = &auth {
+                let connection: DbConn = request.guard()?;
+                if let Err(err) = users::repository::record_roles(auth, &connection) {
+                    error!(
+                        "Could not store the roles of user {}: {}",
+                        auth.user_id, err
+                    );
+                }
+            }
+            auth
+        };
+        if let Some(auth) = auth {
+            Outcome::Success(auth)
+        } else {
+            Outcome::Failure((Status::Forbidden, ()))
+        }
+    }
+}
+
+/// Identity of a runner reporting the results of one file or scratch run. The
+/// runner gets a callback token, signed with the callback secret of this
+/// service, with the job it runs. The audience of the token tells whether it
+/// was issued for a file or a scratch run.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RunnerAuth {
+    #[serde(rename(serialize = "sub", deserialize = "sub"))]
+    pub run_id: Uuid,
+    pub aud: String,
+    pub exp: i64,
+}
+
+impl RunnerAuth {
+    pub fn for_file(file_id: Uuid) -> Self {
+        RunnerAuth::new(file_id, config::CALLBACK_AUDIENCE)
+    }
+
+    pub fn for_scratch_run(scratch_run_id: Uuid) -> Self {
+        RunnerAuth::new(scratch_run_id, config::SCRATCH_RUN_CALLBACK_AUDIENCE)
+    }
+
+    fn new(run_id: Uuid, audience: &str) -> Self {
+        RunnerAuth {
+            run_id,
+            aud: audience.to_owned(),
+            exp: (Utc::now() + Duration::seconds(config::CALLBACK_TOKEN_LIFETIME_SECONDS))
+                .timestamp(),
+        }
+    }
+
+    pub fn is_for_file(&self, file_id: Uuid) -> bool {
+        self.aud == config::CALLBACK_AUDIENCE && self.run_id == file_id
+    }
+
+    pub fn is_for_scratch_run(&self, scratch_run_id: Uuid) -> bool {
+        self.aud == config::SCRATCH_RUN_CALLBACK_AUDIENCE && self.run_id == scratch_run_id
+    }
+
+    pub fn token(&self, encoding_key: &EncodingKey) -> String {
+        jwt::encode(&jwt::Header::new(Algorithm::HS256), self, encoding_key)
+            .expect("not able to encode callback token")
+    }
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for RunnerAuth {
+    type Error = ();
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<RunnerAuth, Self::Error> {
+        let state: State<AppState> = request.guard()?;
+        let decoding_key = DecodingKey::from_secret(&state.callback_secret);
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.set_audience(&[
+            config::CALLBACK_AUDIENCE,
+            config::SCRATCH_RUN_CALLBACK_AUDIENCE,
+        ]);
+        let runner_auth = request
+            .headers()
+            .get_one("authorization")
+            .and_then(extract_token_from_header)
+            .and_then(|token| {
+                jwt::decode(token, &decoding_key, &validation)
+                    .map_err(|err| {
+                        error!("Callback token decode error: {:?}", err);
+                    })
+                    .ok()
+            })
+            .map(|token_data| token_data.claims);
+        if let Some(runner_auth) = runner_auth {
+            Outcome::Success(runner_auth)
+        } else {
+            Outcome::Failure((Status::Forbidden, ()))
+        }
+    }
+}
+
+/// Identity of this service towards the scheduling service. Jobs are
+/// delivered with a new short-lived token of the service, signed with the
+/// private key of the core service, and never with a token of a user, which
+/// could have expired or lost its roles by the time a background job runs.
+#[derive(Debug, Serialize)]
+pub struct ServiceAuth {
+    pub sub: String,
+    pub aud: String,
+    pub exp: i64,
+}
+
+impl ServiceAuth {
+    pub fn for_scheduling() -> Self {
+        ServiceAuth {
+            sub: config::SERVICE_SUBJECT.to_owned(),
+            aud: config::SCHEDULING_AUDIENCE.to_owned(),
+            exp: (Utc::now() + Duration::seconds(config::SERVICE_TOKEN_LIFETIME_SECONDS))
+                .timestamp(),
+        }
+    }
+
+    pub fn token(&self, encoding_key: &EncodingKey) -> String {
+        jwt::encode(&jwt::Header::new(Algorithm::RS256), self, encoding_key)
+            .expect("not able to encode service token")
+    }
+}
+
+fn extract_token_from_header(header: &str) -> Option<&str> {
+    if let Some(stripped) = header.strip_prefix(config::TOKEN_PREFIX) {
+        Some(stripped)
+    } else {
+        None
+    }
+}
+
+/// Decode token into `Auth` struct. If any error is encountered, log it
+/// and return None.
+fn decode_token(token: &str, key_store: &KeyStore) -> Option<Auth> {
+    key_store
+        .decode(token, &Validation::new(Algorithm::RS256))
+        .map_err(|err| {
+            error!("Auth decode error: {}", err);
+        })
+        .ok()
+}
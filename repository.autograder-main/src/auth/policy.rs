@@ -0,0 +1,381 @@
+use crate::access_tokens::Scope;
+use crate::auth::Auth;
+use crate::connection::RepositoryError;
+
+/// Role of a user in the whole service, from the claims of its token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Superuser,
+    Teacher,
+    Student,
+}
+
+impl Role {
+    /// Users who are neither superusers nor students are treated as teachers.
+    pub fn of(auth: &Auth) -> Self {
+        if auth.is_superuser {
+            Role::Superuser
+        } else if auth.is_student {
+            Role::Student
+        } else {
+            Role::Teacher
+        }
+    }
+}
+
+/// Relation of a user to a resource, looked up by the repository of the
+/// resource. A user with several relations gets the strongest one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Relation {
+    /// Created the assignment the resource belongs to.
+    AssignmentOwner,
+    /// Collaborators of the assignment the resource belongs to.
+    CoOwner,
+    Grader,
+    Viewer,
+    /// Student attached to the assignment the resource belongs to.
+    Enrolled,
+    /// Submitted the submission or file, got the extension or started the
+    /// scratch run.
+    Author,
+    Unrelated,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    List,
+    Read,
+    Create,
+    Update,
+    Delete,
+}
+
+impl Action {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Action::List => "list",
+            Action::Read => "read",
+            Action::Create => "create",
+            Action::Update => "update",
+            Action::Delete => "delete",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resource {
+    Assignment,
+    TestCase,
+    Extension,
+    Regrade,
+    Collaborator,
+    Enrollment,
+    Submission,
+    File,
+    ScratchRun,
+    AuditEvent,
+    AccessToken,
+}
+
+impl Resource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Resource::Assignment => "assignment",
+            Resource::TestCase => "test case",
+            Resource::Extension => "extension",
+            Resource::Regrade => "regrade",
+            Resource::Collaborator => "collaborator",
+            Resource::Enrollment => "enrollment",
+            Resource::Submission => "submission",
+            Resource::File => "file",
+            Resource::ScratchRun => "scratch run",
+            Resource::AuditEvent => "audit event",
+            Resource::AccessToken => "access token",
+        }
+    }
+}
+
+/// Whether the user can do the action on the resource. Superusers can do
+/// everything, unless they use a personal access token without the scope.
+pub fn is_allowed(auth: &Auth, relation: Relation, action: Action, resource: Resource) -> bool {
+    rule(Role::of(auth), relation, action, resource)
+        && scope_rule(auth.scopes.as_deref(), action, resource)
+}
+
+/// Same as `is_allowed`, with the error the repositories return.
+pub fn authorize(
+    auth: &Auth,
+    relation: Relation,
+    action: Action,
+    resource: Resource,
+) -> Result<(), RepositoryError<diesel::result::Error>> {
+    if is_allowed(auth, relation, action, resource) {
+        Ok(())
+    } else {
+        Err(RepositoryError::Unauthorized(format!(
+            "user can't {} {}",
+            action.as_str(),
+            resource.as_str()
+        )))
+    }
+}
+
+fn rule(role: Role, relation: Relation, action: Action, resource: Resource) -> bool {
+    if role == Role::Superuser {
+        return true;
+    }
+    let owner = relation == Relation::AssignmentOwner || relation == Relation::CoOwner;
+    let grader = owner || relation == Relation::Grader;
+    let viewer = grader || relation == Relation::Viewer;
+    let enrolled = relation == Relation::Enrolled;
+    let author = relation == Relation::Author;
+    match (resource, action) {
+        (Resource::Assignment, Action::Create) => role == Role::Teacher,
+        (Resource::Assignment, Action::Read) => viewer || enrolled,
+        (Resource::Assignment, Action::Update) => owner,
+        (Resource::TestCase, Action::List)
+        | (Resource::TestCase, Action::Read)
+        | (Resource::Extension, Action::List)
+        | (Resource::Regrade, Action::List)
+        | (Resource::Regrade, Action::Read)
+        | (Resource::Collaborator, Action::List)
+        | (Resource::Collaborator, Action::Read)
+        | (Resource::Enrollment, Action::List) => viewer,
+        (Resource::Extension, Action::Read) => viewer || author,
+        (Resource::Regrade, Action::Create) => grader,
+        (Resource::TestCase, _)
+        | (Resource::Extension, _)
+        | (Resource::Collaborator, _)
+        | (Resource::Enrollment, _) => owner,
+        (Resource::Submission, Action::Create) => enrolled,
+        (Resource::Submission, Action::List) => viewer,
+        (Resource::Submission, Action::Read) | (Resource::File, Action::Read) => viewer || author,
+        (Resource::Submission, Action::Update) => grader,
+        (Resource::File, Action::Update) => grader || author,
+        (Resource::ScratchRun, Action::Create) => viewer || enrolled,
+        (Resource::ScratchRun, Action::Read) => viewer || author,
+        (Resource::ScratchRun, Action::List) => author,
+        (Resource::AccessToken, Action::Create) => role == Role::Teacher,
+        (Resource::AccessToken, Action::List) | (Resource::AccessToken, Action::Delete) => author,
+        _ => false,
+    }
+}
+
+/// Whether the scopes of a personal access token allow the action. Tokens
+/// without scopes come from the core service and are not limited. Access
+/// tokens can't be managed with an access token.
+fn scope_rule(scopes: Option<&[Scope]>, action: Action, resource: Resource) -> bool {
+    let scopes = match scopes {
+        Some(scopes) => scopes,
+        None => return true,
+    };
+    let (read, write) = match resource {
+        Resource::Assignment
+        | Resource::TestCase
+        | Resource::Extension
+        | Resource::Regrade
+        | Resource::Collaborator
+        | Resource::Enrollment => (Scope::AssignmentsRead, Some(Scope::AssignmentsWrite)),
+        Resource::Submission | Resource::File => {
+            (Scope::SubmissionsRead, Some(Scope::SubmissionsWrite))
+        }
+        Resource::ScratchRun => (Scope::ScratchRunsRead, Some(Scope::ScratchRunsWrite)),
+        Resource::AuditEvent => (Scope::AuditEventsRead, None),
+        Resource::AccessToken => return false,
+    };
+    let can_write = write.map_or(false, |write| scopes.contains(&write));
+    match action {
+        Action::List | Action::Read => can_write || scopes.contains(&read),
+        Action::Create | Action::Update | Action::Delete => can_write,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const Y: bool = true;
+    const N: bool = false;
+    const ROLES: [Role; 3] = [Role::Superuser, Role::Teacher, Role::Student];
+    const RELATIONS: [Relation; 7] = [
+        Relation::AssignmentOwner,
+        Relation::CoOwner,
+        Relation::Grader,
+        Relation::Viewer,
+        Relation::Enrolled,
+        Relation::Author,
+        Relation::Unrelated,
+    ];
+
+    /// Endpoint, what it does and whether superusers, teachers and students
+    /// (rows) with each relation (columns: assignment owner, co-owner, grader,
+    /// viewer, enrolled, author, unrelated) are allowed.
+    type Case = (&'static str, Action, Resource, [[bool; 7]; 3]);
+
+    #[rustfmt::skip]
+    const CASES: &[Case] = &[
+        ("POST /assignments", Action::Create, Resource::Assignment, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, Y, Y, Y, Y, Y], [N, N, N, N, N, N, N]]),
+        ("GET /assignments/{id}", Action::Read, Resource::Assignment, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, Y, Y, Y, N, N], [Y, Y, Y, Y, Y, N, N]]),
+        ("PATCH /assignments/{id}", Action::Update, Resource::Assignment, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, N, N, N, N, N], [Y, Y, N, N, N, N, N]]),
+        ("GET /assignments/{id}/test_cases", Action::List, Resource::TestCase, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, Y, Y, N, N, N], [Y, Y, Y, Y, N, N, N]]),
+        ("POST /assignments/{id}/test_cases", Action::Create, Resource::TestCase, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, N, N, N, N, N], [Y, Y, N, N, N, N, N]]),
+        ("GET /assignments/{id}/test_cases/{id}", Action::Read, Resource::TestCase, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, Y, Y, N, N, N], [Y, Y, Y, Y, N, N, N]]),
+        ("PATCH /assignments/{id}/test_cases/{id}", Action::Update, Resource::TestCase, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, N, N, N, N, N], [Y, Y, N, N, N, N, N]]),
+        ("DELETE /assignments/{id}/test_cases/{id}", Action::Delete, Resource::TestCase, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, N, N, N, N, N], [Y, Y, N, N, N, N, N]]),
+        ("GET /assignments/{id}/extensions", Action::List, Resource::Extension, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, Y, Y, N, N, N], [Y, Y, Y, Y, N, N, N]]),
+        ("POST /assignments/{id}/extensions", Action::Create, Resource::Extension, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, N, N, N, N, N], [Y, Y, N, N, N, N, N]]),
+        ("GET /assignments/{id}/extensions/{user_id}", Action::Read, Resource::Extension, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, Y, Y, N, Y, N], [Y, Y, Y, Y, N, Y, N]]),
+        ("DELETE /assignments/{id}/extensions/{user_id}", Action::Delete, Resource::Extension, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, N, N, N, N, N], [Y, Y, N, N, N, N, N]]),
+        ("POST /assignments/{id}/regrade", Action::Create, Resource::Regrade, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, Y, N, N, N, N], [Y, Y, Y, N, N, N, N]]),
+        ("GET /assignments/{id}/regrades", Action::List, Resource::Regrade, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, Y, Y, N, N, N], [Y, Y, Y, Y, N, N, N]]),
+        ("GET /assignments/{id}/regrades/{id}", Action::Read, Resource::Regrade, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, Y, Y, N, N, N], [Y, Y, Y, Y, N, N, N]]),
+        ("GET /assignments/{id}/collaborators", Action::List, Resource::Collaborator, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, Y, Y, N, N, N], [Y, Y, Y, Y, N, N, N]]),
+        ("POST /assignments/{id}/collaborators", Action::Create, Resource::Collaborator, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, N, N, N, N, N], [Y, Y, N, N, N, N, N]]),
+        ("GET /assignments/{id}/collaborators/{user_id}", Action::Read, Resource::Collaborator, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, Y, Y, N, N, N], [Y, Y, Y, Y, N, N, N]]),
+        ("DELETE /assignments/{id}/collaborators/{user_id}", Action::Delete, Resource::Collaborator, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, N, N, N, N, N], [Y, Y, N, N, N, N, N]]),
+        ("GET /assignments/{id}/students", Action::List, Resource::Enrollment, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, Y, Y, N, N, N], [Y, Y, Y, Y, N, N, N]]),
+        ("POST /assignments/{id}/students", Action::Update, Resource::Enrollment, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, N, N, N, N, N], [Y, Y, N, N, N, N, N]]),
+        // Lists the submissions of all assignments, so the user is always unrelated.
+        ("GET /submissions", Action::List, Resource::Submission, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, Y, Y, N, N, N], [Y, Y, Y, Y, N, N, N]]),
+        ("GET /submissions?assignment_id", Action::List, Resource::Submission, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, Y, Y, N, N, N], [Y, Y, Y, Y, N, N, N]]),
+        ("GET /submissions?user_id&assignment_id", Action::Read, Resource::Submission, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, Y, Y, N, Y, N], [Y, Y, Y, Y, N, Y, N]]),
+        ("GET /submissions/{id}", Action::Read, Resource::Submission, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, Y, Y, N, Y, N], [Y, Y, Y, Y, N, Y, N]]),
+        ("GET /submissions/{id}/files", Action::Read, Resource::Submission, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, Y, Y, N, Y, N], [Y, Y, Y, Y, N, Y, N]]),
+        ("PATCH /submissions/{id}/active_file", Action::Update, Resource::Submission, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, Y, N, N, N, N], [Y, Y, Y, N, N, N, N]]),
+        ("POST /submissions", Action::Create, Resource::Submission, [[Y, Y, Y, Y, Y, Y, Y], [N, N, N, N, Y, N, N], [N, N, N, N, Y, N, N]]),
+        ("POST /submissions/archive", Action::Create, Resource::Submission, [[Y, Y, Y, Y, Y, Y, Y], [N, N, N, N, Y, N, N], [N, N, N, N, Y, N, N]]),
+        ("GET /files/{id}", Action::Read, Resource::File, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, Y, Y, N, Y, N], [Y, Y, Y, Y, N, Y, N]]),
+        ("GET /files?submission_id", Action::Read, Resource::File, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, Y, Y, N, Y, N], [Y, Y, Y, Y, N, Y, N]]),
+        ("GET /files/{id}/job", Action::Read, Resource::File, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, Y, Y, N, Y, N], [Y, Y, Y, Y, N, Y, N]]),
+        ("GET /files/{id}/events", Action::Read, Resource::File, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, Y, Y, N, Y, N], [Y, Y, Y, Y, N, Y, N]]),
+        ("GET /files/{id}/results", Action::Read, Resource::File, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, Y, Y, N, Y, N], [Y, Y, Y, Y, N, Y, N]]),
+        ("GET /files/{id}/sources", Action::Read, Resource::File, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, Y, Y, N, Y, N], [Y, Y, Y, Y, N, Y, N]]),
+        ("GET /files/{id}/diff", Action::Read, Resource::File, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, Y, Y, N, Y, N], [Y, Y, Y, Y, N, Y, N]]),
+        ("PATCH /files/{id}", Action::Update, Resource::File, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, Y, N, N, Y, N], [Y, Y, Y, N, N, Y, N]]),
+        ("GET /scratch_runs", Action::List, Resource::ScratchRun, [[Y, Y, Y, Y, Y, Y, Y], [N, N, N, N, N, Y, N], [N, N, N, N, N, Y, N]]),
+        ("POST /scratch_runs", Action::Create, Resource::ScratchRun, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, Y, Y, Y, N, N], [Y, Y, Y, Y, Y, N, N]]),
+        ("GET /scratch_runs/{id}", Action::Read, Resource::ScratchRun, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, Y, Y, N, Y, N], [Y, Y, Y, Y, N, Y, N]]),
+        ("GET /audit_events", Action::List, Resource::AuditEvent, [[Y, Y, Y, Y, Y, Y, Y], [N, N, N, N, N, N, N], [N, N, N, N, N, N, N]]),
+        ("POST /access_tokens", Action::Create, Resource::AccessToken, [[Y, Y, Y, Y, Y, Y, Y], [Y, Y, Y, Y, Y, Y, Y], [N, N, N, N, N, N, N]]),
+        ("GET /access_tokens", Action::List, Resource::AccessToken, [[Y, Y, Y, Y, Y, Y, Y], [N, N, N, N, N, Y, N], [N, N, N, N, N, Y, N]]),
+        ("DELETE /access_tokens/{id}", Action::Delete, Resource::AccessToken, [[Y, Y, Y, Y, Y, Y, Y], [N, N, N, N, N, Y, N], [N, N, N, N, N, Y, N]]),
+    ];
+
+    #[test]
+    fn every_role_and_relation_on_every_endpoint() {
+        for (endpoint, action, resource, expected) in CASES {
+            for (row, role) in ROLES.iter().enumerate() {
+                for (column, relation) in RELATIONS.iter().enumerate() {
+                    assert_eq!(
+                        rule(*role, *relation, *action, *resource),
+                        expected[row][column],
+                        "{} as {:?} with relation {:?}",
+                        endpoint,
+                        role,
+                        relation
+                    );
+                }
+            }
+        }
+    }
+
+    /// Scopes of an access token and whether they allow the action.
+    #[rustfmt::skip]
+    const SCOPE_CASES: &[(&[Scope], Action, Resource, bool)] = &[
+        (&[Scope::AssignmentsRead], Action::Read, Resource::Assignment, Y),
+        (&[Scope::AssignmentsRead], Action::List, Resource::TestCase, Y),
+        (&[Scope::AssignmentsRead], Action::Update, Resource::Assignment, N),
+        (&[Scope::AssignmentsRead], Action::Create, Resource::Regrade, N),
+        (&[Scope::AssignmentsWrite], Action::Read, Resource::Assignment, Y),
+        (&[Scope::AssignmentsWrite], Action::Create, Resource::TestCase, Y),
+        (&[Scope::AssignmentsWrite], Action::Update, Resource::Enrollment, Y),
+        (&[Scope::AssignmentsWrite], Action::Read, Resource::Submission, N),
+        (&[Scope::SubmissionsRead], Action::Read, Resource::File, Y),
+        (&[Scope::SubmissionsRead], Action::Update, Resource::File, N),
+        (&[Scope::SubmissionsWrite], Action::Create, Resource::Submission, Y),
+        (&[Scope::SubmissionsWrite], Action::Update, Resource::File, Y),
+        (&[Scope::SubmissionsWrite], Action::Create, Resource::ScratchRun, N),
+        (&[Scope::ScratchRunsWrite], Action::Create, Resource::ScratchRun, Y),
+        (&[Scope::AuditEventsRead], Action::List, Resource::AuditEvent, Y),
+        (&[Scope::AssignmentsRead, Scope::SubmissionsWrite], Action::Update, Resource::Submission, Y),
+        (&[Scope::AssignmentsWrite, Scope::SubmissionsWrite, Scope::AuditEventsRead], Action::Create, Resource::AccessToken, N),
+        (&[Scope::AssignmentsWrite, Scope::SubmissionsWrite, Scope::AuditEventsRead], Action::Delete, Resource::AccessToken, N),
+        (&[], Action::Read, Resource::Assignment, N),
+    ];
+
+    #[test]
+    fn scopes_of_access_tokens() {
+        for (scopes, action, resource, expected) in SCOPE_CASES {
+            assert_eq!(
+                scope_rule(Some(scopes), *action, *resource),
+                *expected,
+                "{:?} {:?} with {:?}",
+                action,
+                resource,
+                scopes
+            );
+        }
+    }
+
+    #[test]
+    fn tokens_of_the_core_service_have_no_scopes() {
+        let auth = Auth {
+            user_id: Default::default(),
+            is_superuser: true,
+            is_teacher: false,
+            is_student: false,
+            email: String::new(),
+            exp: 0,
+            scopes: None,
+        };
+        assert!(is_allowed(
+            &auth,
+            Relation::Unrelated,
+            Action::Create,
+            Resource::AccessToken
+        ));
+        let scoped = Auth {
+            scopes: Some(vec![Scope::AssignmentsWrite]),
+            ..auth
+        };
+        assert!(is_allowed(
+            &scoped,
+            Relation::Unrelated,
+            Action::Update,
+            Resource::Assignment
+        ));
+        assert!(!is_allowed(
+            &scoped,
+            Relation::Unrelated,
+            Action::Create,
+            Resource::AccessToken
+        ));
+    }
+
+    #[test]
+    fn role_of_token() {
+        let auth = |is_superuser, is_teacher, is_student| Auth {
+            user_id: Default::default(),
+            is_superuser,
+            is_teacher,
+            is_student,
+            email: String::new(),
+            exp: 0,
+            scopes: None,
+        };
+        let cases = [
+            ((true, false, false), Role::Superuser),
+            ((true, true, true), Role::Superuser),
+            ((false, true, false), Role::Teacher),
+            ((false, false, false), Role::Teacher),
+            ((false, false, true), Role::Student),
+            ((false, true, true), Role::Student),
+        ];
+        for ((is_superuser, is_teacher, is_student), role) in cases.iter() {
+            assert_eq!(
+                Role::of(&auth(*is_superuser, *is_teacher, *is_student)),
+                *role
+            );
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use std::str::FromStr;
+
+use rocket::http::Status;
+use rocket::response::status;
+use rocket_contrib::json::Json;
+use uuid::Uuid;
+
+use crate::audit::{AuditContext, RequestId};
+use crate::auth::Auth;
+use crate::collaborators;
+use crate::collaborators::{Collaborator, InsertableCollaborator};
+use crate::connection::{to_status_created, DbConn};
+
+#[get("/<assignment_id>/collaborators")]
+pub fn all(
+    auth: Auth,
+    assignment_id: String,
+    connection: DbConn,
+) -> Result<Json<Vec<Collaborator>>, rocket::http::Status> {
+    Uuid::from_str(&assignment_id)
+        .map_err(|_| Status::BadRequest)
+        .and_then(|uuid| {
+            collaborators::repository::all_by_assignment_id(uuid, auth, &connection).into()
+        })
+}
+
+#[post(
+    "/<assignment_id>/collaborators",
+    format = "application/json",
+    data = "<insertable_collaborator>"
+)]
+pub fn add(
+    auth: Auth,
+    request_id: RequestId,
+    assignment_id: String,
+    insertable_collaborator: Json<InsertableCollaborator>,
+    connection: DbConn,
+) -> Result<status::Created<Json<Collaborator>>, rocket::http::Status> {
+    let audit = AuditContext::user(&auth, request_id);
+    let result: Result<Json<Collaborator>, rocket::http::Status> = Uuid::from_str(&assignment_id)
+        .map_err(|_| Status::BadRequest)
+        .and_then(|uuid| {
+            collaborators::repository::add(
+                uuid,
+                insertable_collaborator.into_inner(),
+                auth,
+                &audit,
+                &connection,
+            )
+            .into()
+        });
+    result.map(|collaborator| {
+        let path = format!("/assignments/{}/collaborators/", collaborator.assignment_id);
+        to_status_created(collaborator.user_id, &path, collaborator)
+    })
+}
+
+#[get("/<assignment_id>/collaborators/<user_id>")]
+pub fn get(
+    auth: Auth,
+    assignment_id: String,
+    user_id: String,
+    connection: DbConn,
+) -> Result<Json<Collaborator>, rocket::http::Status> {
+    parse_ids(&assignment_id, &user_id).and_then(|(assignment_id, user_id)| {
+        collaborators::repository::get(assignment_id, user_id, auth, &connection).into()
+    })
+}
+
+#[delete("/<assignment_id>/collaborators/<user_id>")]
+pub fn remove(
+    auth: Auth,
+    request_id: RequestId,
+    assignment_id: String,
+    user_id: String,
+    connection: DbConn,
+) -> Result<Json<Collaborator>, rocket::http::Status> {
+    let audit = AuditContext::user(&auth, request_id);
+    parse_ids(&assignment_id, &user_id).and_then(|(assignment_id, user_id)| {
+        collaborators::repository::remove(assignment_id, user_id, auth, &audit, &connection).into()
+    })
+}
+
+fn parse_ids(assignment_id: &str, user_id: &str) -> Result<(Uuid, Uuid), Status> {
+    Uuid::from_str(assignment_id)
+        .and_then(|assignment_id| Uuid::from_str(user_id).map(|user_id| (assignment_id, user_id)))
+        .map_err(|_| Status::BadRequest)
+}
@@ -0,0 +1,91 @@
+use std::io::Write;
+
+use chrono::NaiveDateTime;
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Text;
+use uuid::Uuid;
+
+use crate::assignments::Assignment;
+use crate::auth::policy::Relation;
+
+use super::schema::assignment_collaborators;
+
+pub mod handler;
+pub mod repository;
+pub mod router;
+
+/// What a collaborator can do with an assignment besides its owner.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, AsExpression, FromSqlRow)]
+#[serde(rename_all = "snake_case")]
+#[sql_type = "Text"]
+pub enum CollaboratorRole {
+    /// Can do everything the owner can.
+    CoOwner,
+    /// Can see the assignment and grade its submissions.
+    Grader,
+    /// Can see the assignment and its submissions.
+    Viewer,
+}
+
+impl CollaboratorRole {
+    fn as_str(&self) -> &'static str {
+        match self {
+            CollaboratorRole::CoOwner => "co_owner",
+            CollaboratorRole::Grader => "grader",
+            CollaboratorRole::Viewer => "viewer",
+        }
+    }
+}
+
+impl ToSql<Text, Pg> for CollaboratorRole {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        <str as ToSql<Text, Pg>>::to_sql(self.as_str(), out)
+    }
+}
+
+impl FromSql<Text, Pg> for CollaboratorRole {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match <String as FromSql<Text, Pg>>::from_sql(bytes)?.as_str() {
+            "co_owner" => Ok(CollaboratorRole::CoOwner),
+            "grader" => Ok(CollaboratorRole::Grader),
+            "viewer" => Ok(CollaboratorRole::Viewer),
+            other => Err(format!("unknown collaborator role: {}", other).into()),
+        }
+    }
+}
+
+impl From<CollaboratorRole> for Relation {
+    fn from(role: CollaboratorRole) -> Self {
+        match role {
+            CollaboratorRole::CoOwner => Relation::CoOwner,
+            CollaboratorRole::Grader => Relation::Grader,
+            CollaboratorRole::Viewer => Relation::Viewer,
+        }
+    }
+}
+
+/// User who works on an assignment with its owner, for example a teaching
+/// assistant.
+#[derive(Identifiable, Queryable, Associations, PartialEq, Debug, Serialize)]
+#[primary_key(assignment_id, user_id)]
+#[belongs_to(Assignment)]
+pub struct Collaborator {
+    pub assignment_id: Uuid,
+    pub user_id: Uuid,
+    pub role: CollaboratorRole,
+    pub created: NaiveDateTime,
+    pub updated: NaiveDateTime,
+}
+
+/// Collaborator as sent by the client. The assignment id is taken from the
+/// path.
+#[derive(Deserialize, Insertable)]
+#[table_name = "assignment_collaborators"]
+pub struct InsertableCollaborator {
+    #[serde(skip)]
+    pub assignment_id: Uuid,
+    pub user_id: Uuid,
+    pub role: CollaboratorRole,
+}
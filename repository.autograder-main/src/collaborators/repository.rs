@@ -0,0 +1,173 @@
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+use uuid::Uuid;
+
+use crate::assignments;
+use crate::audit;
+use crate::audit::AuditContext;
+use crate::auth::policy::{Action, Resource};
+use crate::auth::Auth;
+use crate::collaborators::{Collaborator, CollaboratorRole, InsertableCollaborator};
+use crate::connection::{RepositoryError, RepositoryQueryResult};
+use crate::schema::assignment_collaborators;
+
+pub fn all_by_assignment_id(
+    assignment_id: Uuid,
+    auth: Auth,
+    connection: &PgConnection,
+) -> RepositoryQueryResult<Vec<Collaborator>> {
+    if let Err(error) = assignments::repository::authorize(
+        assignment_id,
+        &auth,
+        Action::List,
+        Resource::Collaborator,
+        connection,
+    ) {
+        return RepositoryQueryResult::Err(error);
+    }
+    assignment_collaborators::table
+        .filter(assignment_collaborators::assignment_id.eq(assignment_id))
+        .order(assignment_collaborators::created.asc())
+        .load::<Collaborator>(connection)
+        .into()
+}
+
+pub fn get(
+    assignment_id: Uuid,
+    user_id: Uuid,
+    auth: Auth,
+    connection: &PgConnection,
+) -> RepositoryQueryResult<Collaborator> {
+    if let Err(error) = assignments::repository::authorize(
+        assignment_id,
+        &auth,
+        Action::Read,
+        Resource::Collaborator,
+        connection,
+    ) {
+        return RepositoryQueryResult::Err(error);
+    }
+    match assignment_collaborators::table
+        .find((assignment_id, user_id))
+        .get_result(connection)
+        .optional()
+    {
+        Ok(Some(collaborator)) => RepositoryQueryResult::Ok(collaborator),
+        Ok(None) => RepositoryQueryResult::Err(RepositoryError::NotFound),
+        Err(err) => RepositoryQueryResult::Err(RepositoryError::QueryError(err)),
+    }
+}
+
+/// Add a collaborator to an assignment or change the role of an existing one.
+/// The owner of the assignment can't be added. Changes of collaborators are
+/// recorded under the id of the assignment.
+pub fn add(
+    assignment_id: Uuid,
+    mut insertable_collaborator: InsertableCollaborator,
+    auth: Auth,
+    audit: &AuditContext,
+    connection: &PgConnection,
+) -> RepositoryQueryResult<Collaborator> {
+    if let Err(error) = assignments::repository::authorize(
+        assignment_id,
+        &auth,
+        Action::Create,
+        Resource::Collaborator,
+        connection,
+    ) {
+        return RepositoryQueryResult::Err(error);
+    }
+    match assignments::repository::owner_id(assignment_id, connection) {
+        Ok(owner_id) if owner_id == insertable_collaborator.user_id => {
+            return RepositoryQueryResult::Err(RepositoryError::Rejected(
+                "owner of assignment can't be a collaborator".to_owned(),
+            ))
+        }
+        Ok(_) => {}
+        Err(err) => return RepositoryQueryResult::Err(RepositoryError::QueryError(err)),
+    }
+    insertable_collaborator.assignment_id = assignment_id;
+    connection
+        .transaction::<_, RepositoryError<diesel::result::Error>, _>(|| {
+            let before: Option<Collaborator> = assignment_collaborators::table
+                .find((assignment_id, insertable_collaborator.user_id))
+                .for_update()
+                .get_result(connection)
+                .optional()?;
+            let after: Collaborator = diesel::insert_into(assignment_collaborators::table)
+                .values(&insertable_collaborator)
+                .on_conflict((
+                    assignment_collaborators::assignment_id,
+                    assignment_collaborators::user_id,
+                ))
+                .do_update()
+                .set((
+                    assignment_collaborators::role.eq(excluded(assignment_collaborators::role)),
+                    assignment_collaborators::updated.eq(Utc::now().naive_utc()),
+                ))
+                .get_result(connection)?;
+            audit::repository::record(
+                audit,
+                "add",
+                Resource::Collaborator,
+                assignment_id,
+                before.as_ref(),
+                Some(&after),
+                connection,
+            )?;
+            Ok(after)
+        })
+        .into()
+}
+
+pub fn remove(
+    assignment_id: Uuid,
+    user_id: Uuid,
+    auth: Auth,
+    audit: &AuditContext,
+    connection: &PgConnection,
+) -> RepositoryQueryResult<Collaborator> {
+    if let Err(error) = assignments::repository::authorize(
+        assignment_id,
+        &auth,
+        Action::Delete,
+        Resource::Collaborator,
+        connection,
+    ) {
+        return RepositoryQueryResult::Err(error);
+    }
+    connection
+        .transaction::<_, RepositoryError<diesel::result::Error>, _>(|| {
+            let collaborator: Collaborator =
+                diesel::delete(assignment_collaborators::table.find((assignment_id, user_id)))
+                    .get_result(connection)
+                    .optional()?
+                    .ok_or(RepositoryError::NotFound)?;
+            audit::repository::record(
+                audit,
+                "remove",
+                Resource::Collaborator,
+                assignment_id,
+                Some(&collaborator),
+                None,
+                connection,
+            )?;
+            Ok(collaborator)
+        })
+        .into()
+}
+
+/// Role of a user in an assignment without authorization, used to look up the
+/// relation of the user to it.
+pub fn find_role(
+    assignment_id: Uuid,
+    user_id: Uuid,
+    connection: &PgConnection,
+) -> QueryResult<Option<CollaboratorRole>> {
+    assignment_collaborators::table
+        .find((assignment_id, user_id))
+        .select(assignment_collaborators::role)
+        .get_result(connection)
+        .optional()
+}
@@ -0,0 +1,10 @@
+use rocket::Rocket;
+
+use crate::collaborators::handler;
+
+pub fn create_routes(rocket: Rocket) -> Rocket {
+    rocket.mount(
+        "/assignments",
+        routes![handler::all, handler::add, handler::get, handler::remove],
+    )
+}
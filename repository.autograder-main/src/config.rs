@@ -4,31 +4,61 @@ use jsonwebtoken::EncodingKey;
 use rocket::fairing::AdHoc;
 use rocket::Rocket;
 
+use crate::auth::jwks::KeyStore;
+
 const PRIVATE_KEY_ENV: &str = "CORE_PRIVATE_KEY";
-const PUBLIC_KEY_ENV: &str = "CORE_PUBLIC_KEY";
+const CALLBACK_SECRET_ENV: &str = "CALLBACK_TOKEN_SECRET";
+const PUBLIC_BASE_URL_ENV: &str = "PUBLIC_BASE_URL";
 pub const TOKEN_PREFIX: &str = "Bearer ";
+pub const CALLBACK_AUDIENCE: &str = "runner-callback";
+pub const SCRATCH_RUN_CALLBACK_AUDIENCE: &str = "scratch-run-callback";
+pub const CALLBACK_TOKEN_LIFETIME_SECONDS: i64 = 24 * 60 * 60;
+pub const SERVICE_SUBJECT: &str = "repository-service";
+pub const SCHEDULING_AUDIENCE: &str = "scheduling-service";
+pub const SERVICE_TOKEN_LIFETIME_SECONDS: i64 = 5 * 60;
 
 pub struct AppState {
-    pub encoding_key: EncodingKey,
-    pub decoding_key: Vec<u8>,
+    pub key_store: KeyStore,
+    pub callback_secret: Vec<u8>,
 }
 
 impl AppState {
-    pub fn manage() -> AdHoc {
+    pub fn manage(key_store: KeyStore) -> AdHoc {
         AdHoc::on_attach("Manage config", |rocket: Rocket| {
-            let private_key = env::var(PRIVATE_KEY_ENV)
-                .map(|key| {
-                    EncodingKey::from_rsa_pem(&key.into_bytes()).unwrap_or_else(|err| {
-                        panic!("private key could not be read: {}", err.to_string())
-                    })
-                })
-                .unwrap_or_else(|_| panic!("No {} environment variable found", PRIVATE_KEY_ENV));
             Ok(rocket.manage(AppState {
-                encoding_key: private_key,
-                decoding_key: env::var(PUBLIC_KEY_ENV)
-                    .map(|key| key.into_bytes())
-                    .unwrap_or_else(|_| panic!("No {} environment variable found", PUBLIC_KEY_ENV)),
+                key_store,
+                callback_secret: callback_secret(),
             }))
         })
     }
 }
+
+/// Key the tokens of this service are signed with, which the core service
+/// shares with it.
+pub fn private_key() -> EncodingKey {
+    env::var(PRIVATE_KEY_ENV)
+        .map(|key| {
+            EncodingKey::from_rsa_pem(&key.into_bytes())
+                .unwrap_or_else(|err| panic!("private key could not be read: {}", err.to_string()))
+        })
+        .unwrap_or_else(|_| panic!("No {} environment variable found", PRIVATE_KEY_ENV))
+}
+
+/// Secret the callback tokens of runners are signed with.
+pub fn callback_secret() -> Vec<u8> {
+    env::var(CALLBACK_SECRET_ENV)
+        .map(|secret| secret.into_bytes())
+        .unwrap_or_else(|_| panic!("No {} environment variable found", CALLBACK_SECRET_ENV))
+}
+
+/// URL this service is reached at by the runners, like
+/// `https://repository.example.com/api/v0`, which the callback paths of runs
+/// are appended to.
+pub fn public_base_url() -> String {
+    let url = env::var(PUBLIC_BASE_URL_ENV)
+        .unwrap_or_else(|_| panic!("No {} environment variable found", PUBLIC_BASE_URL_ENV));
+    if !(url.starts_with("http://") || url.starts_with("https://")) {
+        panic!("{} has to be an absolute http(s) URL", PUBLIC_BASE_URL_ENV);
+    }
+    url.trim_end_matches('/').to_owned()
+}
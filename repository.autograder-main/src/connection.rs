@@ -24,7 +24,7 @@ pub fn init_pool() -> Pool {
     pool
 }
 
-fn database_url() -> String {
+pub(crate) fn database_url() -> String {
     env::var("DATABASE_URL").expect("DATABASE_URL must be set")
 }
 
@@ -101,6 +101,18 @@ where
     }
 }
 
+/// Deserialize a field that can be left out, set to null or set to a value,
+/// so that `None` means unchanged and `Some(None)` means cleared.
+pub(crate) fn deserialize_nullable<'de, D, T>(
+    deserializer: D,
+) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
 pub type RepositoryQueryResult<T> = RepositoryResult<T, diesel::result::Error>;
 
 pub enum RepositoryResult<T, E> {
@@ -111,6 +123,10 @@ pub enum RepositoryResult<T, E> {
 #[derive(Debug, Clone)]
 pub enum RepositoryError<E> {
     Unauthorized(String),
+    Rejected(String),
+    /// The request can't be stored as sent, like a test case whose expected
+    /// output is no valid pattern.
+    Invalid(String),
     QueryError(E),
     NotFound,
 }
@@ -124,12 +140,30 @@ impl<T> From<QueryResult<T>> for RepositoryQueryResult<T> {
     }
 }
 
+impl From<diesel::result::Error> for RepositoryError<diesel::result::Error> {
+    fn from(err: diesel::result::Error) -> Self {
+        RepositoryError::QueryError(err)
+    }
+}
+
+/// Result of a transaction that can be rolled back by a repository error.
+impl<T, E> From<Result<T, RepositoryError<E>>> for RepositoryResult<T, E> {
+    fn from(result: Result<T, RepositoryError<E>>) -> Self {
+        match result {
+            Ok(value) => RepositoryResult::Ok(value),
+            Err(err) => RepositoryResult::Err(err),
+        }
+    }
+}
+
 impl<T> From<RepositoryQueryResult<T>> for Result<Json<T>, rocket::http::Status> {
     fn from(res: RepositoryQueryResult<T>) -> Self {
         match res {
             RepositoryResult::Ok(value) => Ok(Json(value)),
             RepositoryResult::Err(err) => match err {
                 RepositoryError::Unauthorized(_) => Err(Status::Unauthorized),
+                RepositoryError::Rejected(_) => Err(Status::Forbidden),
+                RepositoryError::Invalid(_) => Err(Status::BadRequest),
                 RepositoryError::QueryError(_) => Err(Status::InternalServerError),
                 RepositoryError::NotFound => Err(Status::NotFound),
             },
@@ -139,7 +173,7 @@ impl<T> From<RepositoryQueryResult<T>> for Result<Json<T>, rocket::http::Status>
 
 pub fn to_status_created<T>(
     id: Uuid,
-    path_with_leading_and_trailing_slash: &'static str,
+    path_with_leading_and_trailing_slash: &str,
     created_entry: Json<T>,
 ) -> status::Created<Json<T>> {
     let path = path_with_leading_and_trailing_slash;
@@ -159,3 +193,44 @@ pub fn to_status_created<T>(
         Some(created_entry),
     )
 }
+
+const DEFAULT_PAGE_SIZE: i64 = 20;
+const MAX_PAGE_SIZE: i64 = 100;
+
+/// One page of a list, shaped like the pages of the assignment service.
+#[derive(Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: i64,
+    pub page: i64,
+    pub size: i64,
+}
+
+/// Page number (starting at 1) and page size from optional query parameters.
+#[derive(Clone, Copy)]
+pub struct PageRequest {
+    pub page: i64,
+    pub size: i64,
+}
+
+impl PageRequest {
+    pub fn new(page: Option<i64>, size: Option<i64>) -> Self {
+        PageRequest {
+            page: page.unwrap_or(1).max(1),
+            size: size.unwrap_or(DEFAULT_PAGE_SIZE).max(1).min(MAX_PAGE_SIZE),
+        }
+    }
+
+    pub fn offset(&self) -> i64 {
+        (self.page - 1) * self.size
+    }
+
+    pub fn to_page<T>(&self, items: Vec<T>, total: i64) -> Page<T> {
+        Page {
+            items,
+            total,
+            page: self.page,
+            size: self.size,
+        }
+    }
+}
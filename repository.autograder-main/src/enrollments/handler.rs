@@ -0,0 +1,51 @@
+use std::str::FromStr;
+
+use rocket::http::Status;
+use rocket_contrib::json::Json;
+use uuid::Uuid;
+
+use crate::audit::{AuditContext, RequestId};
+use crate::auth::Auth;
+use crate::connection::DbConn;
+use crate::enrollments;
+use crate::enrollments::{Enrollment, EnrollmentSync};
+
+#[get("/<assignment_id>/students")]
+pub fn all(
+    auth: Auth,
+    assignment_id: String,
+    connection: DbConn,
+) -> Result<Json<Vec<Enrollment>>, rocket::http::Status> {
+    Uuid::from_str(&assignment_id)
+        .map_err(|_| Status::BadRequest)
+        .and_then(|uuid| {
+            enrollments::repository::all_by_assignment_id(uuid, auth, &connection).into()
+        })
+}
+
+#[post(
+    "/<assignment_id>/students",
+    format = "application/json",
+    data = "<enrollment_sync>"
+)]
+pub fn sync(
+    auth: Auth,
+    request_id: RequestId,
+    assignment_id: String,
+    enrollment_sync: Json<EnrollmentSync>,
+    connection: DbConn,
+) -> Result<Json<Vec<Enrollment>>, rocket::http::Status> {
+    let audit = AuditContext::user(&auth, request_id);
+    Uuid::from_str(&assignment_id)
+        .map_err(|_| Status::BadRequest)
+        .and_then(|uuid| {
+            enrollments::repository::sync(
+                uuid,
+                enrollment_sync.into_inner(),
+                auth,
+                &audit,
+                &connection,
+            )
+            .into()
+        })
+}
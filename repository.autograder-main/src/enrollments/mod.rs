@@ -0,0 +1,27 @@
+use chrono::NaiveDateTime;
+use uuid::Uuid;
+
+use crate::assignments::Assignment;
+
+use super::schema::enrollments;
+
+pub mod handler;
+pub mod repository;
+pub mod router;
+
+/// Student attached to an assignment in the assignment service, who can read
+/// the assignment and submit to it.
+#[derive(Identifiable, Queryable, Associations, PartialEq, Debug, Serialize)]
+#[primary_key(assignment_id, user_id)]
+#[belongs_to(Assignment)]
+pub struct Enrollment {
+    pub assignment_id: Uuid,
+    pub user_id: Uuid,
+    pub created: NaiveDateTime,
+}
+
+/// All students attached to an assignment, as sent by the assignment service.
+#[derive(Deserialize)]
+pub struct EnrollmentSync {
+    pub user_ids: Vec<Uuid>,
+}
@@ -0,0 +1,111 @@
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::assignments;
+use crate::audit;
+use crate::audit::AuditContext;
+use crate::auth::policy::{Action, Resource};
+use crate::auth::Auth;
+use crate::connection::{RepositoryError, RepositoryQueryResult};
+use crate::enrollments::{Enrollment, EnrollmentSync};
+use crate::schema::enrollments;
+
+pub fn all_by_assignment_id(
+    assignment_id: Uuid,
+    auth: Auth,
+    connection: &PgConnection,
+) -> RepositoryQueryResult<Vec<Enrollment>> {
+    if let Err(error) = assignments::repository::authorize(
+        assignment_id,
+        &auth,
+        Action::List,
+        Resource::Enrollment,
+        connection,
+    ) {
+        return RepositoryQueryResult::Err(error);
+    }
+    load(assignment_id, connection).into()
+}
+
+/// Replace the students of an assignment with the ones attached to it in the
+/// assignment service. Students who are no longer attached can't submit
+/// anymore, their submissions are kept. The students before and after the sync
+/// are recorded under the id of the assignment.
+pub fn sync(
+    assignment_id: Uuid,
+    enrollment_sync: EnrollmentSync,
+    auth: Auth,
+    audit: &AuditContext,
+    connection: &PgConnection,
+) -> RepositoryQueryResult<Vec<Enrollment>> {
+    if let Err(error) = assignments::repository::authorize(
+        assignment_id,
+        &auth,
+        Action::Update,
+        Resource::Enrollment,
+        connection,
+    ) {
+        return RepositoryQueryResult::Err(error);
+    }
+    let user_ids = enrollment_sync.user_ids;
+    connection
+        .transaction::<_, RepositoryError<diesel::result::Error>, _>(|| {
+            let before: Vec<Enrollment> = enrollments::table
+                .filter(enrollments::assignment_id.eq(assignment_id))
+                .order(enrollments::created.asc())
+                .for_update()
+                .load(connection)?;
+            diesel::delete(
+                enrollments::table
+                    .filter(enrollments::assignment_id.eq(assignment_id))
+                    .filter(enrollments::user_id.ne_all(user_ids.clone())),
+            )
+            .execute(connection)?;
+            let insertable: Vec<_> = user_ids
+                .into_iter()
+                .map(|user_id| {
+                    (
+                        enrollments::assignment_id.eq(assignment_id),
+                        enrollments::user_id.eq(user_id),
+                    )
+                })
+                .collect();
+            diesel::insert_into(enrollments::table)
+                .values(insertable)
+                .on_conflict_do_nothing()
+                .execute(connection)?;
+            let after = load(assignment_id, connection)?;
+            audit::repository::record(
+                audit,
+                "sync",
+                Resource::Enrollment,
+                assignment_id,
+                Some(&before),
+                Some(&after),
+                connection,
+            )?;
+            Ok(after)
+        })
+        .into()
+}
+
+/// Whether a student is attached to an assignment, without authorization.
+pub fn is_enrolled(
+    assignment_id: Uuid,
+    user_id: Uuid,
+    connection: &PgConnection,
+) -> QueryResult<bool> {
+    enrollments::table
+        .find((assignment_id, user_id))
+        .select(enrollments::user_id)
+        .get_result::<Uuid>(connection)
+        .optional()
+        .map(|user_id| user_id.is_some())
+}
+
+fn load(assignment_id: Uuid, connection: &PgConnection) -> QueryResult<Vec<Enrollment>> {
+    enrollments::table
+        .filter(enrollments::assignment_id.eq(assignment_id))
+        .order(enrollments::created.asc())
+        .load(connection)
+}
@@ -0,0 +1,7 @@
+use rocket::Rocket;
+
+use crate::enrollments::handler;
+
+pub fn create_routes(rocket: Rocket) -> Rocket {
+    rocket.mount("/assignments", routes![handler::all, handler::sync])
+}
@@ -0,0 +1,54 @@
+use diesel::PgConnection;
+use jsonwebtoken::EncodingKey;
+use reqwest::blocking::Client;
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE, USER_AGENT};
+
+use crate::auth::ServiceAuth;
+use crate::config;
+use crate::execution::{required_env, ExecutionBackend};
+use crate::files::ScheduleInputFile;
+use crate::jobs::Job;
+
+/// Sends files to the scheduling service at `SCHEDULING_SUBMISSION_URL` with
+/// a token of this service, signed with `CORE_PRIVATE_KEY`.
+pub struct HttpBackend {
+    client: Client,
+    scheduling_url: String,
+    service_key: EncodingKey,
+}
+
+impl HttpBackend {
+    pub fn from_env() -> Self {
+        HttpBackend {
+            client: Client::new(),
+            scheduling_url: required_env("SCHEDULING_SUBMISSION_URL"),
+            service_key: config::private_key(),
+        }
+    }
+}
+
+impl ExecutionBackend for HttpBackend {
+    fn submit(
+        &self,
+        _job: &Job,
+        schedule_file: &ScheduleInputFile,
+        _connection: &PgConnection,
+    ) -> Result<(), String> {
+        self.client
+            .post(&self.scheduling_url)
+            .headers(construct_headers())
+            .bearer_auth(ServiceAuth::for_scheduling().token(&self.service_key))
+            .json(schedule_file)
+            .send()
+            .and_then(|response| response.error_for_status())
+            .map(|_| ())
+            .map_err(|err| err.to_string())
+    }
+}
+
+pub(crate) fn construct_headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(USER_AGENT, HeaderValue::from_static("reqwest"));
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    headers
+}
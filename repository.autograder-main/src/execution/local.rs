@@ -0,0 +1,69 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use diesel::PgConnection;
+
+use crate::execution::{required_env, run_in_process, ExecutionBackend};
+use crate::files::{ScheduleInputFile, ScheduleOutputFile};
+use crate::jobs::Job;
+
+/// Runs files with the command in `LOCAL_RUNNER_COMMAND` on this machine, for
+/// development and integration tests. The command gets the file as JSON on
+/// stdin and prints the output of the run as JSON on stdout, in the shape of
+/// `PATCH /files/<id>/output`.
+pub struct LocalBackend {
+    command: String,
+}
+
+impl LocalBackend {
+    pub fn from_env() -> Self {
+        LocalBackend {
+            command: required_env("LOCAL_RUNNER_COMMAND"),
+        }
+    }
+
+    fn run(&self, schedule_file: &ScheduleInputFile) -> Result<ScheduleOutputFile, String> {
+        let input = serde_json::to_vec(schedule_file).map_err(|err| err.to_string())?;
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(&self.command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| err.to_string())?;
+        let mut stdin = child.stdin.take().expect("stdin of runner is piped");
+        let writer = thread::spawn(move || stdin.write_all(&input));
+        let output = child.wait_with_output().map_err(|err| err.to_string())?;
+        writer
+            .join()
+            .map_err(|_| "could not write to runner".to_owned())?
+            .map_err(|err| err.to_string())?;
+        if !output.status.success() {
+            return Err(format!(
+                "runner exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+        serde_json::from_slice(&output.stdout).map_err(|err| err.to_string())
+    }
+}
+
+impl ExecutionBackend for LocalBackend {
+    fn submit(
+        &self,
+        job: &Job,
+        schedule_file: &ScheduleInputFile,
+        connection: &PgConnection,
+    ) -> Result<(), String> {
+        run_in_process(job, schedule_file, connection, |schedule_file| {
+            self.run(schedule_file)
+        })
+    }
+
+    fn batch_size(&self) -> i64 {
+        1
+    }
+}
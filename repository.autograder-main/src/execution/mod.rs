@@ -0,0 +1,128 @@
+use std::env;
+
+use diesel::PgConnection;
+
+use crate::audit::AuditContext;
+use crate::auth::RunnerAuth;
+use crate::connection::RepositoryResult;
+use crate::execution::http::HttpBackend;
+use crate::execution::local::LocalBackend;
+use crate::execution::openfaas::OpenFaasBackend;
+use crate::execution::sandbox::SandboxBackend;
+use crate::files;
+use crate::files::status::FileStatus;
+use crate::files::{ScheduleInputFile, ScheduleOutputFile, StatusUpdate};
+use crate::jobs::{Job, RunTarget};
+use crate::scratch_runs;
+
+pub mod http;
+pub mod local;
+pub mod openfaas;
+pub mod sandbox;
+
+const BACKEND_ENV: &str = "EXECUTION_BACKEND";
+const BATCH_SIZE: i64 = 20;
+
+/// Where queued files and scratch runs are run. A backend either hands the run
+/// over to a runner, which reports the results through the callback path of
+/// the run, or runs it itself and stores the results before it returns.
+pub trait ExecutionBackend: Send {
+    fn submit(
+        &self,
+        job: &Job,
+        schedule_file: &ScheduleInputFile,
+        connection: &PgConnection,
+    ) -> Result<(), String>;
+
+    /// Jobs the worker claims at once. Backends that run the jobs themselves
+    /// claim one at a time, so that the others stay free for other instances
+    /// while a job runs.
+    fn batch_size(&self) -> i64 {
+        BATCH_SIZE
+    }
+}
+
+/// Backend selected by `EXECUTION_BACKEND`, which is `http` (the scheduling
+/// service) by default, `openfaas`, `local` or `sandbox`.
+pub fn from_env() -> Box<dyn ExecutionBackend> {
+    match env::var(BACKEND_ENV)
+        .unwrap_or_else(|_| "http".to_owned())
+        .as_str()
+    {
+        "http" => Box::new(HttpBackend::from_env()),
+        "openfaas" => Box::new(OpenFaasBackend::from_env()),
+        "local" => Box::new(LocalBackend::from_env()),
+        "sandbox" => Box::new(SandboxBackend::from_env()),
+        other => panic!("unknown {}: {}", BACKEND_ENV, other),
+    }
+}
+
+fn required_env(name: &str) -> String {
+    env::var(name).unwrap_or_else(|_| panic!("No {} environment variable found", name))
+}
+
+/// Mark the file or scratch run as running, run it and store its output the
+/// same way as a runner reporting through its callback path. A run that fails
+/// ends with an internal error.
+fn run_in_process<F>(
+    job: &Job,
+    schedule_file: &ScheduleInputFile,
+    connection: &PgConnection,
+    run: F,
+) -> Result<(), String>
+where
+    F: FnOnce(&ScheduleInputFile) -> Result<ScheduleOutputFile, String>,
+{
+    let running = StatusUpdate {
+        status: FileStatus::Running,
+    };
+    match job.target() {
+        RunTarget::File(id) => into_result(files::repository::update_status_by_runner(
+            id,
+            &RunnerAuth::for_file(id),
+            running,
+            &AuditContext::runner(None),
+            connection,
+        )),
+        RunTarget::ScratchRun(id) => {
+            into_result(scratch_runs::repository::update_status_by_runner(
+                id,
+                &RunnerAuth::for_scratch_run(id),
+                running,
+                &AuditContext::runner(None),
+                connection,
+            ))
+        }
+    }?;
+    let schedule_output_file = run(schedule_file).unwrap_or_else(|reason| {
+        error!("Run of {} failed: {}", schedule_file.callback_path, reason);
+        ScheduleOutputFile {
+            status: Some(FileStatus::InternalError),
+            encoded_stderr: Some(reason.into_bytes()),
+            ..Default::default()
+        }
+    });
+    match job.target() {
+        RunTarget::File(id) => into_result(files::repository::update_output(
+            id,
+            RunnerAuth::for_file(id),
+            schedule_output_file,
+            &AuditContext::runner(None),
+            connection,
+        )),
+        RunTarget::ScratchRun(id) => into_result(scratch_runs::repository::update_output(
+            id,
+            RunnerAuth::for_scratch_run(id),
+            schedule_output_file,
+            &AuditContext::runner(None),
+            connection,
+        )),
+    }
+}
+
+fn into_result<T>(result: RepositoryResult<T, diesel::result::Error>) -> Result<(), String> {
+    match result {
+        RepositoryResult::Ok(_) => Ok(()),
+        RepositoryResult::Err(err) => Err(format!("{:?}", err)),
+    }
+}
@@ -0,0 +1,79 @@
+use std::env;
+
+use diesel::PgConnection;
+use reqwest::blocking::Client;
+
+use crate::execution::http::construct_headers;
+use crate::execution::{required_env, ExecutionBackend};
+use crate::files::ScheduleInputFile;
+use crate::jobs::Job;
+
+/// Invokes the OpenFaaS function of the submission's language asynchronously
+/// through the gateway at `OPENFAAS_GATEWAY_URL`. The functions are named like
+/// the language templates, prefixed with `OPENFAAS_FUNCTION_PREFIX`, and report
+/// their results with the callback token of the file.
+pub struct OpenFaasBackend {
+    client: Client,
+    gateway_url: String,
+    function_prefix: String,
+    basic_auth: Option<(String, String)>,
+}
+
+impl OpenFaasBackend {
+    pub fn from_env() -> Self {
+        OpenFaasBackend {
+            client: Client::new(),
+            gateway_url: required_env("OPENFAAS_GATEWAY_URL")
+                .trim_end_matches('/')
+                .to_owned(),
+            function_prefix: env::var("OPENFAAS_FUNCTION_PREFIX").unwrap_or_default(),
+            basic_auth: env::var("OPENFAAS_BASIC_AUTH").ok().map(|credentials| {
+                let mut parts = credentials.splitn(2, ':');
+                (
+                    parts.next().unwrap_or_default().to_owned(),
+                    parts.next().unwrap_or_default().to_owned(),
+                )
+            }),
+        }
+    }
+}
+
+impl ExecutionBackend for OpenFaasBackend {
+    fn submit(
+        &self,
+        _job: &Job,
+        schedule_file: &ScheduleInputFile,
+        _connection: &PgConnection,
+    ) -> Result<(), String> {
+        let template = template_of(&schedule_file.extension)
+            .ok_or_else(|| format!("no function for extension {}", schedule_file.extension))?;
+        let mut request = self
+            .client
+            .post(&format!(
+                "{}/async-function/{}{}",
+                self.gateway_url, self.function_prefix, template
+            ))
+            .headers(construct_headers())
+            .json(schedule_file);
+        if let Some((user, password)) = &self.basic_auth {
+            request = request.basic_auth(user, Some(password));
+        }
+        request
+            .send()
+            .and_then(|response| response.error_for_status())
+            .map(|_| ())
+            .map_err(|err| err.to_string())
+    }
+}
+
+/// Language template of the examples repository a file extension belongs to.
+fn template_of(extension: &str) -> Option<&'static str> {
+    match extension.trim_start_matches('.').to_lowercase().as_str() {
+        "cs" | "csharp" => Some("csharp"),
+        "go" => Some("go"),
+        "java" => Some("java"),
+        "js" | "javascript" => Some("javascript"),
+        "php" | "php7" => Some("php7"),
+        _ => None,
+    }
+}
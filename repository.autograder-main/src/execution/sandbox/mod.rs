@@ -0,0 +1,327 @@
+use std::collections::HashMap;
+use std::env;
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use diesel::PgConnection;
+use uuid::Uuid;
+
+use crate::execution::sandbox::process::{Confinement, Execution, Limits};
+use crate::execution::{run_in_process, ExecutionBackend};
+use crate::files::status::FileStatus;
+use crate::files::{ScheduleInputFile, ScheduleOutputFile, ScheduleOutputTestCase};
+use crate::jobs::Job;
+
+pub mod process;
+
+/// Host paths the toolchains are usually found in.
+const DEFAULT_READ_ONLY_PATHS: &str = "/bin:/etc:/lib:/lib32:/lib64:/opt:/sbin:/usr";
+/// `nobody` on most systems.
+const DEFAULT_SANDBOX_ID: u64 = 65534;
+
+/// How the files of a language are compiled and run. Single file submissions
+/// are stored as `source` in the working directory.
+#[derive(Deserialize, Clone)]
+pub struct Language {
+    pub source: String,
+    #[serde(default)]
+    pub compile: Option<Vec<String>>,
+    pub run: Vec<String>,
+}
+
+/// Compiles and runs files on this machine, each process in a sandbox with its
+/// own network namespace and resource limits, as an unprivileged user that
+/// only sees the toolchains and a temporary directory that is removed
+/// afterwards. The languages are configured by file extension in the JSON file
+/// `SANDBOX_LANGUAGES_FILE`, or a few defaults are used. Compilers get their
+/// own limits, as they need more time and memory than most submissions and
+/// write binaries larger than the output of a test case.
+pub struct SandboxBackend {
+    languages: HashMap<String, Language>,
+    limits: Limits,
+    compile_limits: Limits,
+    confinement: Confinement,
+    root: PathBuf,
+}
+
+impl SandboxBackend {
+    pub fn from_env() -> Self {
+        let languages = env::var("SANDBOX_LANGUAGES_FILE")
+            .map(|path| {
+                let content = fs::read(&path)
+                    .unwrap_or_else(|err| panic!("could not read {}: {}", path, err));
+                serde_json::from_slice(&content)
+                    .unwrap_or_else(|err| panic!("invalid languages in {}: {}", path, err))
+            })
+            .unwrap_or_else(|_| default_languages());
+        // Only root can switch to the sandbox user.
+        if unsafe { libc::geteuid() } != 0 {
+            panic!("the sandbox backend has to run as root");
+        }
+        SandboxBackend {
+            languages,
+            limits: Limits {
+                cpu_seconds: env_or("SANDBOX_CPU_SECONDS", 5),
+                memory_bytes: env_or("SANDBOX_MEMORY_MB", 512) * 1024 * 1024,
+                output_bytes: env_or("SANDBOX_OUTPUT_KB", 1024) * 1024,
+                file_bytes: env_or("SANDBOX_FILE_KB", 1024) * 1024,
+                processes: env_or("SANDBOX_PROCESSES", 64),
+                wall_time: Duration::from_secs(env_or("SANDBOX_WALL_SECONDS", 10)),
+            },
+            compile_limits: Limits {
+                cpu_seconds: env_or("SANDBOX_COMPILE_CPU_SECONDS", 30),
+                memory_bytes: env_or("SANDBOX_COMPILE_MEMORY_MB", 2048) * 1024 * 1024,
+                output_bytes: env_or("SANDBOX_OUTPUT_KB", 1024) * 1024,
+                file_bytes: env_or("SANDBOX_COMPILE_FILE_MB", 256) * 1024 * 1024,
+                processes: env_or("SANDBOX_COMPILE_PROCESSES", 256),
+                wall_time: Duration::from_secs(env_or("SANDBOX_COMPILE_WALL_SECONDS", 60)),
+            },
+            confinement: Confinement {
+                uid: env_or("SANDBOX_UID", DEFAULT_SANDBOX_ID) as libc::uid_t,
+                gid: env_or("SANDBOX_GID", DEFAULT_SANDBOX_ID) as libc::gid_t,
+                read_only_paths: env::var("SANDBOX_READ_ONLY_PATHS")
+                    .unwrap_or_else(|_| DEFAULT_READ_ONLY_PATHS.to_owned())
+                    .split(':')
+                    .filter(|path| !path.is_empty())
+                    .map(PathBuf::from)
+                    .collect(),
+            },
+            root: env::var("SANDBOX_DIR")
+                .map(PathBuf::from)
+                .unwrap_or_else(|_| env::temp_dir()),
+        }
+    }
+
+    fn run(&self, schedule_file: &ScheduleInputFile) -> Result<ScheduleOutputFile, String> {
+        let language = self
+            .languages
+            .get(schedule_file.extension.trim_start_matches('.'))
+            .ok_or_else(|| format!("no language for extension {}", schedule_file.extension))?;
+        let work_dir = WorkDir::create(&self.root).map_err(|err| err.to_string())?;
+        write_sources(work_dir.work(), language, schedule_file)
+            .and_then(|_| chown_all(work_dir.work(), &self.confinement))
+            .map_err(|err| err.to_string())?;
+
+        let mut output = ScheduleOutputFile::default();
+        if let Some(compile) = &language.compile {
+            let execution = process::execute(
+                compile,
+                work_dir.work(),
+                work_dir.root(),
+                &[],
+                self.compile_limits,
+                &self.confinement,
+            )
+            .map_err(|err| err.to_string())?;
+            let compiled = execution.succeeded();
+            output.encoded_stdout = Some(execution.stdout);
+            output.encoded_stderr = Some(execution.stderr);
+            output.exit_code = execution.exit_code;
+            output.signal = execution.signal;
+            output.duration_ms = Some(execution.duration.as_millis() as i64);
+            output.memory_kb = Some(execution.memory_kb);
+            if !compiled {
+                output.status = Some(FileStatus::CompileError);
+                return Ok(output);
+            }
+        }
+        for test_case in &schedule_file.test_cases {
+            let execution = process::execute(
+                &language.run,
+                work_dir.work(),
+                work_dir.root(),
+                &test_case.encoded_input,
+                self.limits,
+                &self.confinement,
+            )
+            .map_err(|err| err.to_string())?;
+            output.status = worse_status(output.status, status_of(&execution));
+            output.results.push(ScheduleOutputTestCase {
+                test_case_id: test_case.id,
+                encoded_output: execution.stdout,
+                encoded_stderr: Some(execution.stderr),
+                exit_code: execution.exit_code,
+                signal: execution.signal,
+                duration_ms: Some(execution.duration.as_millis() as i64),
+                memory_kb: Some(execution.memory_kb),
+            });
+        }
+        Ok(output)
+    }
+}
+
+impl ExecutionBackend for SandboxBackend {
+    fn submit(
+        &self,
+        job: &Job,
+        schedule_file: &ScheduleInputFile,
+        connection: &PgConnection,
+    ) -> Result<(), String> {
+        run_in_process(job, schedule_file, connection, |schedule_file| {
+            self.run(schedule_file)
+        })
+    }
+
+    fn batch_size(&self) -> i64 {
+        1
+    }
+}
+
+/// Temporary directory of a run with the working directory of its processes
+/// and the mount point of their root, removed when it is dropped.
+struct WorkDir {
+    path: PathBuf,
+    work: PathBuf,
+    root: PathBuf,
+}
+
+impl WorkDir {
+    fn create(root: &Path) -> std::io::Result<Self> {
+        let path = root.join(format!("sandbox-{}", Uuid::new_v4()));
+        let work_dir = WorkDir {
+            work: path.join("work"),
+            root: path.join("root"),
+            path,
+        };
+        fs::create_dir_all(&work_dir.work)?;
+        fs::create_dir_all(&work_dir.root)?;
+        Ok(work_dir)
+    }
+
+    fn work(&self) -> &Path {
+        &self.work
+    }
+
+    fn root(&self) -> &Path {
+        &self.root
+    }
+}
+
+impl Drop for WorkDir {
+    fn drop(&mut self) {
+        if let Err(err) = fs::remove_dir_all(&self.path) {
+            error!("Could not remove {}: {}", self.path.display(), err);
+        }
+    }
+}
+
+/// Write the files of a project, whose paths were validated when it was
+/// submitted, or the single file as the source of the language.
+fn write_sources(
+    dir: &Path,
+    language: &Language,
+    schedule_file: &ScheduleInputFile,
+) -> std::io::Result<()> {
+    if schedule_file.files.is_empty() {
+        return fs::write(dir.join(&language.source), &schedule_file.encoded_text);
+    }
+    for source_file in &schedule_file.files {
+        let path = dir.join(&source_file.path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, &source_file.encoded_text)?;
+    }
+    Ok(())
+}
+
+/// Hand the working directory over to the sandbox user, who writes the
+/// compiled program to it.
+fn chown_all(path: &Path, confinement: &Confinement) -> io::Result<()> {
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    if unsafe { libc::lchown(c_path.as_ptr(), confinement.uid, confinement.gid) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if fs::symlink_metadata(path)?.is_dir() {
+        for entry in fs::read_dir(path)? {
+            chown_all(&entry?.path(), confinement)?;
+        }
+    }
+    Ok(())
+}
+
+fn status_of(execution: &Execution) -> Option<FileStatus> {
+    if execution.timed_out {
+        Some(FileStatus::Timeout)
+    } else if execution.output_exceeded || !execution.succeeded() {
+        Some(FileStatus::RuntimeError)
+    } else {
+        None
+    }
+}
+
+/// A timeout in any test case outweighs a runtime error.
+fn worse_status(current: Option<FileStatus>, status: Option<FileStatus>) -> Option<FileStatus> {
+    match (current, status) {
+        (Some(FileStatus::Timeout), _) | (_, Some(FileStatus::Timeout)) => {
+            Some(FileStatus::Timeout)
+        }
+        (current, status) => current.or(status),
+    }
+}
+
+fn env_or(name: &str, default: u64) -> u64 {
+    env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+fn default_languages() -> HashMap<String, Language> {
+    let language = |source: &str, compile: Option<&[&str]>, run: &[&str]| Language {
+        source: source.to_owned(),
+        compile: compile.map(|compile| compile.iter().map(|arg| arg.to_string()).collect()),
+        run: run.iter().map(|arg| arg.to_string()).collect(),
+    };
+    let mut languages = HashMap::new();
+    languages.insert(
+        "c".to_owned(),
+        language(
+            "main.c",
+            Some(&["gcc", "-O2", "-o", "main", "main.c", "-lm"]),
+            &["./main"],
+        ),
+    );
+    languages.insert(
+        "cpp".to_owned(),
+        language(
+            "main.cpp",
+            Some(&["g++", "-O2", "-o", "main", "main.cpp"]),
+            &["./main"],
+        ),
+    );
+    languages.insert(
+        "go".to_owned(),
+        language(
+            "main.go",
+            Some(&["go", "build", "-o", "main", "main.go"]),
+            &["./main"],
+        ),
+    );
+    languages.insert(
+        "java".to_owned(),
+        language(
+            "Main.java",
+            Some(&["javac", "Main.java"]),
+            &["java", "Main"],
+        ),
+    );
+    languages.insert(
+        "js".to_owned(),
+        language("main.js", None, &["node", "main.js"]),
+    );
+    languages.insert(
+        "php".to_owned(),
+        language("main.php", None, &["php", "main.php"]),
+    );
+    languages.insert(
+        "py".to_owned(),
+        language("main.py", None, &["python3", "main.py"]),
+    );
+    languages
+}
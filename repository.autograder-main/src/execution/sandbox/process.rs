@@ -0,0 +1,450 @@
+use std::ffi::CString;
+use std::io;
+use std::io::{Read, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const SANDBOX_PATH: &str = "/usr/local/bin:/usr/bin:/bin";
+/// Working directory of the process inside the sandbox.
+const SANDBOX_DIR: &str = "/sandbox";
+const MAX_OPEN_FILES: u64 = 64;
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+/// Time the output of a killed process is waited for.
+const OUTPUT_TIMEOUT: Duration = Duration::from_secs(1);
+const KILL_ROUNDS: usize = 3;
+/// Devices that are mounted into the sandbox.
+const DEVICES: [&str; 4] = ["/dev/null", "/dev/zero", "/dev/random", "/dev/urandom"];
+
+/// Resources a sandboxed process may use.
+#[derive(Clone, Copy)]
+pub struct Limits {
+    pub cpu_seconds: u64,
+    /// Heap and other writable memory. Reserved address space that is not
+    /// written to, like the heap a JVM reserves up front, doesn't count.
+    pub memory_bytes: u64,
+    /// Bytes read from stdout and from stderr each.
+    pub output_bytes: u64,
+    /// Size of every file the process writes, like a compiled binary.
+    pub file_bytes: u64,
+    /// Processes and threads the program may run at the same time.
+    pub processes: u64,
+    pub wall_time: Duration,
+}
+
+/// User the sandboxed processes run as and what they see of the file system.
+/// No other process may run as the user, as all processes of the user are
+/// killed after every run.
+pub struct Confinement {
+    pub uid: libc::uid_t,
+    pub gid: libc::gid_t,
+    /// Host paths mounted read-only at the same path into the otherwise empty
+    /// root of the sandbox, like the toolchains in `/usr`.
+    pub read_only_paths: Vec<PathBuf>,
+}
+
+pub struct Execution {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+    pub duration: Duration,
+    pub memory_kb: i64,
+    pub timed_out: bool,
+    pub output_exceeded: bool,
+}
+
+impl Execution {
+    pub fn succeeded(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
+/// Run a program as the user of the confinement in its own session, user,
+/// mount and network namespace, so it has no network access, with the given
+/// limits and the input on stdin. Its root is the empty directory `root` with
+/// the read-only paths of the confinement, a few devices and `work_dir`, which
+/// is mounted writable at `/sandbox`. All processes of the user are killed when
+/// the program exceeds the wall time or prints more than the output limit and
+/// after it ended.
+pub fn execute(
+    program: &[String],
+    work_dir: &Path,
+    root: &Path,
+    stdin: &[u8],
+    limits: Limits,
+    confinement: &Confinement,
+) -> io::Result<Execution> {
+    let (executable, args) = program
+        .split_first()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty command"))?;
+    let mounts = Mounts::new(work_dir, root, &confinement.read_only_paths)?;
+    let user = User::new(confinement);
+    let mut command = Command::new(executable);
+    command
+        .args(args)
+        .env_clear()
+        .env("PATH", SANDBOX_PATH)
+        .env("HOME", SANDBOX_DIR)
+        .env("TMPDIR", SANDBOX_DIR)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    unsafe {
+        command.pre_exec(move || confine(limits, &user, &mounts));
+    }
+
+    // Processes left over from a previous run would count against the limit.
+    kill_user(confinement.uid);
+    let started = Instant::now();
+    let mut child = command.spawn()?;
+    let pid = child.id() as libc::pid_t;
+    let exceeded = Arc::new(AtomicBool::new(false));
+    let mut child_stdin = child.stdin.take().expect("stdin is piped");
+    let input = stdin.to_vec();
+    let writer = thread::spawn(move || {
+        // The program may exit without reading its input.
+        let _ = child_stdin.write_all(&input);
+    });
+    let stdout_reader = read_limited(
+        child.stdout.take().expect("stdout is piped"),
+        limits.output_bytes,
+        exceeded.clone(),
+    );
+    let stderr_reader = read_limited(
+        child.stderr.take().expect("stderr is piped"),
+        limits.output_bytes,
+        exceeded.clone(),
+    );
+
+    let mut status = 0;
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    let mut timed_out = false;
+    loop {
+        let result = unsafe { libc::wait4(pid, &mut status, libc::WNOHANG, &mut usage) };
+        if result == pid {
+            break;
+        }
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if started.elapsed() > limits.wall_time {
+            timed_out = true;
+            kill_user(confinement.uid);
+        } else if exceeded.load(Ordering::SeqCst) {
+            kill_user(confinement.uid);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    let duration = started.elapsed();
+    // Kills the processes that left the session of the program as well, so
+    // nothing keeps its output open.
+    kill_user(confinement.uid);
+    let _ = writer.join();
+    let stdout = stdout_reader
+        .recv_timeout(OUTPUT_TIMEOUT)
+        .unwrap_or_default();
+    let stderr = stderr_reader
+        .recv_timeout(OUTPUT_TIMEOUT)
+        .unwrap_or_default();
+
+    let signal = if libc::WIFSIGNALED(status) {
+        Some(libc::WTERMSIG(status))
+    } else {
+        None
+    };
+    Ok(Execution {
+        stdout,
+        stderr,
+        exit_code: if libc::WIFEXITED(status) {
+            Some(libc::WEXITSTATUS(status))
+        } else {
+            None
+        },
+        signal,
+        duration,
+        memory_kb: usage.ru_maxrss as i64,
+        timed_out: timed_out || signal == Some(libc::SIGXCPU),
+        output_exceeded: exceeded.load(Ordering::SeqCst),
+    })
+}
+
+/// The user of the confinement with the maps of its user namespace, which
+/// maps it to itself.
+struct User {
+    uid: libc::uid_t,
+    gid: libc::gid_t,
+    uid_map: Vec<u8>,
+    gid_map: Vec<u8>,
+}
+
+impl User {
+    fn new(confinement: &Confinement) -> Self {
+        User {
+            uid: confinement.uid,
+            gid: confinement.gid,
+            uid_map: format!("{0} {0} 1", confinement.uid).into_bytes(),
+            gid_map: format!("{0} {0} 1", confinement.gid).into_bytes(),
+        }
+    }
+}
+
+/// A host path mounted into the root of the sandbox.
+struct Bind {
+    source: CString,
+    target: CString,
+    /// Flags of the host mount that have to be kept when it is remounted
+    /// read-only.
+    read_only_flags: Option<libc::c_ulong>,
+}
+
+/// Everything the child mounts, prepared beforehand because the child may
+/// not allocate between fork and exec.
+struct Mounts {
+    root: CString,
+    /// Mount points to create in the root, parents first.
+    directories: Vec<CString>,
+    files: Vec<CString>,
+    binds: Vec<Bind>,
+}
+
+impl Mounts {
+    fn new(work_dir: &Path, root: &Path, read_only_paths: &[PathBuf]) -> io::Result<Self> {
+        let mut mounts = Mounts {
+            root: c_string(root)?,
+            directories: Vec::new(),
+            files: Vec::new(),
+            binds: Vec::new(),
+        };
+        for path in read_only_paths.iter().filter(|path| path.exists()) {
+            let stat = statvfs(path)?;
+            let kept_flags = [
+                (libc::ST_NODEV, libc::MS_NODEV),
+                (libc::ST_NOEXEC, libc::MS_NOEXEC),
+            ];
+            let flags = kept_flags
+                .iter()
+                .filter(|(flag, _)| stat.f_flag & *flag != 0)
+                .fold(libc::MS_NOSUID, |flags, (_, mount_flag)| {
+                    flags | *mount_flag
+                });
+            mounts.add(path, path, Some(flags), root)?;
+        }
+        for device in DEVICES.iter().map(Path::new) {
+            mounts.add(device, device, None, root)?;
+        }
+        mounts.add(work_dir, Path::new(SANDBOX_DIR), None, root)?;
+        Ok(mounts)
+    }
+
+    fn add(
+        &mut self,
+        source: &Path,
+        target: &Path,
+        read_only_flags: Option<libc::c_ulong>,
+        root: &Path,
+    ) -> io::Result<()> {
+        let relative = target.strip_prefix("/").unwrap_or(target);
+        for parent in relative
+            .ancestors()
+            .skip(1)
+            .collect::<Vec<&Path>>()
+            .iter()
+            .rev()
+        {
+            let directory = c_string(&root.join(parent))?;
+            if !parent.as_os_str().is_empty() && !self.directories.contains(&directory) {
+                self.directories.push(directory);
+            }
+        }
+        let target = c_string(&root.join(relative))?;
+        if source.is_dir() {
+            if !self.directories.contains(&target) {
+                self.directories.push(target.clone());
+            }
+        } else {
+            self.files.push(target.clone());
+        }
+        self.binds.push(Bind {
+            source: c_string(source)?,
+            target,
+            read_only_flags,
+        });
+        Ok(())
+    }
+}
+
+fn c_string(path: &Path) -> io::Result<CString> {
+    CString::new(path.as_os_str().as_bytes())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+}
+
+fn statvfs(path: &Path) -> io::Result<libc::statvfs> {
+    let path = c_string(path)?;
+    unsafe {
+        let mut stat: libc::statvfs = std::mem::zeroed();
+        if libc::statvfs(path.as_ptr(), &mut stat) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(stat)
+    }
+}
+
+/// Runs in the forked child before the program is executed.
+fn confine(limits: Limits, user: &User, mounts: &Mounts) -> io::Result<()> {
+    unsafe {
+        if libc::setsid() < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // The hard CPU limit is a second above the soft one, so that a
+        // process running out of CPU time gets SIGXCPU before it is killed.
+        let resources = [
+            (libc::RLIMIT_CPU, limits.cpu_seconds, limits.cpu_seconds + 1),
+            (libc::RLIMIT_DATA, limits.memory_bytes, limits.memory_bytes),
+            (libc::RLIMIT_FSIZE, limits.file_bytes, limits.file_bytes),
+            (libc::RLIMIT_NOFILE, MAX_OPEN_FILES, MAX_OPEN_FILES),
+            (libc::RLIMIT_NPROC, limits.processes, limits.processes),
+            (libc::RLIMIT_CORE, 0, 0),
+        ];
+        for (resource, soft, hard) in resources.iter() {
+            let limit = libc::rlimit {
+                rlim_cur: *soft,
+                rlim_max: *hard,
+            };
+            check(libc::setrlimit(*resource, &limit))?;
+        }
+        check(libc::setgroups(0, std::ptr::null()))?;
+        check(libc::setgid(user.gid))?;
+        check(libc::setuid(user.uid))?;
+        check(libc::unshare(
+            libc::CLONE_NEWUSER | libc::CLONE_NEWNS | libc::CLONE_NEWNET,
+        ))?;
+        write_file(b"/proc/self/setgroups\0", b"deny")?;
+        write_file(b"/proc/self/uid_map\0", &user.uid_map)?;
+        write_file(b"/proc/self/gid_map\0", &user.gid_map)?;
+        mount_root(mounts)?;
+        check(libc::chdir(mounts.root.as_ptr()))?;
+        check(libc::chroot(b".\0".as_ptr() as *const libc::c_char))?;
+        check(libc::chdir(b"/sandbox\0".as_ptr() as *const libc::c_char))?;
+    }
+    Ok(())
+}
+
+/// Mount an empty read-only root with the bound paths, which only this mount
+/// namespace sees.
+unsafe fn mount_root(mounts: &Mounts) -> io::Result<()> {
+    let none = std::ptr::null::<libc::c_char>();
+    check(libc::mount(
+        none,
+        b"/\0".as_ptr() as *const libc::c_char,
+        none,
+        libc::MS_REC | libc::MS_PRIVATE,
+        std::ptr::null(),
+    ))?;
+    check(libc::mount(
+        b"tmpfs\0".as_ptr() as *const libc::c_char,
+        mounts.root.as_ptr(),
+        b"tmpfs\0".as_ptr() as *const libc::c_char,
+        libc::MS_NOSUID | libc::MS_NODEV,
+        b"size=1m,mode=755\0".as_ptr() as *const libc::c_void,
+    ))?;
+    for directory in &mounts.directories {
+        check(libc::mkdir(directory.as_ptr(), 0o755))?;
+    }
+    for file in &mounts.files {
+        let fd = libc::open(file.as_ptr(), libc::O_CREAT | libc::O_WRONLY, 0o644);
+        check(fd)?;
+        libc::close(fd);
+    }
+    for bind in &mounts.binds {
+        check(libc::mount(
+            bind.source.as_ptr(),
+            bind.target.as_ptr(),
+            none,
+            libc::MS_BIND | libc::MS_REC,
+            std::ptr::null(),
+        ))?;
+        if let Some(flags) = bind.read_only_flags {
+            check(libc::mount(
+                none,
+                bind.target.as_ptr(),
+                none,
+                libc::MS_BIND | libc::MS_REMOUNT | libc::MS_RDONLY | flags,
+                std::ptr::null(),
+            ))?;
+        }
+    }
+    check(libc::mount(
+        none,
+        mounts.root.as_ptr(),
+        none,
+        libc::MS_REMOUNT | libc::MS_RDONLY | libc::MS_NOSUID | libc::MS_NODEV,
+        std::ptr::null(),
+    ))
+}
+
+/// Write a file of `/proc`, given as a nul terminated path.
+unsafe fn write_file(path: &[u8], content: &[u8]) -> io::Result<()> {
+    let fd = libc::open(path.as_ptr() as *const libc::c_char, libc::O_WRONLY);
+    check(fd)?;
+    let written = libc::write(fd, content.as_ptr() as *const libc::c_void, content.len());
+    libc::close(fd);
+    if written != content.len() as isize {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn check(result: libc::c_int) -> io::Result<()> {
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Kill every process of the user, wherever it moved to, from a child that
+/// switches to the user, so it can't signal anything else.
+fn kill_user(uid: libc::uid_t) {
+    unsafe {
+        let pid = libc::fork();
+        if pid == 0 {
+            if libc::setuid(uid) == 0 {
+                // Again for processes forked while the signal was sent.
+                for _ in 0..KILL_ROUNDS {
+                    libc::kill(-1, libc::SIGKILL);
+                }
+            }
+            libc::_exit(0);
+        }
+        if pid > 0 {
+            libc::waitpid(pid, std::ptr::null_mut(), 0);
+        }
+    }
+}
+
+/// Read at most `limit` bytes and flag the process as exceeding its output if
+/// there is more.
+fn read_limited<R: Read + Send + 'static>(
+    reader: R,
+    limit: u64,
+    exceeded: Arc<AtomicBool>,
+) -> Receiver<Vec<u8>> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut output = Vec::new();
+        let _ = reader.take(limit + 1).read_to_end(&mut output);
+        if output.len() as u64 > limit {
+            output.truncate(limit as usize);
+            exceeded.store(true, Ordering::SeqCst);
+        }
+        let _ = sender.send(output);
+    });
+    receiver
+}
@@ -0,0 +1,88 @@
+use std::str::FromStr;
+
+use rocket::http::Status;
+use rocket::response::status;
+use rocket_contrib::json::Json;
+use uuid::Uuid;
+
+use crate::audit::{AuditContext, RequestId};
+use crate::auth::Auth;
+use crate::connection::{to_status_created, DbConn};
+use crate::extensions;
+use crate::extensions::{Extension, InsertableExtension};
+
+#[get("/<assignment_id>/extensions")]
+pub fn all(
+    auth: Auth,
+    assignment_id: String,
+    connection: DbConn,
+) -> Result<Json<Vec<Extension>>, rocket::http::Status> {
+    Uuid::from_str(&assignment_id)
+        .map_err(|_| Status::BadRequest)
+        .and_then(|uuid| {
+            extensions::repository::all_by_assignment_id(uuid, auth, &connection).into()
+        })
+}
+
+#[post(
+    "/<assignment_id>/extensions",
+    format = "application/json",
+    data = "<insertable_extension>"
+)]
+pub fn grant(
+    auth: Auth,
+    request_id: RequestId,
+    assignment_id: String,
+    insertable_extension: Json<InsertableExtension>,
+    connection: DbConn,
+) -> Result<status::Created<Json<Extension>>, rocket::http::Status> {
+    let audit = AuditContext::user(&auth, request_id);
+    let result: Result<Json<Extension>, rocket::http::Status> = Uuid::from_str(&assignment_id)
+        .map_err(|_| Status::BadRequest)
+        .and_then(|uuid| {
+            extensions::repository::grant(
+                uuid,
+                insertable_extension.into_inner(),
+                auth,
+                &audit,
+                &connection,
+            )
+            .into()
+        });
+    result.map(|extension| {
+        let path = format!("/assignments/{}/extensions/", extension.assignment_id);
+        to_status_created(extension.user_id, &path, extension)
+    })
+}
+
+#[get("/<assignment_id>/extensions/<user_id>")]
+pub fn get(
+    auth: Auth,
+    assignment_id: String,
+    user_id: String,
+    connection: DbConn,
+) -> Result<Json<Extension>, rocket::http::Status> {
+    parse_ids(&assignment_id, &user_id).and_then(|(assignment_id, user_id)| {
+        extensions::repository::get(assignment_id, user_id, auth, &connection).into()
+    })
+}
+
+#[delete("/<assignment_id>/extensions/<user_id>")]
+pub fn revoke(
+    auth: Auth,
+    request_id: RequestId,
+    assignment_id: String,
+    user_id: String,
+    connection: DbConn,
+) -> Result<Json<Extension>, rocket::http::Status> {
+    let audit = AuditContext::user(&auth, request_id);
+    parse_ids(&assignment_id, &user_id).and_then(|(assignment_id, user_id)| {
+        extensions::repository::revoke(assignment_id, user_id, auth, &audit, &connection).into()
+    })
+}
+
+fn parse_ids(assignment_id: &str, user_id: &str) -> Result<(Uuid, Uuid), Status> {
+    Uuid::from_str(assignment_id)
+        .and_then(|assignment_id| Uuid::from_str(user_id).map(|user_id| (assignment_id, user_id)))
+        .map_err(|_| Status::BadRequest)
+}
@@ -0,0 +1,31 @@
+use chrono::NaiveDateTime;
+use uuid::Uuid;
+
+use crate::assignments::Assignment;
+
+use super::schema::extensions;
+
+pub mod handler;
+pub mod repository;
+pub mod router;
+
+/// Individual due date of a student for an assignment.
+#[derive(Identifiable, Queryable, Associations, PartialEq, Debug, Serialize)]
+#[primary_key(assignment_id, user_id)]
+#[belongs_to(Assignment)]
+pub struct Extension {
+    pub assignment_id: Uuid,
+    pub user_id: Uuid,
+    pub due_at: NaiveDateTime,
+    pub updated: NaiveDateTime,
+}
+
+/// Extension as sent by the client. The assignment id is taken from the path.
+#[derive(Deserialize, Insertable)]
+#[table_name = "extensions"]
+pub struct InsertableExtension {
+    #[serde(skip)]
+    pub assignment_id: Uuid,
+    pub user_id: Uuid,
+    pub due_at: NaiveDateTime,
+}
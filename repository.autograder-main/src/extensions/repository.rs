@@ -0,0 +1,162 @@
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+use uuid::Uuid;
+
+use crate::assignments;
+use crate::audit;
+use crate::audit::AuditContext;
+use crate::auth::policy::{Action, Relation, Resource};
+use crate::auth::{policy, Auth};
+use crate::connection::{RepositoryError, RepositoryQueryResult};
+use crate::extensions::{Extension, InsertableExtension};
+use crate::schema::extensions;
+
+pub fn all_by_assignment_id(
+    assignment_id: Uuid,
+    auth: Auth,
+    connection: &PgConnection,
+) -> RepositoryQueryResult<Vec<Extension>> {
+    if let Err(error) = assignments::repository::authorize(
+        assignment_id,
+        &auth,
+        Action::List,
+        Resource::Extension,
+        connection,
+    ) {
+        return RepositoryQueryResult::Err(error);
+    }
+    extensions::table
+        .filter(extensions::assignment_id.eq(assignment_id))
+        .order(extensions::due_at.asc())
+        .load::<Extension>(connection)
+        .into()
+}
+
+/// An extension can be seen by the student it was granted to and by everyone
+/// who is allowed to change the assignment.
+pub fn get(
+    assignment_id: Uuid,
+    user_id: Uuid,
+    auth: Auth,
+    connection: &PgConnection,
+) -> RepositoryQueryResult<Extension> {
+    let authorized = if auth.user_id == user_id {
+        policy::authorize(&auth, Relation::Author, Action::Read, Resource::Extension)
+    } else {
+        assignments::repository::authorize(
+            assignment_id,
+            &auth,
+            Action::Read,
+            Resource::Extension,
+            connection,
+        )
+    };
+    if let Err(error) = authorized {
+        return RepositoryQueryResult::Err(error);
+    }
+    match find(assignment_id, user_id, connection) {
+        Ok(Some(extension)) => RepositoryQueryResult::Ok(extension),
+        Ok(None) => RepositoryQueryResult::Err(RepositoryError::NotFound),
+        Err(err) => RepositoryQueryResult::Err(RepositoryError::QueryError(err)),
+    }
+}
+
+/// Grant an extension or move the due date of an existing one. Changes of
+/// extensions are recorded under the id of the assignment.
+pub fn grant(
+    assignment_id: Uuid,
+    mut insertable_extension: InsertableExtension,
+    auth: Auth,
+    audit: &AuditContext,
+    connection: &PgConnection,
+) -> RepositoryQueryResult<Extension> {
+    if let Err(error) = assignments::repository::authorize(
+        assignment_id,
+        &auth,
+        Action::Create,
+        Resource::Extension,
+        connection,
+    ) {
+        return RepositoryQueryResult::Err(error);
+    }
+    insertable_extension.assignment_id = assignment_id;
+    connection
+        .transaction::<_, RepositoryError<diesel::result::Error>, _>(|| {
+            let before: Option<Extension> = extensions::table
+                .find((assignment_id, insertable_extension.user_id))
+                .for_update()
+                .get_result(connection)
+                .optional()?;
+            let after: Extension = diesel::insert_into(extensions::table)
+                .values(&insertable_extension)
+                .on_conflict((extensions::assignment_id, extensions::user_id))
+                .do_update()
+                .set((
+                    extensions::due_at.eq(excluded(extensions::due_at)),
+                    extensions::updated.eq(Utc::now().naive_utc()),
+                ))
+                .get_result(connection)?;
+            audit::repository::record(
+                audit,
+                "grant",
+                Resource::Extension,
+                assignment_id,
+                before.as_ref(),
+                Some(&after),
+                connection,
+            )?;
+            Ok(after)
+        })
+        .into()
+}
+
+pub fn revoke(
+    assignment_id: Uuid,
+    user_id: Uuid,
+    auth: Auth,
+    audit: &AuditContext,
+    connection: &PgConnection,
+) -> RepositoryQueryResult<Extension> {
+    if let Err(error) = assignments::repository::authorize(
+        assignment_id,
+        &auth,
+        Action::Delete,
+        Resource::Extension,
+        connection,
+    ) {
+        return RepositoryQueryResult::Err(error);
+    }
+    connection
+        .transaction::<_, RepositoryError<diesel::result::Error>, _>(|| {
+            let extension: Extension =
+                diesel::delete(extensions::table.find((assignment_id, user_id)))
+                    .get_result(connection)
+                    .optional()?
+                    .ok_or(RepositoryError::NotFound)?;
+            audit::repository::record(
+                audit,
+                "revoke",
+                Resource::Extension,
+                assignment_id,
+                Some(&extension),
+                None,
+                connection,
+            )?;
+            Ok(extension)
+        })
+        .into()
+}
+
+/// Extension of a student for an assignment without authorization, used to
+/// decide whether a submission is accepted.
+pub fn find(
+    assignment_id: Uuid,
+    user_id: Uuid,
+    connection: &PgConnection,
+) -> QueryResult<Option<Extension>> {
+    extensions::table
+        .find((assignment_id, user_id))
+        .get_result(connection)
+        .optional()
+}
@@ -0,0 +1,475 @@
+use uuid::Uuid;
+
+/// Unchanged lines shown around every change, as in `diff -u`.
+const CONTEXT: usize = 3;
+
+/// Entries of the tables a diff of two files may build in total. The table of
+/// a text has an entry for every pair of old and new lines between the
+/// unchanged lines at the start and end, which takes 4 bytes, so a diff takes
+/// at most 64 MiB.
+pub const MAX_TABLE_CELLS: usize = 16 * 1024 * 1024;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum LineKind {
+    Context,
+    Added,
+    Removed,
+}
+
+#[derive(Serialize)]
+pub struct DiffLine {
+    pub kind: LineKind,
+    pub content: String,
+}
+
+#[derive(Serialize)]
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_lines: usize,
+    pub new_start: usize,
+    pub new_lines: usize,
+    pub lines: Vec<DiffLine>,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum PathChange {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// Line diff of a source file of a project, which is left out if the file is
+/// unchanged.
+#[derive(Serialize)]
+pub struct SourceFileDiff {
+    pub path: String,
+    pub change: PathChange,
+    pub unified: String,
+    pub hunks: Vec<Hunk>,
+}
+
+/// Text and source files of a file, with the source files ordered by path.
+pub struct FileContent<'a> {
+    pub id: Uuid,
+    pub text: &'a [u8],
+    pub source_files: &'a [(String, Vec<u8>)],
+}
+
+/// Line diff of two files, from the older `against` file to `file`. The text
+/// of single file submissions is diffed in `hunks`, the source files of
+/// projects by path in `source_files`. `unified` contains both.
+#[derive(Serialize)]
+pub struct FileDiff {
+    pub file_id: Uuid,
+    pub against_id: Uuid,
+    pub unified: String,
+    pub hunks: Vec<Hunk>,
+    pub source_files: Vec<SourceFileDiff>,
+}
+
+impl FileDiff {
+    /// Fails if diffing the files would take more than `MAX_TABLE_CELLS`.
+    pub fn new(file: FileContent, against: FileContent) -> Result<FileDiff, String> {
+        let old_text = String::from_utf8_lossy(against.text);
+        let new_text = String::from_utf8_lossy(file.text);
+        let changed_paths = changed_paths(against.source_files, file.source_files);
+        let cells = changed_paths
+            .iter()
+            .map(|changed_path| table_cells(&changed_path.old_text, &changed_path.new_text))
+            .fold(table_cells(&old_text, &new_text), usize::saturating_add);
+        if cells > MAX_TABLE_CELLS {
+            return Err(format!(
+                "the files differ in too many lines to diff them, at most {} line pairs are compared",
+                MAX_TABLE_CELLS
+            ));
+        }
+        let hunks = hunks(&old_text, &new_text);
+        let mut unified = unified(&against.id.to_string(), &file.id.to_string(), &hunks);
+        let source_files: Vec<SourceFileDiff> = changed_paths
+            .into_iter()
+            .map(SourceFileDiff::from)
+            .collect();
+        for source_file in &source_files {
+            unified.push_str(&source_file.unified);
+        }
+        Ok(FileDiff {
+            file_id: file.id,
+            against_id: against.id,
+            unified,
+            hunks,
+            source_files,
+        })
+    }
+}
+
+/// Entries of the table the diff of two texts builds, for the lines between
+/// the equal lines at their start and end.
+fn table_cells(old: &str, new: &str) -> usize {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let prefix = old_lines
+        .iter()
+        .zip(&new_lines)
+        .take_while(|(old_line, new_line)| old_line == new_line)
+        .count();
+    let suffix = old_lines[prefix..]
+        .iter()
+        .rev()
+        .zip(new_lines[prefix..].iter().rev())
+        .take_while(|(old_line, new_line)| old_line == new_line)
+        .count();
+    (old_lines.len() - prefix - suffix + 1).saturating_mul(new_lines.len() - prefix - suffix + 1)
+}
+
+/// A source file path that was added, removed or changed, with its old and
+/// new text, which is empty if the path doesn't exist on that side.
+struct ChangedPath {
+    path: String,
+    change: PathChange,
+    old_name: String,
+    new_name: String,
+    old_text: String,
+    new_text: String,
+}
+
+impl From<ChangedPath> for SourceFileDiff {
+    fn from(changed_path: ChangedPath) -> SourceFileDiff {
+        let hunks = hunks(&changed_path.old_text, &changed_path.new_text);
+        SourceFileDiff {
+            unified: unified(&changed_path.old_name, &changed_path.new_name, &hunks),
+            path: changed_path.path,
+            change: changed_path.change,
+            hunks,
+        }
+    }
+}
+
+/// Paths that were added, removed or changed, ordered by path.
+fn changed_paths(old: &[(String, Vec<u8>)], new: &[(String, Vec<u8>)]) -> Vec<ChangedPath> {
+    let mut paths: Vec<&String> = old.iter().chain(new.iter()).map(|(path, _)| path).collect();
+    paths.sort();
+    paths.dedup();
+    let text_of = |source_files: &'_ [(String, Vec<u8>)], path: &String| {
+        source_files
+            .iter()
+            .find(|(other, _)| other == path)
+            .map(|(_, text)| String::from_utf8_lossy(text).into_owned())
+    };
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let old_text = text_of(old, path);
+            let new_text = text_of(new, path);
+            let (change, old_name, new_name) = match (&old_text, &new_text) {
+                (None, Some(_)) => (
+                    PathChange::Added,
+                    "/dev/null".to_owned(),
+                    format!("b/{}", path),
+                ),
+                (Some(_), None) => (
+                    PathChange::Removed,
+                    format!("a/{}", path),
+                    "/dev/null".to_owned(),
+                ),
+                (Some(old_text), Some(new_text)) if old_text != new_text => (
+                    PathChange::Modified,
+                    format!("a/{}", path),
+                    format!("b/{}", path),
+                ),
+                _ => return None,
+            };
+            Some(ChangedPath {
+                path: path.clone(),
+                change,
+                old_name,
+                new_name,
+                old_text: old_text.unwrap_or_default(),
+                new_text: new_text.unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+/// A line of the diff with the number of old and new lines before it.
+struct Position<'a> {
+    kind: LineKind,
+    content: &'a str,
+    old_index: usize,
+    new_index: usize,
+}
+
+fn hunks(old: &str, new: &str) -> Vec<Hunk> {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let mut old_index = 0;
+    let mut new_index = 0;
+    let positions: Vec<Position> = diff::slice(&old_lines, &new_lines)
+        .into_iter()
+        .map(|result| {
+            let (kind, content) = match result {
+                diff::Result::Left(line) => (LineKind::Removed, *line),
+                diff::Result::Right(line) => (LineKind::Added, *line),
+                diff::Result::Both(line, _) => (LineKind::Context, *line),
+            };
+            let position = Position {
+                kind,
+                content,
+                old_index,
+                new_index,
+            };
+            if kind != LineKind::Added {
+                old_index += 1;
+            }
+            if kind != LineKind::Removed {
+                new_index += 1;
+            }
+            position
+        })
+        .collect();
+    let changes: Vec<usize> = positions
+        .iter()
+        .enumerate()
+        .filter(|(_, position)| position.kind != LineKind::Context)
+        .map(|(index, _)| index)
+        .collect();
+
+    let mut hunks = Vec::new();
+    let mut next = 0;
+    while next < changes.len() {
+        let first = changes[next];
+        let mut last = first;
+        next += 1;
+        // changes separated by at most twice the context share one hunk
+        while next < changes.len() && changes[next] - last - 1 <= 2 * CONTEXT {
+            last = changes[next];
+            next += 1;
+        }
+        let start = first.saturating_sub(CONTEXT);
+        let end = (last + CONTEXT).min(positions.len() - 1);
+        hunks.push(hunk(&positions[start..=end]));
+    }
+    hunks
+}
+
+fn hunk(positions: &[Position]) -> Hunk {
+    let old_lines = positions
+        .iter()
+        .filter(|position| position.kind != LineKind::Added)
+        .count();
+    let new_lines = positions
+        .iter()
+        .filter(|position| position.kind != LineKind::Removed)
+        .count();
+    // an empty range starts at the line before it, as in `diff -u`
+    let old_start = positions[0].old_index + if old_lines == 0 { 0 } else { 1 };
+    let new_start = positions[0].new_index + if new_lines == 0 { 0 } else { 1 };
+    Hunk {
+        old_start,
+        old_lines,
+        new_start,
+        new_lines,
+        lines: positions
+            .iter()
+            .map(|position| DiffLine {
+                kind: position.kind,
+                content: position.content.to_owned(),
+            })
+            .collect(),
+    }
+}
+
+fn unified(old_name: &str, new_name: &str, hunks: &[Hunk]) -> String {
+    if hunks.is_empty() {
+        return String::new();
+    }
+    let mut unified = format!("--- {}\n+++ {}\n", old_name, new_name);
+    for hunk in hunks {
+        unified.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            hunk.old_start, hunk.old_lines, hunk.new_start, hunk.new_lines
+        ));
+        for line in &hunk.lines {
+            let prefix = match line.kind {
+                LineKind::Context => ' ',
+                LineKind::Added => '+',
+                LineKind::Removed => '-',
+            };
+            unified.push(prefix);
+            unified.push_str(&line.content);
+            unified.push('\n');
+        }
+    }
+    unified
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbered_lines(count: usize) -> Vec<String> {
+        (1..=count).map(|line| format!("line {}", line)).collect()
+    }
+
+    /// The lines with the numbered lines replaced by changed ones.
+    fn changed(lines: &[String], changed: &[usize]) -> String {
+        lines
+            .iter()
+            .enumerate()
+            .map(|(index, line)| {
+                if changed.contains(&(index + 1)) {
+                    format!("{} changed\n", line)
+                } else {
+                    format!("{}\n", line)
+                }
+            })
+            .collect()
+    }
+
+    fn ranges(hunks: &[Hunk]) -> Vec<(usize, usize, usize, usize)> {
+        hunks
+            .iter()
+            .map(|hunk| {
+                (
+                    hunk.old_start,
+                    hunk.old_lines,
+                    hunk.new_start,
+                    hunk.new_lines,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn equal_texts_have_no_hunks() {
+        let text = changed(&numbered_lines(5), &[]);
+        assert!(hunks(&text, &text).is_empty());
+        assert_eq!(unified("a", "b", &[]), "");
+    }
+
+    #[test]
+    fn a_change_is_shown_with_its_context() {
+        let lines = numbered_lines(20);
+        let hunks = hunks(&changed(&lines, &[]), &changed(&lines, &[10]));
+        assert_eq!(ranges(&hunks), vec![(7, 7, 7, 7)]);
+        assert_eq!(hunks[0].lines.len(), 8);
+        assert_eq!(hunks[0].lines[3].kind, LineKind::Removed);
+        assert_eq!(hunks[0].lines[4].kind, LineKind::Added);
+    }
+
+    #[test]
+    fn context_is_cut_at_the_start_and_end() {
+        let lines = numbered_lines(10);
+        let hunks = hunks(&changed(&lines, &[]), &changed(&lines, &[1, 10]));
+        assert_eq!(ranges(&hunks), vec![(1, 4, 1, 4), (7, 4, 7, 4)]);
+    }
+
+    #[test]
+    fn changes_within_twice_the_context_share_a_hunk() {
+        let lines = numbered_lines(30);
+        let joined = hunks(&changed(&lines, &[]), &changed(&lines, &[10, 17]));
+        assert_eq!(ranges(&joined), vec![(7, 14, 7, 14)]);
+        let split = hunks(&changed(&lines, &[]), &changed(&lines, &[10, 18]));
+        assert_eq!(ranges(&split), vec![(7, 7, 7, 7), (15, 7, 15, 7)]);
+    }
+
+    #[test]
+    fn empty_ranges_start_at_the_line_before() {
+        let added = hunks("", "a\nb\n");
+        assert_eq!(ranges(&added), vec![(0, 0, 1, 2)]);
+        let removed = hunks("a\nb\n", "");
+        assert_eq!(ranges(&removed), vec![(1, 2, 0, 0)]);
+        let appended = hunks("a\n", "a\nb\n");
+        assert_eq!(ranges(&appended), vec![(1, 1, 1, 2)]);
+    }
+
+    #[test]
+    fn unified_diff_has_headers_and_prefixes() {
+        let hunks = hunks("a\nb\n", "a\nc\n");
+        assert_eq!(
+            unified("old", "new", &hunks),
+            "--- old\n+++ new\n@@ -1,2 +1,2 @@\n a\n-b\n+c\n"
+        );
+    }
+
+    #[test]
+    fn source_files_are_diffed_by_path() {
+        let old = vec![
+            ("main.c".to_owned(), b"int main;\n".to_vec()),
+            ("old.h".to_owned(), b"old\n".to_vec()),
+            ("same.h".to_owned(), b"same\n".to_vec()),
+        ];
+        let new = vec![
+            ("main.c".to_owned(), b"int main();\n".to_vec()),
+            ("new.h".to_owned(), b"new\n".to_vec()),
+            ("same.h".to_owned(), b"same\n".to_vec()),
+        ];
+        let diffs: Vec<SourceFileDiff> = changed_paths(&old, &new)
+            .into_iter()
+            .map(SourceFileDiff::from)
+            .collect();
+        let changes: Vec<(&str, PathChange)> = diffs
+            .iter()
+            .map(|diff| (diff.path.as_str(), diff.change))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                ("main.c", PathChange::Modified),
+                ("new.h", PathChange::Added),
+                ("old.h", PathChange::Removed),
+            ]
+        );
+        assert!(diffs[1].unified.starts_with("--- /dev/null\n+++ b/new.h\n"));
+        assert!(diffs[2].unified.starts_with("--- a/old.h\n+++ /dev/null\n"));
+    }
+
+    fn content<'a>(text: &'a [u8], source_files: &'a [(String, Vec<u8>)]) -> FileContent<'a> {
+        FileContent {
+            id: Uuid::nil(),
+            text,
+            source_files,
+        }
+    }
+
+    #[test]
+    fn unchanged_lines_around_changes_are_not_compared() {
+        let lines = numbered_lines(100_000);
+        let old = changed(&lines, &[]);
+        let new = changed(&lines, &[50_000]);
+        assert_eq!(table_cells(&old, &new), 4);
+        assert_eq!(table_cells(&old, &old), 1);
+        assert!(FileDiff::new(content(new.as_bytes(), &[]), content(old.as_bytes(), &[])).is_ok());
+    }
+
+    #[test]
+    fn too_large_diffs_are_rejected() {
+        let lines = numbered_lines(5_000);
+        let old = changed(&lines, &[]);
+        let new = changed(&lines, &(1..=5_000).collect::<Vec<usize>>());
+        assert!(table_cells(&old, &new) > MAX_TABLE_CELLS);
+        assert!(FileDiff::new(content(new.as_bytes(), &[]), content(old.as_bytes(), &[])).is_err());
+
+        // the tables of all source files count together
+        let half = changed(&lines[..3_000], &(1..=3_000).collect::<Vec<usize>>());
+        let old_source_files = vec![
+            ("a.c".to_owned(), changed(&lines[..3_000], &[]).into_bytes()),
+            ("b.c".to_owned(), changed(&lines[..3_000], &[]).into_bytes()),
+        ];
+        let new_source_files = vec![
+            ("a.c".to_owned(), half.clone().into_bytes()),
+            ("b.c".to_owned(), half.into_bytes()),
+        ];
+        assert!(FileDiff::new(
+            content(b"", &new_source_files[..1]),
+            content(b"", &old_source_files[..1])
+        )
+        .is_ok());
+        assert!(FileDiff::new(
+            content(b"", &new_source_files),
+            content(b"", &old_source_files)
+        )
+        .is_err());
+    }
+}
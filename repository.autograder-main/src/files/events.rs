@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+use std::env;
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::NaiveDateTime;
+use postgres::fallible_iterator::FallibleIterator;
+use postgres::{Client, NoTls};
+use uuid::Uuid;
+
+use crate::connection;
+use crate::files::status::FileStatus;
+
+/// Channel the `files_notify_event` trigger notifies on.
+const CHANNEL: &str = "file_events";
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(5);
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+/// A stream ends after this time and the client reconnects, so that streams of
+/// runs that never finish don't occupy a worker thread forever.
+const MAX_STREAM_DURATION: Duration = Duration::from_secs(5 * 60);
+const MAX_STREAMS_ENV: &str = "FILE_EVENT_STREAMS";
+const DEFAULT_MAX_STREAMS: usize = 8;
+
+/// Status change of a file, as sent by the database and to clients.
+#[derive(Queryable, Serialize, Deserialize, Clone)]
+pub struct FileEvent {
+    pub file_id: Uuid,
+    pub status: FileStatus,
+    pub updated: NaiveDateTime,
+}
+
+/// Subscribers of the events of files in this instance of the service. Every
+/// subscriber occupies a worker thread, so at most `FILE_EVENT_STREAMS` (8 by
+/// default) can subscribe at the same time.
+#[derive(Clone)]
+pub struct FileEvents {
+    subscribers: Arc<Mutex<HashMap<Uuid, Vec<(u64, Sender<FileEvent>)>>>>,
+    next_id: Arc<AtomicU64>,
+    streams: Arc<AtomicUsize>,
+    max_streams: usize,
+}
+
+impl FileEvents {
+    pub fn from_env() -> Self {
+        FileEvents {
+            subscribers: Arc::default(),
+            next_id: Arc::default(),
+            streams: Arc::default(),
+            max_streams: env::var(MAX_STREAMS_ENV)
+                .ok()
+                .and_then(|max_streams| max_streams.parse().ok())
+                .unwrap_or(DEFAULT_MAX_STREAMS),
+        }
+    }
+
+    /// Subscribe to the events of a file, or `None` if too many streams are
+    /// open already.
+    pub fn subscribe(&self, file_id: Uuid) -> Option<Subscription> {
+        let max_streams = self.max_streams;
+        let reserved = self
+            .streams
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |streams| {
+                if streams < max_streams {
+                    Some(streams + 1)
+                } else {
+                    None
+                }
+            });
+        if reserved.is_err() {
+            return None;
+        }
+        let (sender, receiver) = mpsc::channel();
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.subscribers
+            .lock()
+            .unwrap()
+            .entry(file_id)
+            .or_default()
+            .push((id, sender));
+        Some(Subscription {
+            file_events: self.clone(),
+            file_id,
+            id,
+            receiver,
+        })
+    }
+
+    fn publish(&self, event: FileEvent) {
+        if let Some(senders) = self.subscribers.lock().unwrap().get(&event.file_id) {
+            for (_, sender) in senders {
+                let _ = sender.send(event.clone());
+            }
+        }
+    }
+
+    fn unsubscribe(&self, file_id: Uuid, id: u64) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        if let Some(senders) = subscribers.get_mut(&file_id) {
+            senders.retain(|(sender_id, _)| *sender_id != id);
+            if senders.is_empty() {
+                subscribers.remove(&file_id);
+            }
+        }
+        self.streams.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+pub struct Subscription {
+    file_events: FileEvents,
+    file_id: Uuid,
+    id: u64,
+    receiver: Receiver<FileEvent>,
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.file_events.unsubscribe(self.file_id, self.id);
+    }
+}
+
+/// Start the background thread that listens for the notifications of the
+/// database, so that every instance of the service sees the changes made by
+/// the others. Notifications sent while the listener reconnects are lost.
+pub fn listen(file_events: FileEvents) {
+    thread::Builder::new()
+        .name("file-events".to_owned())
+        .spawn(move || loop {
+            if let Err(err) = receive(&file_events) {
+                error!("File event listener lost its database connection: {}", err);
+            }
+            thread::sleep(RECONNECT_INTERVAL);
+        })
+        .expect("could not start file event listener");
+}
+
+fn receive(file_events: &FileEvents) -> Result<(), postgres::Error> {
+    let mut client = Client::connect(&connection::database_url(), NoTls)?;
+    client.batch_execute(&format!("LISTEN {}", CHANNEL))?;
+    let mut notifications = client.notifications();
+    let mut iter = notifications.blocking_iter();
+    while let Some(notification) = iter.next()? {
+        match serde_json::from_str(notification.payload()) {
+            Ok(event) => file_events.publish(event),
+            Err(err) => error!("Invalid file event {}: {}", notification.payload(), err),
+        }
+    }
+    Ok(())
+}
+
+/// Server-Sent Events of a file, starting with its current status. Events
+/// older than the last one sent are skipped, and the stream ends after the
+/// run of the file finished, or after `MAX_STREAM_DURATION`, when clients
+/// reconnect and start again with the current status. A comment is sent while
+/// nothing changes to notice closed connections.
+pub struct EventStream {
+    subscription: Subscription,
+    last_updated: NaiveDateTime,
+    buffer: Vec<u8>,
+    flush: bool,
+    finished: bool,
+    ends_at: Instant,
+}
+
+impl EventStream {
+    pub fn new(current: FileEvent, subscription: Subscription) -> Self {
+        let mut stream = EventStream {
+            subscription,
+            last_updated: current.updated,
+            buffer: Vec::new(),
+            flush: false,
+            finished: false,
+            ends_at: Instant::now() + MAX_STREAM_DURATION,
+        };
+        stream.push(&current);
+        stream
+    }
+
+    fn push(&mut self, event: &FileEvent) {
+        self.last_updated = event.updated;
+        self.finished = event.status.is_finished();
+        self.buffer.extend_from_slice(
+            format!(
+                "event: status\ndata: {}\n\n",
+                serde_json::to_string(event).unwrap_or_default()
+            )
+            .as_bytes(),
+        );
+    }
+}
+
+impl Read for EventStream {
+    /// Every event is followed by a `WouldBlock` error, which makes Rocket send
+    /// it right away instead of waiting for a full chunk.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.buffer.is_empty() {
+            if self.flush {
+                self.flush = false;
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            let now = Instant::now();
+            if self.finished || now >= self.ends_at {
+                return Ok(0);
+            }
+            let timeout = KEEP_ALIVE_INTERVAL.min(self.ends_at - now);
+            match self.subscription.receiver.recv_timeout(timeout) {
+                Ok(event) if event.updated < self.last_updated => {}
+                Ok(event) => self.push(&event),
+                Err(RecvTimeoutError::Timeout) => {
+                    self.buffer.extend_from_slice(b": keep-alive\n\n")
+                }
+                Err(RecvTimeoutError::Disconnected) => return Ok(0),
+            }
+        }
+        let length = buf.len().min(self.buffer.len());
+        buf[..length].copy_from_slice(&self.buffer[..length]);
+        self.buffer.drain(..length);
+        self.flush = self.buffer.is_empty();
+        Ok(length)
+    }
+}
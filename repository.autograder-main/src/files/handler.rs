@@ -1,18 +1,22 @@
-use std::env;
 use std::str::FromStr;
 
-use jsonwebtoken::EncodingKey;
-use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE, USER_AGENT};
-use rocket::http::Status;
+use rocket::http::{ContentType, Status};
+use rocket::response::content::Content;
+use rocket::response::Stream;
 use rocket::State;
 use rocket_contrib::json::Json;
 use uuid::Uuid;
 
-use crate::auth::Auth;
-use crate::config::AppState;
+use crate::audit::{AuditContext, RequestId};
+use crate::auth::{Auth, RunnerAuth};
 use crate::connection::DbConn;
 use crate::files;
-use crate::files::{File, ScheduleOutputFile, ScheduleTriggerFile};
+use crate::files::diff::FileDiff;
+use crate::files::events::{EventStream, FileEvent, FileEvents};
+use crate::files::{
+    File, FileWithResults, ScheduleOutputFile, SourceFile, StatusUpdate, TestCaseResult,
+};
+use crate::jobs::Job;
 
 #[patch(
     "/<id>/output",
@@ -20,7 +24,8 @@ use crate::files::{File, ScheduleOutputFile, ScheduleTriggerFile};
     data = "<schedule_output_file>"
 )]
 pub fn patch_output(
-    auth: Auth,
+    runner_auth: RunnerAuth,
+    request_id: RequestId,
     id: String,
     schedule_output_file: Json<ScheduleOutputFile>,
     connection: DbConn,
@@ -30,100 +35,147 @@ pub fn patch_output(
         .and_then(|uuid| {
             files::repository::update_output(
                 uuid,
-                auth,
+                runner_auth,
                 schedule_output_file.into_inner(),
+                &AuditContext::runner(Some(request_id)),
                 &connection,
             )
             .into()
         })
 }
 
-#[patch("/<id>", format = "application/json", data = "<schedule_trigger_file>")]
+#[patch("/<id>", format = "application/json", data = "<status_update>")]
 pub fn patch(
     auth: Auth,
-    state: State<AppState>,
+    request_id: RequestId,
     id: String,
-    schedule_trigger_file: Json<ScheduleTriggerFile>,
+    status_update: Json<StatusUpdate>,
     connection: DbConn,
 ) -> Result<Json<File>, rocket::http::Status> {
     Uuid::from_str(&id)
         .map_err(|_| Status::BadRequest)
         .and_then(|uuid| {
-            let updatable_file = schedule_trigger_file.into_inner();
-            let update_result: Result<Json<File>, rocket::http::Status> =
-                files::repository::update(uuid, &auth, &updatable_file, &connection).into();
-            update_result.map(|file| (file.into_inner(), updatable_file))
-        })
-        .and_then(|(file, schedule_trigger_file)| {
-            schedule_run(
+            files::repository::update_status(
+                uuid,
                 &auth,
-                &state.encoding_key,
-                file,
-                schedule_trigger_file,
-                connection,
+                status_update.into_inner(),
+                &AuditContext::user(&auth, request_id),
+                &connection,
             )
+            .into()
         })
 }
 
+#[patch("/<id>/status", format = "application/json", data = "<status_update>")]
+pub fn patch_status(
+    runner_auth: RunnerAuth,
+    request_id: RequestId,
+    id: String,
+    status_update: Json<StatusUpdate>,
+    connection: DbConn,
+) -> Result<Json<File>, rocket::http::Status> {
+    Uuid::from_str(&id)
+        .map_err(|_| Status::BadRequest)
+        .and_then(|uuid| {
+            files::repository::update_status_by_runner(
+                uuid,
+                &runner_auth,
+                status_update.into_inner(),
+                &AuditContext::runner(Some(request_id)),
+                &connection,
+            )
+            .into()
+        })
+}
+
+#[get("/<id>/job")]
+pub fn get_job(
+    auth: Auth,
+    id: String,
+    connection: DbConn,
+) -> Result<Json<Job>, rocket::http::Status> {
+    Uuid::from_str(&id)
+        .map_err(|_| Status::BadRequest)
+        .and_then(|uuid| files::repository::get_job(uuid, auth, &connection).into())
+}
+
 #[get("/<id>")]
-pub fn get(auth: Auth, id: String, connection: DbConn) -> Result<Json<File>, rocket::http::Status> {
+pub fn get(
+    auth: Auth,
+    id: String,
+    connection: DbConn,
+) -> Result<Json<FileWithResults>, rocket::http::Status> {
     Uuid::from_str(&id)
         .map_err(|_| Status::BadRequest)
         .and_then(|uuid| files::repository::get_by_uuid(uuid, auth, &connection).into())
 }
 
-#[get("/?<submission_id>")]
-pub fn get_by_submission_id(
+/// Status changes of a file as Server-Sent Events. Every stream occupies a
+/// worker thread until the run of the file finished, the stream ends or the
+/// client is gone, so too many open streams are answered with 503.
+#[get("/<id>/events")]
+pub fn get_events(
     auth: Auth,
-    submission_id: String,
+    id: String,
+    file_events: State<FileEvents>,
     connection: DbConn,
-) -> Result<Json<File>, rocket::http::Status> {
-    Uuid::from_str(&submission_id)
+) -> Result<Content<Stream<EventStream>>, rocket::http::Status> {
+    let uuid = Uuid::from_str(&id).map_err(|_| Status::BadRequest)?;
+    let subscription = file_events
+        .subscribe(uuid)
+        .ok_or(Status::ServiceUnavailable)?;
+    let current: Result<Json<FileEvent>, Status> =
+        files::repository::get_event(uuid, auth, &connection).into();
+    current.map(|current| {
+        Content(
+            ContentType::new("text", "event-stream"),
+            Stream::chunked(EventStream::new(current.into_inner(), subscription), 1024),
+        )
+    })
+}
+
+#[get("/<id>/results")]
+pub fn get_results(
+    auth: Auth,
+    id: String,
+    connection: DbConn,
+) -> Result<Json<Vec<TestCaseResult>>, rocket::http::Status> {
+    Uuid::from_str(&id)
         .map_err(|_| Status::BadRequest)
-        .and_then(|uuid| files::repository::get_by_submission_id(uuid, auth, &connection).into())
+        .and_then(|uuid| files::repository::get_results(uuid, auth, &connection).into())
 }
 
-fn schedule_run(
-    auth: &Auth,
-    key: &EncodingKey,
-    file: File,
-    mut schedule_trigger_file: ScheduleTriggerFile,
+#[get("/<id>/sources")]
+pub fn get_sources(
+    auth: Auth,
+    id: String,
     connection: DbConn,
-) -> Result<Json<File>, Status> {
-    env::var("SCHEDULING_SUBMISSION_URL")
-        .map(|url| (url, file))
+) -> Result<Json<Vec<SourceFile>>, rocket::http::Status> {
+    Uuid::from_str(&id)
         .map_err(|_| Status::BadRequest)
-        .and_then(|(scheduling_url, file)| {
-            files::repository::get_schedule_file(file.id, &connection)
-                .map(|schedule_file| (scheduling_url, file, schedule_file))
-                .map_err(|_| Status::InternalServerError)
-        })
-        .and_then(|(scheduling_url, file, schedule_file)| {
-            reqwest::blocking::Client::new()
-                .post(&scheduling_url)
-                .headers(construct_headers())
-                .bearer_auth(auth.token(key))
-                .json(&schedule_file)
-                .send()
-                .map(|_| Json(file))
-                .map_err(|_| {
-                    schedule_trigger_file.scheduled = false;
-                    let update_result: Result<Json<File>, rocket::http::Status> =
-                        files::repository::update(
-                            schedule_trigger_file.id,
-                            auth,
-                            &schedule_trigger_file,
-                            &connection,
-                        )
-                        .into();
-                    update_result.map_or(Status::NotFound, |_| Status::InternalServerError)
-                })
-        })
+        .and_then(|uuid| files::repository::get_sources(uuid, auth, &connection).into())
 }
 
-fn construct_headers() -> HeaderMap {
-    let mut headers = HeaderMap::new();
-    headers.insert(USER_AGENT, HeaderValue::from_static("reqwest"));
-    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-    headers
+#[get("/<id>/diff?<against>")]
+pub fn diff(
+    auth: Auth,
+    id: String,
+    against: String,
+    connection: DbConn,
+) -> Result<Json<FileDiff>, rocket::http::Status> {
+    Uuid::from_str(&id)
+        .and_then(|id| Uuid::from_str(&against).map(|against| (id, against)))
+        .map_err(|_| Status::BadRequest)
+        .and_then(|(id, against)| files::repository::diff(id, against, auth, &connection).into())
+}
+
+#[get("/?<submission_id>")]
+pub fn get_by_submission_id(
+    auth: Auth,
+    submission_id: String,
+    connection: DbConn,
+) -> Result<Json<File>, rocket::http::Status> {
+    Uuid::from_str(&submission_id)
+        .map_err(|_| Status::BadRequest)
+        .and_then(|uuid| files::repository::get_by_submission_id(uuid, auth, &connection).into())
 }
@@ -1,19 +1,21 @@
-use base64::DecodeError;
 use chrono::NaiveDateTime;
 use serde::Deserialize;
 use uuid::Uuid;
 
-use crate::connection::{
-    deserialize_base64, deserialize_optional_base64, serialize_base64, serialize_optional_base64,
-};
-use crate::submissions::InsertableCode;
+use crate::assignments::comparison::Comparison;
+use crate::connection::{deserialize_base64, deserialize_optional_base64, serialize_base64};
+use crate::files::status::FileStatus;
+use crate::test_cases::TestCase;
 
-use super::schema::files;
+use super::schema::{files, run_results, source_files, test_case_results};
 use super::submissions::Submission;
 
+pub mod diff;
+pub mod events;
 pub mod handler;
 pub mod repository;
 pub mod router;
+pub mod status;
 
 #[derive(Identifiable, Queryable, Associations, PartialEq, Debug, Serialize, Deserialize)]
 #[belongs_to(Submission)]
@@ -23,12 +25,34 @@ pub struct File {
     pub updated: NaiveDateTime,
     #[serde(serialize_with = "serialize_base64")]
     pub encoded_text: Vec<u8>,
-    pub scheduled: bool,
-    pub validated: bool,
-    #[serde(serialize_with = "serialize_optional_base64")]
-    #[serde(deserialize_with = "deserialize_optional_base64")]
-    #[serde(default)]
-    pub encoded_output: Option<Vec<u8>>,
+    pub score: f64,
+    pub max_score: f64,
+    pub late: bool,
+    pub status: FileStatus,
+    pub queued_at: Option<NaiveDateTime>,
+    pub started_at: Option<NaiveDateTime>,
+    pub finished_at: Option<NaiveDateTime>,
+    pub run_retries: i16,
+    pub status_reason: Option<String>,
+    pub created: NaiveDateTime,
+}
+
+/// File without its content, used to list the versions of a submission.
+#[derive(Queryable, Serialize)]
+pub struct FileVersion {
+    pub id: Uuid,
+    pub submission_id: Uuid,
+    pub updated: NaiveDateTime,
+    pub score: f64,
+    pub max_score: f64,
+    pub late: bool,
+    pub status: FileStatus,
+    pub queued_at: Option<NaiveDateTime>,
+    pub started_at: Option<NaiveDateTime>,
+    pub finished_at: Option<NaiveDateTime>,
+    pub run_retries: i16,
+    pub status_reason: Option<String>,
+    pub created: NaiveDateTime,
 }
 
 #[derive(Insertable, Associations)]
@@ -37,36 +61,254 @@ pub struct File {
 pub struct InsertableFile {
     pub submission_id: Uuid,
     pub encoded_text: Vec<u8>,
+    pub late: bool,
 }
 
-#[derive(Identifiable, Queryable, Deserialize, AsChangeset, Copy, Clone)]
-#[table_name = "files"]
-pub struct ScheduleTriggerFile {
+/// File of a project submission, identified by its path relative to the
+/// project root.
+#[derive(Identifiable, Queryable, Associations, PartialEq, Debug, Serialize)]
+#[belongs_to(File)]
+pub struct SourceFile {
     pub id: Uuid,
-    pub scheduled: bool,
+    pub file_id: Uuid,
+    pub path: String,
+    #[serde(serialize_with = "serialize_base64")]
+    pub encoded_text: Vec<u8>,
 }
 
-#[derive(Deserialize, AsChangeset)]
-#[table_name = "files"]
+#[derive(Deserialize, Insertable)]
+#[table_name = "source_files"]
+pub struct InsertableSourceFile {
+    #[serde(skip)]
+    pub file_id: Uuid,
+    pub path: String,
+    #[serde(deserialize_with = "deserialize_base64")]
+    pub encoded_text: Vec<u8>,
+}
+
+impl InsertableSourceFile {
+    pub fn new(path: String, encoded_text: Vec<u8>) -> Self {
+        InsertableSourceFile {
+            file_id: Uuid::nil(),
+            path,
+            encoded_text,
+        }
+    }
+}
+
+/// Status a client or runner moves a file to.
+#[derive(Deserialize, Copy, Clone)]
+pub struct StatusUpdate {
+    pub status: FileStatus,
+}
+
+/// File waiting for or in a run, with the run limits of its assignment.
+#[derive(Queryable)]
+pub struct PendingRun {
+    pub id: Uuid,
+    pub status: FileStatus,
+    pub updated: NaiveDateTime,
+    pub started_at: Option<NaiveDateTime>,
+    pub run_retries: i16,
+    pub run_timeout_seconds: Option<i32>,
+    pub max_run_retries: i16,
+}
+
+impl PendingRun {
+    /// Run timeout of the assignment, or the default if it has none.
+    pub fn timeout_seconds(&self, default_timeout_seconds: i64) -> i64 {
+        self.run_timeout_seconds
+            .map_or(default_timeout_seconds, i64::from)
+    }
+}
+
+/// Output of a run. A runner that could not run the test cases sends the
+/// error status of the run, otherwise the file passes or fails depending on
+/// its results. The output and resource usage of the whole run, for example
+/// of its compilation, are optional.
+#[derive(Deserialize, Default)]
 pub struct ScheduleOutputFile {
+    #[serde(default)]
+    pub status: Option<FileStatus>,
+    #[serde(deserialize_with = "deserialize_optional_base64")]
+    #[serde(default)]
+    pub encoded_stdout: Option<Vec<u8>>,
+    #[serde(deserialize_with = "deserialize_optional_base64")]
+    #[serde(default)]
+    pub encoded_stderr: Option<Vec<u8>>,
+    #[serde(default)]
+    pub exit_code: Option<i32>,
+    #[serde(default)]
+    pub signal: Option<i32>,
+    #[serde(default)]
+    pub duration_ms: Option<i64>,
+    #[serde(default)]
+    pub memory_kb: Option<i64>,
+    #[serde(default)]
+    pub results: Vec<ScheduleOutputTestCase>,
+}
+
+#[derive(Deserialize)]
+pub struct ScheduleOutputTestCase {
+    pub test_case_id: Uuid,
     #[serde(deserialize_with = "deserialize_base64")]
     pub encoded_output: Vec<u8>,
+    #[serde(deserialize_with = "deserialize_optional_base64")]
+    #[serde(default)]
+    pub encoded_stderr: Option<Vec<u8>>,
+    #[serde(default)]
+    pub exit_code: Option<i32>,
+    #[serde(default)]
+    pub signal: Option<i32>,
+    #[serde(default)]
+    pub duration_ms: Option<i64>,
+    #[serde(default)]
+    pub memory_kb: Option<i64>,
 }
 
-#[derive(Queryable)]
-pub struct ValidatableFile {
+/// Stored result of a test case, to validate it again.
+impl From<TestCaseResult> for ScheduleOutputTestCase {
+    fn from(test_case_result: TestCaseResult) -> Self {
+        ScheduleOutputTestCase {
+            test_case_id: test_case_result.test_case_id,
+            encoded_output: test_case_result.encoded_output,
+            encoded_stderr: Some(test_case_result.encoded_stderr),
+            exit_code: test_case_result.exit_code,
+            signal: test_case_result.signal,
+            duration_ms: test_case_result.duration_ms,
+            memory_kb: test_case_result.memory_kb,
+        }
+    }
+}
+
+/// Output and resource usage of the last run of a file as a whole.
+#[derive(Identifiable, Queryable, Associations, PartialEq, Debug, Serialize)]
+#[primary_key(file_id)]
+#[belongs_to(File)]
+pub struct RunResult {
+    pub file_id: Uuid,
+    #[serde(serialize_with = "serialize_base64")]
+    pub encoded_stdout: Vec<u8>,
+    #[serde(serialize_with = "serialize_base64")]
+    pub encoded_stderr: Vec<u8>,
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+    pub duration_ms: Option<i64>,
+    pub memory_kb: Option<i64>,
+    pub updated: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "run_results"]
+pub struct InsertableRunResult {
+    pub file_id: Uuid,
+    pub encoded_stdout: Vec<u8>,
+    pub encoded_stderr: Vec<u8>,
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+    pub duration_ms: Option<i64>,
+    pub memory_kb: Option<i64>,
+    pub updated: NaiveDateTime,
+}
+
+impl InsertableRunResult {
+    pub fn new(
+        id: Uuid,
+        schedule_output_file: &mut ScheduleOutputFile,
+        updated: NaiveDateTime,
+    ) -> Self {
+        InsertableRunResult {
+            file_id: id,
+            encoded_stdout: schedule_output_file
+                .encoded_stdout
+                .take()
+                .unwrap_or_default(),
+            encoded_stderr: schedule_output_file
+                .encoded_stderr
+                .take()
+                .unwrap_or_default(),
+            exit_code: schedule_output_file.exit_code,
+            signal: schedule_output_file.signal,
+            duration_ms: schedule_output_file.duration_ms,
+            memory_kb: schedule_output_file.memory_kb,
+            updated,
+        }
+    }
+}
+
+/// File with the results of its last run.
+#[derive(Serialize)]
+pub struct FileWithResults {
+    #[serde(flatten)]
+    pub file: File,
+    pub run: Option<RunResult>,
+    pub results: Vec<TestCaseResult>,
+}
+
+#[derive(Identifiable, Queryable, Associations, PartialEq, Debug, Serialize)]
+#[belongs_to(File)]
+#[belongs_to(TestCase)]
+pub struct TestCaseResult {
     pub id: Uuid,
-    pub validated: bool,
-    pub encoded_output: Option<Vec<u8>>,
-    pub encoded_sample_output: Vec<u8>,
+    pub file_id: Uuid,
+    pub test_case_id: Uuid,
+    #[serde(serialize_with = "serialize_base64")]
+    pub encoded_output: Vec<u8>,
+    pub passed: bool,
     pub updated: NaiveDateTime,
+    #[serde(serialize_with = "serialize_base64")]
+    pub encoded_stderr: Vec<u8>,
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+    pub duration_ms: Option<i64>,
+    pub memory_kb: Option<i64>,
 }
 
-impl ValidatableFile {
+pub struct ValidatableTestCase<'a> {
+    pub file_id: Uuid,
+    pub comparison: &'a Comparison,
+    pub test_case: &'a TestCase,
+    pub result: ScheduleOutputTestCase,
+    pub updated: NaiveDateTime,
+}
+
+impl ValidatableTestCase<'_> {
     pub fn is_valid(&self) -> bool {
-        match &self.encoded_output {
-            None => false,
-            Some(encoded_output) => self.encoded_sample_output.eq(encoded_output),
+        self.comparison
+            .matches(&self.test_case.encoded_output, &self.result.encoded_output)
+    }
+}
+
+#[derive(Insertable)]
+#[table_name = "test_case_results"]
+pub struct ValidatedTestCase {
+    pub file_id: Uuid,
+    pub test_case_id: Uuid,
+    pub encoded_output: Vec<u8>,
+    pub passed: bool,
+    pub updated: NaiveDateTime,
+    pub encoded_stderr: Vec<u8>,
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+    pub duration_ms: Option<i64>,
+    pub memory_kb: Option<i64>,
+}
+
+impl<'a> From<ValidatableTestCase<'a>> for ValidatedTestCase {
+    fn from(validatable_test_case: ValidatableTestCase<'a>) -> ValidatedTestCase {
+        let passed = validatable_test_case.is_valid();
+        let result = validatable_test_case.result;
+        ValidatedTestCase {
+            passed,
+            file_id: validatable_test_case.file_id,
+            test_case_id: validatable_test_case.test_case.id,
+            encoded_output: result.encoded_output,
+            updated: validatable_test_case.updated,
+            encoded_stderr: result.encoded_stderr.unwrap_or_default(),
+            exit_code: result.exit_code,
+            signal: result.signal,
+            duration_ms: result.duration_ms,
+            memory_kb: result.memory_kb,
         }
     }
 }
@@ -75,24 +317,56 @@ impl ValidatableFile {
 #[table_name = "files"]
 pub struct ValidatedFile {
     pub id: Uuid,
-    pub validated: bool,
-    pub encoded_output: Option<Vec<u8>>,
+    pub status: FileStatus,
+    pub score: f64,
+    pub max_score: f64,
     pub updated: NaiveDateTime,
+    pub finished_at: NaiveDateTime,
 }
 
-impl From<ValidatableFile> for ValidatedFile {
-    fn from(validatable_file: ValidatableFile) -> ValidatedFile {
+impl ValidatedFile {
+    /// Score a file with the weights of its passed test cases, reduced by the
+    /// given penalty. The file passed if every test case of the assignment
+    /// passed.
+    pub fn from_results(
+        id: Uuid,
+        test_cases: &[TestCase],
+        results: &[ValidatedTestCase],
+        penalty: f64,
+        updated: NaiveDateTime,
+    ) -> ValidatedFile {
+        let passed = |test_case: &&TestCase| {
+            results
+                .iter()
+                .any(|result| result.test_case_id == test_case.id && result.passed)
+        };
+        let all_passed =
+            !test_cases.is_empty() && test_cases.iter().all(|test_case| passed(&test_case));
         ValidatedFile {
-            id: validatable_file.id,
-            validated: validatable_file.is_valid(),
-            encoded_output: validatable_file.encoded_output,
-            updated: validatable_file.updated,
+            id,
+            status: if all_passed {
+                FileStatus::Passed
+            } else {
+                FileStatus::Failed
+            },
+            score: test_cases
+                .iter()
+                .filter(passed)
+                .map(|test_case| test_case.weight)
+                .sum::<f64>()
+                * (1.0 - penalty),
+            max_score: test_cases.iter().map(|test_case| test_case.weight).sum(),
+            updated,
+            finished_at: updated,
         }
     }
 }
 
-#[derive(Identifiable, Queryable, Serialize)]
-#[table_name = "files"]
+/// Run as it is sent to the scheduling service. The runner reports to
+/// `{callback_url}/status` and `{callback_url}/output`, which is the file or
+/// the scratch run the run belongs to. `callback_path` is the same URL
+/// relative to the public base URL of this service.
+#[derive(Serialize)]
 pub struct ScheduleInputFile {
     #[serde(rename(serialize = "file_id"))]
     pub id: Uuid,
@@ -101,28 +375,27 @@ pub struct ScheduleInputFile {
     #[serde(rename(serialize = "content"))]
     #[serde(serialize_with = "serialize_base64")]
     pub encoded_text: Vec<u8>,
-    #[serde(rename(serialize = "test_case"))]
-    #[serde(serialize_with = "serialize_base64")]
-    pub encoded_input: Vec<u8>,
+    pub files: Vec<ScheduleSourceFile>,
+    pub test_cases: Vec<ScheduleTestCase>,
+    pub callback_token: String,
+    pub callback_path: String,
+    pub callback_url: String,
 }
 
-impl InsertableFile {
-    pub fn from_insertable_code(
-        submission: &Submission,
-        insertable_code: &InsertableCode,
-    ) -> Result<InsertableFile, DecodeError> {
-        base64::decode(&insertable_code.encoded_text).map(|value| InsertableFile {
-            submission_id: submission.id,
-            encoded_text: value,
-        })
-    }
+#[derive(Queryable, Serialize)]
+pub struct ScheduleSourceFile {
+    pub path: String,
+    #[serde(rename(serialize = "content"))]
+    #[serde(serialize_with = "serialize_base64")]
+    pub encoded_text: Vec<u8>,
 }
 
-impl From<File> for ScheduleTriggerFile {
-    fn from(file: File) -> Self {
-        ScheduleTriggerFile {
-            id: file.id,
-            scheduled: file.scheduled,
-        }
-    }
+#[derive(Queryable, Serialize)]
+pub struct ScheduleTestCase {
+    #[serde(rename(serialize = "test_case_id"))]
+    pub id: Uuid,
+    pub name: String,
+    #[serde(rename(serialize = "input"))]
+    #[serde(serialize_with = "serialize_base64")]
+    pub encoded_input: Vec<u8>,
 }
@@ -1,142 +1,754 @@
-use chrono::Utc;
+use chrono::{Duration, NaiveDateTime, Utc};
 use diesel::prelude::*;
 use uuid::Uuid;
 
-use crate::auth::Auth;
-use crate::connection::RepositoryError::{NotFound, Unauthorized};
-use crate::connection::RepositoryQueryResult;
-use crate::files::repository::Ownership::Viewer;
+use crate::assignments::comparison::Comparison;
+use crate::assignments::Assignment;
+use crate::audit;
+use crate::audit::AuditContext;
+use crate::auth::policy::{Action, Relation, Resource};
+use crate::auth::{policy, Auth, RunnerAuth};
+use crate::collaborators::CollaboratorRole;
+use crate::connection::RepositoryError::{Invalid, NotFound, QueryError, Rejected, Unauthorized};
+use crate::connection::{Page, PageRequest, RepositoryError, RepositoryQueryResult};
+use crate::extensions;
+use crate::files::diff::{FileContent, FileDiff};
+use crate::files::events::FileEvent;
+use crate::files::status::{FileStatus, StatusChange};
 use crate::files::{
-    File, InsertableFile, ScheduleInputFile, ScheduleOutputFile, ScheduleTriggerFile,
-    ValidatableFile, ValidatedFile,
+    File, FileVersion, FileWithResults, InsertableFile, InsertableRunResult, InsertableSourceFile,
+    PendingRun, ScheduleInputFile, ScheduleOutputFile, ScheduleOutputTestCase, ScheduleSourceFile,
+    SourceFile, StatusUpdate, TestCaseResult, ValidatableTestCase, ValidatedFile,
+    ValidatedTestCase,
 };
-use crate::schema::{assignments, files, submissions};
-
-enum Ownership {
-    Owner,
-    Viewer,
-    Unauthorized,
-}
+use crate::jobs;
+use crate::jobs::{Job, JobStatus};
+use crate::schema::{
+    assignment_collaborators, assignments, files, run_results, source_files, submissions,
+    test_case_results, test_cases,
+};
+use crate::test_cases::TestCase;
 
 #[derive(Queryable)]
 struct Owners {
     owner_id: Uuid,
     assignment_owner_id: Uuid,
+    collaborator_role: Option<CollaboratorRole>,
 }
 
 impl Owners {
-    fn ownership_by_id(&self, user_id: Uuid) -> Ownership {
+    /// The student who submitted a file is rather its author than a viewer of
+    /// the assignment, so they can still queue it.
+    fn ownership_by_id(&self, user_id: Uuid) -> Relation {
         match (user_id, self.assignment_owner_id, self.owner_id) {
-            (input, ass, _) if input == ass => Ownership::Owner,
-            (input, _, own) if input == own => Ownership::Viewer,
-            (_, _, _) => Ownership::Unauthorized,
+            (input, ass, _) if input == ass => Relation::AssignmentOwner,
+            _ => match self.collaborator_role {
+                Some(CollaboratorRole::Viewer) | None if user_id == self.owner_id => {
+                    Relation::Author
+                }
+                Some(role) => Relation::from(role),
+                None => Relation::Unrelated,
+            },
         }
     }
 }
 
-pub fn insert(insertable_file: InsertableFile, connection: &PgConnection) -> QueryResult<File> {
-    diesel::insert_into(files::table)
-        .values(&insertable_file)
-        .get_result(connection)
+/// Insert a new version of a submission, which becomes its active file. The file
+/// is flagged as late if it is submitted after the due date of the assignment
+/// or of the student's extension. The source files of a project submission
+/// are stored with the version.
+pub fn insert(
+    mut insertable_file: InsertableFile,
+    mut source_files: Vec<InsertableSourceFile>,
+    audit: &AuditContext,
+    connection: &PgConnection,
+) -> QueryResult<File> {
+    let (user_id, assignment): (Uuid, Assignment) = submissions::table
+        .find(insertable_file.submission_id)
+        .inner_join(assignments::table)
+        .select((submissions::user_id, assignments::all_columns))
+        .first(connection)?;
+    let extension = extensions::repository::find(assignment.id, user_id, connection)?;
+    insertable_file.late = assignment.is_late_at(Utc::now().naive_utc(), extension.as_ref());
+    connection.transaction(|| {
+        let file: File = diesel::insert_into(files::table)
+            .values(&insertable_file)
+            .get_result(connection)?;
+        for source_file in source_files.iter_mut() {
+            source_file.file_id = file.id;
+        }
+        diesel::insert_into(source_files::table)
+            .values(&source_files)
+            .execute(connection)?;
+        diesel::update(submissions::table.find(file.submission_id))
+            .set(submissions::active_file_id.eq(file.id))
+            .execute(connection)?;
+        audit::repository::record(
+            audit,
+            "create",
+            Resource::File,
+            file.id,
+            None,
+            Some(&file),
+            connection,
+        )?;
+        Ok(file)
+    })
+}
+
+pub fn all_versions(
+    submission_id: Uuid,
+    page_request: PageRequest,
+    connection: &PgConnection,
+) -> QueryResult<Page<FileVersion>> {
+    let total: i64 = files::table
+        .filter(files::submission_id.eq(submission_id))
+        .count()
+        .get_result(connection)?;
+    files::table
+        .filter(files::submission_id.eq(submission_id))
+        .order((files::created.desc(), files::id))
+        .select((
+            files::id,
+            files::submission_id,
+            files::updated,
+            files::score,
+            files::max_score,
+            files::late,
+            files::status,
+            files::queued_at,
+            files::started_at,
+            files::finished_at,
+            files::run_retries,
+            files::status_reason,
+            files::created,
+        ))
+        .limit(page_request.size)
+        .offset(page_request.offset())
+        .load(connection)
+        .map(|versions| page_request.to_page(versions, total))
 }
 
-pub fn update(
+/// Queue a file or move it back to submitted. Queueing a file queues a job for
+/// the scheduling service in the same transaction, which is delivered in the
+/// background. Moving a queued file back to submitted cancels its job. All
+/// other statuses are reported by the runner.
+pub fn update_status(
     id: Uuid,
     auth: &Auth,
-    schedule_trigger_file: &ScheduleTriggerFile,
+    status_update: StatusUpdate,
+    audit: &AuditContext,
     connection: &PgConnection,
 ) -> RepositoryQueryResult<File> {
-    match ownership_of(id, auth, connection) {
-        Ok(Ownership::Unauthorized) if !auth.is_superuser => RepositoryQueryResult::Err(
-            Unauthorized("can't update file if not owner of file".to_owned()),
-        ),
-        Ok(_) => diesel::update(files::table.find(id))
-            .set((
-                schedule_trigger_file,
-                files::updated.eq(Utc::now().naive_utc()),
-            ))
-            .get_result(connection)
+    if let Err(error) = authorize(id, auth, Action::Update, connection) {
+        return RepositoryQueryResult::Err(error);
+    }
+    match status_update.status {
+        FileStatus::Queued | FileStatus::Submitted => connection
+            .transaction::<_, RepositoryError<diesel::result::Error>, _>(|| {
+                let file = change_status(id, status_update.status, audit, connection)?;
+                if file.status == FileStatus::Queued {
+                    jobs::repository::enqueue(id, connection)?;
+                } else {
+                    jobs::repository::cancel(id, connection)?;
+                }
+                Ok(file)
+            })
             .into(),
-        Err(_) => RepositoryQueryResult::Err(NotFound),
+        status => RepositoryQueryResult::Err(Rejected(format!(
+            "only the runner can set status {}",
+            status.as_str()
+        ))),
     }
 }
 
-pub fn update_output(
+/// Mark a file as running when the runner the callback token was issued to
+/// picks it up.
+pub fn update_status_by_runner(
     id: Uuid,
-    auth: Auth,
-    schedule_output_file: ScheduleOutputFile,
+    runner_auth: &RunnerAuth,
+    status_update: StatusUpdate,
+    audit: &AuditContext,
     connection: &PgConnection,
 ) -> RepositoryQueryResult<File> {
-    match ownership_of(id, &auth, connection) {
-        Ok(Ownership::Unauthorized) if !auth.is_superuser => RepositoryQueryResult::Err(
-            Unauthorized("can't update file if not owner of file".to_owned()),
-        ),
-        Ok(_) => patch_schedule_output(id, schedule_output_file, connection).into(),
-        Err(_) => RepositoryQueryResult::Err(NotFound),
+    if !runner_auth.is_for_file(id) {
+        return RepositoryQueryResult::Err(Unauthorized(
+            "callback token was issued for another file".to_owned(),
+        ));
+    }
+    match status_update.status {
+        FileStatus::Running => connection
+            .transaction(|| change_status(id, status_update.status, audit, connection))
+            .into(),
+        status => RepositoryQueryResult::Err(Rejected(format!(
+            "runner can't set status {}",
+            status.as_str()
+        ))),
     }
 }
 
-fn patch_schedule_output(
+fn change_status(
     id: Uuid,
-    schedule_output_file: ScheduleOutputFile,
+    status: FileStatus,
+    audit: &AuditContext,
     connection: &PgConnection,
-) -> QueryResult<File> {
-    let result: QueryResult<ValidatedFile> = files::table
-        .find(id)
+) -> Result<File, RepositoryError<diesel::result::Error>> {
+    let before = lock_for_status_change(id, status, connection)?;
+    let after: File = diesel::update(files::table.find(id))
+        .set(StatusChange::new(status, Utc::now().naive_utc()))
+        .get_result(connection)?;
+    audit::repository::record(
+        audit,
+        "update_status",
+        Resource::File,
+        id,
+        Some(&before),
+        Some(&after),
+        connection,
+    )?;
+    Ok(after)
+}
+
+/// Runs without a result within the run timeout of their assignment, or the
+/// given default timeout. The timeout of a running file starts when the
+/// runner picked it up, the one of a queued file when its job was delivered,
+/// so files waiting for a delivery retry are not overdue.
+pub fn overdue_runs(
+    default_timeout_seconds: i64,
+    now: NaiveDateTime,
+    connection: &PgConnection,
+) -> QueryResult<Vec<PendingRun>> {
+    let runs: Vec<PendingRun> = files::table
         .inner_join(submissions::table.inner_join(assignments::table))
+        .filter(files::status.eq_any(vec![FileStatus::Queued, FileStatus::Running]))
         .select((
             files::id,
-            files::validated,
-            files::encoded_output,
-            assignments::encoded_output,
+            files::status,
             files::updated,
+            files::started_at,
+            files::run_retries,
+            assignments::run_timeout_seconds,
+            assignments::max_run_retries,
         ))
-        .first(connection)
-        .map(|mut validatable_file: ValidatableFile| {
-            validatable_file.encoded_output = Some(schedule_output_file.encoded_output);
-            validatable_file.updated = Utc::now().naive_utc();
-            ValidatedFile::from(validatable_file)
-        });
-
-    result.and_then(|validatable_file| {
-        diesel::update(files::table.find(validatable_file.id))
-            .set(validatable_file)
-            .get_result(connection)
+        .load(connection)?;
+    let mut overdue = Vec::new();
+    for run in runs {
+        let started_at = match run.status {
+            FileStatus::Running => run.started_at,
+            _ => jobs::repository::latest_by_file_id(run.id, connection)?
+                .filter(|job| job.status == JobStatus::Delivered)
+                .and_then(|job| job.delivered_at),
+        };
+        let timeout = run.timeout_seconds(default_timeout_seconds);
+        if started_at.map_or(false, |started_at| {
+            started_at + Duration::seconds(timeout) < now
+        }) {
+            overdue.push(run);
+        }
+    }
+    Ok(overdue)
+}
+
+/// Queue an overdue run again if the assignment allows another retry,
+/// otherwise end it with a timeout. The file is left alone if it changed since
+/// the run was found to be overdue.
+pub fn end_overdue_run(
+    run: &PendingRun,
+    default_timeout_seconds: i64,
+    audit: &AuditContext,
+    connection: &PgConnection,
+) -> QueryResult<Option<File>> {
+    connection.transaction(|| {
+        let file: File = files::table.find(run.id).for_update().first(connection)?;
+        if file.status != run.status || file.updated != run.updated {
+            return Ok(None);
+        }
+        let now = Utc::now().naive_utc();
+        let reason = format!(
+            "no result within {} seconds",
+            run.timeout_seconds(default_timeout_seconds)
+        );
+        let (action, status_change) = if run.run_retries < run.max_run_retries {
+            jobs::repository::enqueue(run.id, connection)?;
+            let mut status_change =
+                StatusChange::new(FileStatus::Queued, now).with_reason(format!(
+                    "{}, queued again (retry {} of {})",
+                    reason,
+                    run.run_retries + 1,
+                    run.max_run_retries
+                ));
+            status_change.run_retries = Some(run.run_retries + 1);
+            ("retry", status_change)
+        } else {
+            (
+                "timeout",
+                StatusChange::new(FileStatus::Timeout, now).with_reason(reason),
+            )
+        };
+        let updated: File = diesel::update(files::table.find(run.id))
+            .set(status_change)
+            .get_result(connection)?;
+        audit::repository::record(
+            audit,
+            action,
+            Resource::File,
+            run.id,
+            Some(&file),
+            Some(&updated),
+            connection,
+        )?;
+        Ok(Some(updated))
     })
 }
 
-pub fn get_by_uuid(id: Uuid, auth: Auth, connection: &PgConnection) -> RepositoryQueryResult<File> {
-    match ownership_of(id, &auth, connection) {
-        Ok(Ownership::Unauthorized) if !auth.is_superuser => {
-            RepositoryQueryResult::Err(Unauthorized("user is not allowed to query file".to_owned()))
+/// Latest scheduling job of a file.
+pub fn get_job(id: Uuid, auth: Auth, connection: &PgConnection) -> RepositoryQueryResult<Job> {
+    if let Err(error) = authorize(id, &auth, Action::Read, connection) {
+        return RepositoryQueryResult::Err(error);
+    }
+    match jobs::repository::latest_by_file_id(id, connection) {
+        Ok(Some(job)) => RepositoryQueryResult::Ok(job),
+        Ok(None) => RepositoryQueryResult::Err(NotFound),
+        Err(err) => RepositoryQueryResult::Err(QueryError(err)),
+    }
+}
+
+/// Store the output of a run. Only the runner the callback token of the file
+/// was issued to can report it.
+pub fn update_output(
+    id: Uuid,
+    runner_auth: RunnerAuth,
+    schedule_output_file: ScheduleOutputFile,
+    audit: &AuditContext,
+    connection: &PgConnection,
+) -> RepositoryQueryResult<File> {
+    if !runner_auth.is_for_file(id) {
+        return RepositoryQueryResult::Err(Unauthorized(
+            "callback token was issued for another file".to_owned(),
+        ));
+    }
+    connection
+        .transaction(|| patch_schedule_output(id, schedule_output_file, audit, connection))
+        .into()
+}
+
+/// Validate the output of a run and replace the results of the previous run.
+/// The file passes if all test cases passed, unless the runner reported an
+/// error status.
+fn patch_schedule_output(
+    id: Uuid,
+    mut schedule_output_file: ScheduleOutputFile,
+    audit: &AuditContext,
+    connection: &PgConnection,
+) -> Result<File, RepositoryError<diesel::result::Error>> {
+    let updated = Utc::now().naive_utc();
+    let run_result = InsertableRunResult::new(id, &mut schedule_output_file, updated);
+    check_test_cases(id, &schedule_output_file.results, connection)?;
+    let (mut validated_file, validated_test_cases) =
+        validate(id, schedule_output_file.results, updated, connection)?;
+    match schedule_output_file.status {
+        Some(status) if status.is_finished() && !status.is_graded() => {
+            validated_file.status = status
         }
-        Ok(_) => files::table.find(id).get_result(connection).into(),
-        Err(_) => RepositoryQueryResult::Err(NotFound),
+        Some(status) => {
+            return Err(Rejected(format!(
+                "runner can't report status {}",
+                status.as_str()
+            )))
+        }
+        None => {}
     }
+    let before = lock_for_status_change(id, validated_file.status, connection)?;
+
+    replace_results(id, &validated_test_cases, connection)?;
+    diesel::delete(run_results::table.find(id)).execute(connection)?;
+    diesel::insert_into(run_results::table)
+        .values(&run_result)
+        .execute(connection)?;
+    let after: File = diesel::update(files::table.find(id))
+        .set(validated_file)
+        .get_result(connection)?;
+    audit::repository::record(
+        audit,
+        "update_output",
+        Resource::File,
+        id,
+        Some(&before),
+        Some(&after),
+        connection,
+    )?;
+    Ok(after)
 }
 
-pub fn get_schedule_file(id: Uuid, connection: &PgConnection) -> QueryResult<ScheduleInputFile> {
-    files::table
+/// Check that every result of a run belongs to a test case of the assignment
+/// of the file, so a wrong payload can't pass a file with the results of
+/// another assignment.
+fn check_test_cases(
+    id: Uuid,
+    results: &[ScheduleOutputTestCase],
+    connection: &PgConnection,
+) -> Result<(), RepositoryError<diesel::result::Error>> {
+    let test_case_ids: Vec<Uuid> = files::table
+        .find(id)
+        .inner_join(submissions::table.inner_join(
+            test_cases::table.on(test_cases::assignment_id.eq(submissions::assignment_id)),
+        ))
+        .select(test_cases::id)
+        .load(connection)?;
+    match results
+        .iter()
+        .find(|result| !test_case_ids.contains(&result.test_case_id))
+    {
+        Some(result) => Err(Invalid(format!(
+            "test case {} doesn't belong to the assignment of the file",
+            result.test_case_id
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Validate the stored outputs of the last run of a graded file again, for
+/// example after expected outputs of the assignment changed. Files whose run
+/// did not end with outputs to validate are left alone.
+pub fn revalidate(
+    id: Uuid,
+    audit: &AuditContext,
+    connection: &PgConnection,
+) -> QueryResult<Option<File>> {
+    connection.transaction(|| {
+        let file: File = files::table.find(id).for_update().first(connection)?;
+        if !file.status.is_graded() {
+            return Ok(None);
+        }
+        let results: Vec<TestCaseResult> = test_case_results::table
+            .filter(test_case_results::file_id.eq(id))
+            .load(connection)?;
+        let results = results
+            .into_iter()
+            .map(ScheduleOutputTestCase::from)
+            .collect();
+        let (mut validated_file, validated_test_cases) =
+            validate(id, results, Utc::now().naive_utc(), connection)?;
+        validated_file.finished_at = file.finished_at.unwrap_or(validated_file.updated);
+        replace_results(id, &validated_test_cases, connection)?;
+        let updated: File = diesel::update(files::table.find(id))
+            .set(validated_file)
+            .get_result(connection)?;
+        audit::repository::record(
+            audit,
+            "revalidate",
+            Resource::File,
+            id,
+            Some(&file),
+            Some(&updated),
+            connection,
+        )?;
+        Ok(Some(updated))
+    })
+}
+
+/// Queue a finished file again to rerun it. Files that are not finished are
+/// left alone.
+pub fn requeue(
+    id: Uuid,
+    audit: &AuditContext,
+    connection: &PgConnection,
+) -> QueryResult<Option<File>> {
+    connection.transaction(|| {
+        let file: File = files::table.find(id).for_update().first(connection)?;
+        if !file.status.is_finished() {
+            return Ok(None);
+        }
+        jobs::repository::enqueue(id, connection)?;
+        let updated: File = diesel::update(files::table.find(id))
+            .set(StatusChange::new(
+                FileStatus::Queued,
+                Utc::now().naive_utc(),
+            ))
+            .get_result(connection)?;
+        audit::repository::record(
+            audit,
+            "requeue",
+            Resource::File,
+            id,
+            Some(&file),
+            Some(&updated),
+            connection,
+        )?;
+        Ok(Some(updated))
+    })
+}
+
+/// Validate the outputs of a run against the expected outputs with the
+/// comparison mode of the assignment. The file is scored with the weights of
+/// the passed test cases and the late penalty of the assignment is applied to
+/// late files.
+fn validate(
+    id: Uuid,
+    results: Vec<ScheduleOutputTestCase>,
+    updated: NaiveDateTime,
+    connection: &PgConnection,
+) -> QueryResult<(ValidatedFile, Vec<ValidatedTestCase>)> {
+    let (late, assignment): (bool, Assignment) = files::table
+        .find(id)
         .inner_join(submissions::table.inner_join(assignments::table))
+        .select((files::late, assignments::all_columns))
+        .first(connection)?;
+    let comparison = Comparison::from(&assignment);
+    let penalty = if late { assignment.late_penalty } else { 0.0 };
+    let test_cases: Vec<TestCase> = test_cases::table
+        .filter(test_cases::assignment_id.eq(assignment.id))
+        .load(connection)?;
+    let validated_test_cases: Vec<ValidatedTestCase> = results
+        .into_iter()
+        .filter_map(|result| {
+            test_cases
+                .iter()
+                .find(|test_case| test_case.id == result.test_case_id)
+                .map(|test_case| ValidatableTestCase {
+                    file_id: id,
+                    comparison: &comparison,
+                    test_case,
+                    result,
+                    updated,
+                })
+        })
+        .map(ValidatedTestCase::from)
+        .collect();
+    let validated_file =
+        ValidatedFile::from_results(id, &test_cases, &validated_test_cases, penalty, updated);
+    Ok((validated_file, validated_test_cases))
+}
+
+fn replace_results(
+    id: Uuid,
+    validated_test_cases: &[ValidatedTestCase],
+    connection: &PgConnection,
+) -> QueryResult<usize> {
+    diesel::delete(test_case_results::table.filter(test_case_results::file_id.eq(id)))
+        .execute(connection)?;
+    diesel::insert_into(test_case_results::table)
+        .values(validated_test_cases)
+        .execute(connection)
+}
+
+/// Lock a file until the end of the transaction and check that it can move to
+/// the given status.
+fn lock_for_status_change(
+    id: Uuid,
+    status: FileStatus,
+    connection: &PgConnection,
+) -> Result<File, RepositoryError<diesel::result::Error>> {
+    let file: File = files::table.find(id).for_update().first(connection)?;
+    if file.status.can_change_to(status) {
+        Ok(file)
+    } else {
+        Err(Rejected(format!(
+            "file can't change from {} to {}",
+            file.status.as_str(),
+            status.as_str()
+        )))
+    }
+}
+
+/// File with the output of its last run, which includes the stderr of the run
+/// for the student who submitted it.
+pub fn get_by_uuid(
+    id: Uuid,
+    auth: Auth,
+    connection: &PgConnection,
+) -> RepositoryQueryResult<FileWithResults> {
+    if let Err(error) = authorize(id, &auth, Action::Read, connection) {
+        return RepositoryQueryResult::Err(error);
+    }
+    with_results(id, connection).into()
+}
+
+/// Current status of a file, which starts its event stream. The same users as
+/// for `get_by_uuid` can follow it.
+pub fn get_event(
+    id: Uuid,
+    auth: Auth,
+    connection: &PgConnection,
+) -> RepositoryQueryResult<FileEvent> {
+    if let Err(error) = authorize(id, &auth, Action::Read, connection) {
+        return RepositoryQueryResult::Err(error);
+    }
+    files::table
+        .find(id)
+        .select((files::id, files::status, files::updated))
+        .get_result(connection)
+        .into()
+}
+
+fn with_results(id: Uuid, connection: &PgConnection) -> QueryResult<FileWithResults> {
+    let file: File = files::table.find(id).get_result(connection)?;
+    let run = run_results::table.find(id).first(connection).optional()?;
+    let results = results_of(id, connection)?;
+    Ok(FileWithResults { file, run, results })
+}
+
+fn results_of(id: Uuid, connection: &PgConnection) -> QueryResult<Vec<TestCaseResult>> {
+    test_case_results::table
+        .inner_join(test_cases::table)
+        .filter(test_case_results::file_id.eq(id))
+        .order((test_cases::position.asc(), test_cases::name.asc()))
+        .select(test_case_results::all_columns)
+        .load(connection)
+}
+
+/// Diff of the text and the source files of a file against another file. The
+/// user has to be allowed to query both files.
+pub fn diff(
+    id: Uuid,
+    against_id: Uuid,
+    auth: Auth,
+    connection: &PgConnection,
+) -> RepositoryQueryResult<FileDiff> {
+    for file_id in &[id, against_id] {
+        if let Err(error) = authorize(*file_id, &auth, Action::Read, connection) {
+            return RepositoryQueryResult::Err(error);
+        }
+    }
+    let contents = content_of(id, connection).and_then(|content| {
+        content_of(against_id, connection).map(|against_content| (content, against_content))
+    });
+    let ((text, source_files), (against_text, against_source_files)) = match contents {
+        Ok(contents) => contents,
+        Err(error) => return RepositoryQueryResult::Err(QueryError(error)),
+    };
+    FileDiff::new(
+        FileContent {
+            id,
+            text: &text,
+            source_files: &source_files,
+        },
+        FileContent {
+            id: against_id,
+            text: &against_text,
+            source_files: &against_source_files,
+        },
+    )
+    .map_err(Invalid)
+    .into()
+}
+
+/// Text of a file with its source files ordered by path.
+fn content_of(
+    id: Uuid,
+    connection: &PgConnection,
+) -> QueryResult<(Vec<u8>, Vec<(String, Vec<u8>)>)> {
+    let text: Vec<u8> = files::table
+        .find(id)
+        .select(files::encoded_text)
+        .get_result(connection)?;
+    let source_files: Vec<(String, Vec<u8>)> = source_files::table
+        .filter(source_files::file_id.eq(id))
+        .order(source_files::path.asc())
+        .select((source_files::path, source_files::encoded_text))
+        .load(connection)?;
+    Ok((text, source_files))
+}
+
+/// File as it is sent to the scheduling service, with the callback token the
+/// runner reports its results with and the URL it reports them to.
+pub fn get_schedule_file(
+    id: Uuid,
+    callback_token: String,
+    public_base_url: &str,
+    connection: &PgConnection,
+) -> QueryResult<ScheduleInputFile> {
+    files::table
+        .inner_join(submissions::table)
         .filter(files::id.eq(id))
         .select((
             files::id,
             submissions::extension,
             submissions::assignment_id,
             files::encoded_text,
-            assignments::encoded_input,
         ))
-        .first(connection)
+        .first::<(Uuid, String, Uuid, Vec<u8>)>(connection)
+        .and_then(|(id, extension, assignment_id, encoded_text)| {
+            let files: Vec<ScheduleSourceFile> = source_files::table
+                .filter(source_files::file_id.eq(id))
+                .order(source_files::path.asc())
+                .select((source_files::path, source_files::encoded_text))
+                .load(connection)?;
+            test_cases::table
+                .filter(test_cases::assignment_id.eq(assignment_id))
+                .order((test_cases::position.asc(), test_cases::name.asc()))
+                .select((test_cases::id, test_cases::name, test_cases::encoded_input))
+                .load(connection)
+                .map(|test_cases| ScheduleInputFile {
+                    id,
+                    extension,
+                    assignment_id,
+                    encoded_text,
+                    files,
+                    test_cases,
+                    callback_token,
+                    callback_path: format!("/files/{}", id),
+                    callback_url: format!("{}/files/{}", public_base_url, id),
+                })
+        })
 }
 
-fn ownership_of(id: Uuid, auth: &Auth, connection: &PgConnection) -> QueryResult<Ownership> {
-    if auth.is_superuser {
-        return QueryResult::Ok(Viewer);
+pub fn get_results(
+    id: Uuid,
+    auth: Auth,
+    connection: &PgConnection,
+) -> RepositoryQueryResult<Vec<TestCaseResult>> {
+    if let Err(error) = authorize(id, &auth, Action::Read, connection) {
+        return RepositoryQueryResult::Err(error);
+    }
+    results_of(id, connection).into()
+}
+
+/// Files of a project submission ordered by path, empty for a single file
+/// submission.
+pub fn get_sources(
+    id: Uuid,
+    auth: Auth,
+    connection: &PgConnection,
+) -> RepositoryQueryResult<Vec<SourceFile>> {
+    if let Err(error) = authorize(id, &auth, Action::Read, connection) {
+        return RepositoryQueryResult::Err(error);
+    }
+    source_files::table
+        .filter(source_files::file_id.eq(id))
+        .order(source_files::path.asc())
+        .load(connection)
+        .into()
+}
+
+/// Check with the policy that the user can do the action on a file. Fails with
+/// `NotFound` if the file doesn't exist.
+fn authorize(
+    id: Uuid,
+    auth: &Auth,
+    action: Action,
+    connection: &PgConnection,
+) -> Result<(), RepositoryError<diesel::result::Error>> {
+    match relation_of(id, auth, connection) {
+        Ok(relation) => policy::authorize(auth, relation, action, Resource::File),
+        Err(_) => Err(NotFound),
     }
+}
+
+fn relation_of(id: Uuid, auth: &Auth, connection: &PgConnection) -> QueryResult<Relation> {
     let owners_result: QueryResult<Owners> = files::table
         .find(id)
         .inner_join(submissions::table.inner_join(assignments::table))
-        .select((submissions::user_id, assignments::user_id))
+        .left_join(
+            assignment_collaborators::table.on(assignment_collaborators::assignment_id
+                .eq(assignments::id)
+                .and(assignment_collaborators::user_id.eq(auth.user_id))),
+        )
+        .select((
+            submissions::user_id,
+            assignments::user_id,
+            assignment_collaborators::role.nullable(),
+        ))
         .get_result(connection);
     owners_result.map(|owners| owners.ownership_by_id(auth.user_id))
 }
@@ -146,18 +758,16 @@ pub fn get_by_submission_id(
     auth: Auth,
     connection: &PgConnection,
 ) -> RepositoryQueryResult<File> {
-    let result: QueryResult<File> = files::table
-        .filter(files::submission_id.eq(submission_id))
-        .order(files::updated.desc())
+    let result: QueryResult<File> = submissions::table
+        .find(submission_id)
+        .inner_join(files::table.on(submissions::active_file_id.eq(files::id.nullable())))
+        .select(files::all_columns)
         .first(connection);
 
     match result {
-        Ok(file) => match ownership_of(file.id, &auth, connection) {
-            Ok(Ownership::Unauthorized) if !auth.is_superuser => RepositoryQueryResult::Err(
-                Unauthorized("user is not allowed to query file".to_owned()),
-            ),
-            Ok(_) => RepositoryQueryResult::Ok(file),
-            Err(_) => RepositoryQueryResult::Err(NotFound),
+        Ok(file) => match authorize(file.id, &auth, Action::Read, connection) {
+            Ok(()) => RepositoryQueryResult::Ok(file),
+            Err(error) => RepositoryQueryResult::Err(error),
         },
         Err(_) => RepositoryQueryResult::Err(NotFound),
     }
@@ -8,7 +8,13 @@ pub fn create_routes(rocket: Rocket) -> Rocket {
         routes![
             handler::patch,
             handler::patch_output,
+            handler::patch_status,
             handler::get,
+            handler::get_events,
+            handler::get_job,
+            handler::get_results,
+            handler::get_sources,
+            handler::diff,
             handler::get_by_submission_id
         ],
     )
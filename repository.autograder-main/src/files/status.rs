@@ -0,0 +1,143 @@
+use std::io::Write;
+
+use chrono::NaiveDateTime;
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Text;
+
+use crate::schema::files;
+
+/// Where a file is in its lifecycle from submission to the result of its run.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, AsExpression, FromSqlRow)]
+#[serde(rename_all = "snake_case")]
+#[sql_type = "Text"]
+pub enum FileStatus {
+    /// Stored but not scheduled.
+    Submitted,
+    /// Waiting to be picked up by a runner.
+    Queued,
+    /// Picked up by a runner.
+    Running,
+    /// Every test case passed.
+    Passed,
+    /// At least one test case failed.
+    Failed,
+    CompileError,
+    RuntimeError,
+    Timeout,
+    /// The run failed for a reason the student is not responsible for.
+    InternalError,
+}
+
+impl FileStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileStatus::Submitted => "submitted",
+            FileStatus::Queued => "queued",
+            FileStatus::Running => "running",
+            FileStatus::Passed => "passed",
+            FileStatus::Failed => "failed",
+            FileStatus::CompileError => "compile_error",
+            FileStatus::RuntimeError => "runtime_error",
+            FileStatus::Timeout => "timeout",
+            FileStatus::InternalError => "internal_error",
+        }
+    }
+
+    /// Whether the run of the file is over.
+    pub fn is_finished(&self) -> bool {
+        !matches!(
+            self,
+            FileStatus::Submitted | FileStatus::Queued | FileStatus::Running
+        )
+    }
+
+    /// Whether the run ended with a result the file is graded by.
+    pub fn is_graded(&self) -> bool {
+        matches!(self, FileStatus::Passed | FileStatus::Failed)
+    }
+
+    /// A file is queued when it is scheduled and goes back to submitted if it
+    /// is unscheduled before it runs. A run ends in one of the finished
+    /// statuses, or is queued again if it is retried. Finished files can be
+    /// queued again to rerun them.
+    pub fn can_change_to(&self, status: FileStatus) -> bool {
+        match (self, status) {
+            (FileStatus::Submitted, FileStatus::Queued) => true,
+            (FileStatus::Queued, FileStatus::Submitted) => true,
+            (FileStatus::Queued, FileStatus::Running) => true,
+            (FileStatus::Running, FileStatus::Queued) => true,
+            (FileStatus::Queued, status) | (FileStatus::Running, status) => status.is_finished(),
+            (current, FileStatus::Queued) => current.is_finished(),
+            _ => false,
+        }
+    }
+}
+
+impl ToSql<Text, Pg> for FileStatus {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        <str as ToSql<Text, Pg>>::to_sql(self.as_str(), out)
+    }
+}
+
+impl FromSql<Text, Pg> for FileStatus {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match <String as FromSql<Text, Pg>>::from_sql(bytes)?.as_str() {
+            "submitted" => Ok(FileStatus::Submitted),
+            "queued" => Ok(FileStatus::Queued),
+            "running" => Ok(FileStatus::Running),
+            "passed" => Ok(FileStatus::Passed),
+            "failed" => Ok(FileStatus::Failed),
+            "compile_error" => Ok(FileStatus::CompileError),
+            "runtime_error" => Ok(FileStatus::RuntimeError),
+            "timeout" => Ok(FileStatus::Timeout),
+            "internal_error" => Ok(FileStatus::InternalError),
+            other => Err(format!("unknown file status: {}", other).into()),
+        }
+    }
+}
+
+/// Status of a file with the timestamp of the change. Queueing a file starts
+/// a new run, so the timestamps and retries of the previous run are cleared.
+/// The reason of the previous status is cleared unless one is given.
+#[derive(AsChangeset)]
+#[table_name = "files"]
+pub struct StatusChange {
+    pub status: FileStatus,
+    pub updated: NaiveDateTime,
+    pub queued_at: Option<Option<NaiveDateTime>>,
+    pub started_at: Option<Option<NaiveDateTime>>,
+    pub finished_at: Option<Option<NaiveDateTime>>,
+    pub run_retries: Option<i16>,
+    pub status_reason: Option<Option<String>>,
+}
+
+impl StatusChange {
+    pub fn new(status: FileStatus, time: NaiveDateTime) -> Self {
+        let (queued_at, started_at, finished_at) = match status {
+            FileStatus::Submitted => (Some(None), Some(None), Some(None)),
+            FileStatus::Queued => (Some(Some(time)), Some(None), Some(None)),
+            FileStatus::Running => (None, Some(Some(time)), Some(None)),
+            _ => (None, None, Some(Some(time))),
+        };
+        let run_retries = match status {
+            FileStatus::Submitted | FileStatus::Queued => Some(0),
+            _ => None,
+        };
+        StatusChange {
+            status,
+            updated: time,
+            queued_at,
+            started_at,
+            finished_at,
+            run_retries,
+            status_reason: Some(None),
+        }
+    }
+
+    pub fn with_reason(mut self, reason: String) -> Self {
+        self.status_reason = Some(Some(reason));
+        self
+    }
+}
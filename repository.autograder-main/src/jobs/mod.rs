@@ -0,0 +1,108 @@
+use std::io::Write;
+
+use chrono::NaiveDateTime;
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Text;
+use uuid::Uuid;
+
+use crate::files::File;
+use crate::scratch_runs::ScratchRun;
+
+use super::schema::schedule_jobs;
+
+pub mod repository;
+pub mod sweeper;
+pub mod worker;
+
+/// Delivery state of a scheduling request.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, AsExpression, FromSqlRow)]
+#[serde(rename_all = "snake_case")]
+#[sql_type = "Text"]
+pub enum JobStatus {
+    /// Waiting for its next delivery attempt.
+    Pending,
+    /// Accepted by the scheduling service.
+    Delivered,
+    /// Replaced by a newer request or unqueued before it was delivered.
+    Cancelled,
+    /// Given up after too many failed attempts.
+    Dead,
+}
+
+impl JobStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Pending => "pending",
+            JobStatus::Delivered => "delivered",
+            JobStatus::Cancelled => "cancelled",
+            JobStatus::Dead => "dead",
+        }
+    }
+}
+
+impl ToSql<Text, Pg> for JobStatus {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        <str as ToSql<Text, Pg>>::to_sql(self.as_str(), out)
+    }
+}
+
+impl FromSql<Text, Pg> for JobStatus {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match <String as FromSql<Text, Pg>>::from_sql(bytes)?.as_str() {
+            "pending" => Ok(JobStatus::Pending),
+            "delivered" => Ok(JobStatus::Delivered),
+            "cancelled" => Ok(JobStatus::Cancelled),
+            "dead" => Ok(JobStatus::Dead),
+            other => Err(format!("unknown job status: {}", other).into()),
+        }
+    }
+}
+
+/// Request to send a file or a scratch run to the scheduling service, kept
+/// until it is delivered.
+#[derive(Identifiable, Queryable, Associations, PartialEq, Debug, Clone, Serialize)]
+#[table_name = "schedule_jobs"]
+#[belongs_to(File)]
+#[belongs_to(ScratchRun)]
+pub struct Job {
+    pub id: Uuid,
+    pub file_id: Option<Uuid>,
+    pub status: JobStatus,
+    pub attempts: i16,
+    pub next_attempt_at: NaiveDateTime,
+    pub last_error: Option<String>,
+    pub created: NaiveDateTime,
+    pub updated: NaiveDateTime,
+    pub delivered_at: Option<NaiveDateTime>,
+    pub scratch_run_id: Option<Uuid>,
+    /// Set when a worker claims the job, so that a worker whose lease ran out
+    /// can't finish a job another worker claimed since.
+    #[serde(skip_serializing)]
+    pub lease_token: Option<Uuid>,
+}
+
+/// What a job runs. Every job has exactly one target.
+#[derive(Clone, Copy)]
+pub enum RunTarget {
+    File(Uuid),
+    ScratchRun(Uuid),
+}
+
+impl Job {
+    pub fn target(&self) -> RunTarget {
+        match (self.file_id, self.scratch_run_id) {
+            (Some(file_id), _) => RunTarget::File(file_id),
+            (None, Some(scratch_run_id)) => RunTarget::ScratchRun(scratch_run_id),
+            (None, None) => unreachable!("schedule job {} has no target", self.id),
+        }
+    }
+}
+
+#[derive(Insertable)]
+#[table_name = "schedule_jobs"]
+pub struct InsertableJob {
+    pub file_id: Option<Uuid>,
+    pub scratch_run_id: Option<Uuid>,
+}
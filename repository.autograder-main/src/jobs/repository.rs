@@ -0,0 +1,206 @@
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::files::status::{FileStatus, StatusChange};
+use crate::jobs::{InsertableJob, Job, JobStatus, RunTarget};
+use crate::schema::{files, schedule_jobs, scratch_runs};
+
+/// Attempts after which a job is dead-lettered.
+pub const MAX_ATTEMPTS: i16 = 10;
+const BASE_BACKOFF_SECONDS: i64 = 5;
+const MAX_BACKOFF_SECONDS: i64 = 60 * 60;
+/// Time a claimed job is hidden from other workers. The worker renews the
+/// lease while it delivers the job, so it only runs out if the worker stops.
+pub const LEASE_SECONDS: i64 = 60;
+
+/// Queue a file for scheduling. Pending jobs of the file are cancelled, so
+/// only the newest request is delivered. Has to run in the transaction that
+/// queues the file.
+pub fn enqueue(file_id: Uuid, connection: &PgConnection) -> QueryResult<Job> {
+    cancel(file_id, connection)?;
+    diesel::insert_into(schedule_jobs::table)
+        .values(&InsertableJob {
+            file_id: Some(file_id),
+            scratch_run_id: None,
+        })
+        .get_result(connection)
+}
+
+/// Queue a scratch run, which is only ever run once.
+pub fn enqueue_scratch_run(scratch_run_id: Uuid, connection: &PgConnection) -> QueryResult<Job> {
+    diesel::insert_into(schedule_jobs::table)
+        .values(&InsertableJob {
+            file_id: None,
+            scratch_run_id: Some(scratch_run_id),
+        })
+        .get_result(connection)
+}
+
+pub fn cancel(file_id: Uuid, connection: &PgConnection) -> QueryResult<usize> {
+    diesel::update(
+        schedule_jobs::table
+            .filter(schedule_jobs::file_id.eq(file_id))
+            .filter(schedule_jobs::status.eq(JobStatus::Pending)),
+    )
+    .set((
+        schedule_jobs::status.eq(JobStatus::Cancelled),
+        schedule_jobs::updated.eq(Utc::now().naive_utc()),
+    ))
+    .execute(connection)
+}
+
+/// Pending jobs that are due, oldest first. The claimed jobs are leased with a
+/// new token, so that other workers skip them until they are delivered or
+/// failed or the lease runs out.
+pub fn claim_due(limit: i64, connection: &PgConnection) -> QueryResult<Vec<Job>> {
+    connection.transaction(|| {
+        let now = Utc::now().naive_utc();
+        let ids: Vec<Uuid> = schedule_jobs::table
+            .filter(schedule_jobs::status.eq(JobStatus::Pending))
+            .filter(schedule_jobs::next_attempt_at.le(now))
+            .order(schedule_jobs::next_attempt_at.asc())
+            .limit(limit)
+            .select(schedule_jobs::id)
+            .for_update()
+            .skip_locked()
+            .load(connection)?;
+        diesel::update(schedule_jobs::table.filter(schedule_jobs::id.eq_any(ids)))
+            .set((
+                schedule_jobs::next_attempt_at.eq(now + Duration::seconds(LEASE_SECONDS)),
+                schedule_jobs::lease_token.eq(Uuid::new_v4()),
+            ))
+            .get_results(connection)
+    })
+}
+
+/// Extend the lease of a claimed job. Returns false if the lease was lost,
+/// because it ran out and another worker claimed the job or it was cancelled.
+pub fn renew_lease(job: &Job, connection: &PgConnection) -> QueryResult<bool> {
+    diesel::update(
+        schedule_jobs::table
+            .find(job.id)
+            .filter(schedule_jobs::status.eq(JobStatus::Pending))
+            .filter(schedule_jobs::lease_token.eq(job.lease_token)),
+    )
+    .set(
+        schedule_jobs::next_attempt_at
+            .eq(Utc::now().naive_utc() + Duration::seconds(LEASE_SECONDS)),
+    )
+    .execute(connection)
+    .map(|updated| updated == 1)
+}
+
+/// Record the delivery of a claimed job. Returns `None` if the worker lost the
+/// lease of the job, which is then left to the worker that holds it.
+pub fn mark_delivered(job: &Job, connection: &PgConnection) -> QueryResult<Option<Job>> {
+    let now = Utc::now().naive_utc();
+    diesel::update(
+        schedule_jobs::table
+            .find(job.id)
+            .filter(schedule_jobs::status.eq(JobStatus::Pending))
+            .filter(schedule_jobs::lease_token.eq(job.lease_token)),
+    )
+    .set((
+        schedule_jobs::status.eq(JobStatus::Delivered),
+        schedule_jobs::attempts.eq(schedule_jobs::attempts + 1),
+        schedule_jobs::last_error.eq(None::<String>),
+        schedule_jobs::delivered_at.eq(now),
+        schedule_jobs::updated.eq(now),
+        schedule_jobs::lease_token.eq(None::<Uuid>),
+    ))
+    .get_result(connection)
+    .optional()
+}
+
+/// Record a failed delivery of a claimed job and retry it with exponential
+/// backoff. After `MAX_ATTEMPTS` the job is dead and its file or scratch run,
+/// if it is still queued, ends with an internal error. Returns `None` if the
+/// worker lost the lease of the job.
+pub fn mark_failed(
+    job: &Job,
+    error: String,
+    connection: &PgConnection,
+) -> QueryResult<Option<Job>> {
+    let now = Utc::now().naive_utc();
+    let attempts = job.attempts + 1;
+    connection.transaction(|| {
+        let status = if attempts >= MAX_ATTEMPTS {
+            JobStatus::Dead
+        } else {
+            JobStatus::Pending
+        };
+        let failed: Option<Job> = diesel::update(
+            schedule_jobs::table
+                .find(job.id)
+                .filter(schedule_jobs::status.eq(JobStatus::Pending))
+                .filter(schedule_jobs::lease_token.eq(job.lease_token)),
+        )
+        .set((
+            schedule_jobs::status.eq(status),
+            schedule_jobs::attempts.eq(attempts),
+            schedule_jobs::next_attempt_at.eq(now + backoff(attempts)),
+            schedule_jobs::last_error.eq(&error),
+            schedule_jobs::updated.eq(now),
+            schedule_jobs::lease_token.eq(None::<Uuid>),
+        ))
+        .get_result(connection)
+        .optional()?;
+        if failed.is_some() && status == JobStatus::Dead {
+            let reason = format!("scheduling failed {} times: {}", attempts, error);
+            match job.target() {
+                RunTarget::File(file_id) => diesel::update(
+                    files::table
+                        .find(file_id)
+                        .filter(files::status.eq(FileStatus::Queued)),
+                )
+                .set(StatusChange::new(FileStatus::InternalError, now).with_reason(reason))
+                .execute(connection)?,
+                RunTarget::ScratchRun(scratch_run_id) => diesel::update(
+                    scratch_runs::table
+                        .find(scratch_run_id)
+                        .filter(scratch_runs::status.eq(FileStatus::Queued)),
+                )
+                .set((
+                    scratch_runs::status.eq(FileStatus::InternalError),
+                    scratch_runs::status_reason.eq(reason),
+                    scratch_runs::updated.eq(now),
+                    scratch_runs::finished_at.eq(now),
+                ))
+                .execute(connection)?,
+            };
+        }
+        Ok(failed)
+    })
+}
+
+pub fn status(id: Uuid, connection: &PgConnection) -> QueryResult<JobStatus> {
+    schedule_jobs::table
+        .find(id)
+        .select(schedule_jobs::status)
+        .get_result(connection)
+}
+
+pub fn latest_by_file_id(file_id: Uuid, connection: &PgConnection) -> QueryResult<Option<Job>> {
+    schedule_jobs::table
+        .filter(schedule_jobs::file_id.eq(file_id))
+        .order(schedule_jobs::created.desc())
+        .first(connection)
+        .optional()
+}
+
+pub fn latest_by_scratch_run_id(
+    scratch_run_id: Uuid,
+    connection: &PgConnection,
+) -> QueryResult<Option<Job>> {
+    schedule_jobs::table
+        .filter(schedule_jobs::scratch_run_id.eq(scratch_run_id))
+        .order(schedule_jobs::created.desc())
+        .first(connection)
+        .optional()
+}
+
+fn backoff(attempts: i16) -> Duration {
+    let exponent = (attempts.max(1) - 1).min(16) as u32;
+    Duration::seconds((BASE_BACKOFF_SECONDS * 2i64.pow(exponent)).min(MAX_BACKOFF_SECONDS))
+}
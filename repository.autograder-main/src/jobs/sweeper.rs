@@ -0,0 +1,71 @@
+use std::env;
+use std::thread;
+use std::time::Duration;
+
+use chrono::Utc;
+use diesel::PgConnection;
+
+use crate::audit::AuditContext;
+use crate::connection::Pool;
+use crate::files;
+use crate::scratch_runs;
+
+const SWEEP_INTERVAL: Duration = Duration::from_secs(30);
+const RUN_TIMEOUT_ENV: &str = "RUN_TIMEOUT_SECONDS";
+/// Run timeout of assignments without their own.
+const DEFAULT_RUN_TIMEOUT_SECONDS: i64 = 15 * 60;
+
+/// Start the background thread that ends or retries runs of files and ends
+/// scratch runs which got no result within their timeout.
+pub fn spawn(pool: Pool) {
+    let default_timeout_seconds = env::var(RUN_TIMEOUT_ENV)
+        .ok()
+        .map(|value| {
+            value
+                .parse()
+                .unwrap_or_else(|_| panic!("{} has to be a number of seconds", RUN_TIMEOUT_ENV))
+        })
+        .unwrap_or(DEFAULT_RUN_TIMEOUT_SECONDS);
+    thread::Builder::new()
+        .name("run-sweeper".to_owned())
+        .spawn(move || loop {
+            match pool.get() {
+                Ok(connection) => sweep(default_timeout_seconds, &connection),
+                Err(err) => error!("Run sweeper has no database connection: {}", err),
+            }
+            thread::sleep(SWEEP_INTERVAL);
+        })
+        .expect("could not start run sweeper");
+}
+
+fn sweep(default_timeout_seconds: i64, connection: &PgConnection) {
+    if let Err(err) = scratch_runs::repository::end_overdue(
+        default_timeout_seconds,
+        Utc::now().naive_utc(),
+        &AuditContext::system(),
+        connection,
+    ) {
+        error!("Could not end overdue scratch runs: {}", err);
+    }
+    let runs = match files::repository::overdue_runs(
+        default_timeout_seconds,
+        Utc::now().naive_utc(),
+        connection,
+    ) {
+        Ok(runs) => runs,
+        Err(err) => {
+            error!("Could not load overdue runs: {}", err);
+            return;
+        }
+    };
+    for run in runs {
+        if let Err(err) = files::repository::end_overdue_run(
+            &run,
+            default_timeout_seconds,
+            &AuditContext::system(),
+            connection,
+        ) {
+            error!("Could not end overdue run of file {}: {}", run.id, err);
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+
+use diesel::PgConnection;
+use jsonwebtoken::EncodingKey;
+
+use crate::auth::RunnerAuth;
+use crate::connection::Pool;
+use crate::execution::ExecutionBackend;
+use crate::files;
+use crate::jobs;
+use crate::jobs::{Job, JobStatus, RunTarget};
+use crate::scratch_runs;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Leases are renewed three times per lease, so that a slow renewal doesn't
+/// lose it.
+const RENEW_INTERVAL: Duration = Duration::from_secs(jobs::repository::LEASE_SECONDS as u64 / 3);
+
+/// Start the background thread that submits queued files and scratch runs to
+/// the execution backend.
+pub fn spawn(
+    pool: Pool,
+    callback_key: EncodingKey,
+    public_base_url: String,
+    backend: Box<dyn ExecutionBackend>,
+) {
+    thread::Builder::new()
+        .name("schedule-worker".to_owned())
+        .spawn(move || loop {
+            match pool.get() {
+                Ok(connection) => deliver_due(
+                    backend.as_ref(),
+                    &callback_key,
+                    &public_base_url,
+                    &pool,
+                    &connection,
+                ),
+                Err(err) => error!("Schedule worker has no database connection: {}", err),
+            }
+            thread::sleep(POLL_INTERVAL);
+        })
+        .expect("could not start schedule worker");
+}
+
+fn deliver_due(
+    backend: &dyn ExecutionBackend,
+    callback_key: &EncodingKey,
+    public_base_url: &str,
+    pool: &Pool,
+    connection: &PgConnection,
+) {
+    let jobs = match jobs::repository::claim_due(backend.batch_size(), connection) {
+        Ok(jobs) => jobs,
+        Err(err) => {
+            error!("Could not load schedule jobs: {}", err);
+            return;
+        }
+    };
+    for job in jobs {
+        // Jobs at the end of a batch may have waited longer than their lease.
+        match jobs::repository::renew_lease(&job, connection) {
+            Ok(true) => {}
+            Ok(false) => continue,
+            Err(err) => {
+                error!(
+                    "Could not renew the lease of schedule job {}: {}",
+                    job.id, err
+                );
+                continue;
+            }
+        }
+        let heartbeat = Heartbeat::start(pool.clone(), job.clone());
+        let delivered = deliver(backend, callback_key, public_base_url, &job, connection);
+        drop(heartbeat);
+        let result = match delivered {
+            Ok(()) => jobs::repository::mark_delivered(&job, connection),
+            Err(reason) => jobs::repository::mark_failed(&job, reason, connection),
+        };
+        match result {
+            Ok(Some(_)) => {}
+            Ok(None) => lost(&job, connection),
+            Err(err) => error!("Could not update schedule job {}: {}", job.id, err),
+        }
+    }
+}
+
+/// Log why a delivered job could no longer be updated. Files and scratch runs
+/// that are cancelled while they are delivered cancel their job, which is no
+/// error.
+fn lost(job: &Job, connection: &PgConnection) {
+    match jobs::repository::status(job.id, connection) {
+        Ok(JobStatus::Cancelled) => info!(
+            "Schedule job {} was cancelled while it was delivered",
+            job.id
+        ),
+        Ok(_) => error!(
+            "Schedule job {} was claimed by another worker while it was delivered",
+            job.id
+        ),
+        Err(err) => error!("Could not load schedule job {}: {}", job.id, err),
+    }
+}
+
+/// Renews the lease of a job in the background while it is delivered, which
+/// can take longer than the lease for backends that run the job themselves.
+/// Stops when it is dropped.
+struct Heartbeat {
+    stop: Sender<()>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Heartbeat {
+    fn start(pool: Pool, job: Job) -> Self {
+        let (stop, stopped) = mpsc::channel();
+        let thread = thread::spawn(move || loop {
+            match stopped.recv_timeout(RENEW_INTERVAL) {
+                Err(RecvTimeoutError::Timeout) => {}
+                _ => return,
+            }
+            let renewed = pool
+                .get()
+                .map_err(|err| err.to_string())
+                .and_then(|connection| {
+                    jobs::repository::renew_lease(&job, &connection).map_err(|err| err.to_string())
+                });
+            match renewed {
+                Ok(true) => {}
+                Ok(false) => return,
+                Err(err) => error!(
+                    "Could not renew the lease of schedule job {}: {}",
+                    job.id, err
+                ),
+            }
+        });
+        Heartbeat {
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for Heartbeat {
+    fn drop(&mut self) {
+        let _ = self.stop.send(());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn deliver(
+    backend: &dyn ExecutionBackend,
+    callback_key: &EncodingKey,
+    public_base_url: &str,
+    job: &Job,
+    connection: &PgConnection,
+) -> Result<(), String> {
+    let schedule_file = match job.target() {
+        RunTarget::File(file_id) => files::repository::get_schedule_file(
+            file_id,
+            RunnerAuth::for_file(file_id).token(callback_key),
+            public_base_url,
+            connection,
+        ),
+        RunTarget::ScratchRun(scratch_run_id) => scratch_runs::repository::get_schedule_file(
+            scratch_run_id,
+            RunnerAuth::for_scratch_run(scratch_run_id).token(callback_key),
+            public_base_url,
+            connection,
+        ),
+    }
+    .map_err(|err| err.to_string())?;
+    backend.submit(job, &schedule_file, connection)
+}
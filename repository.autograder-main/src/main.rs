@@ -1,15 +1,19 @@
 #![feature(decl_macro, proc_macro_hygiene)]
 
 extern crate base64;
+extern crate diff;
 #[macro_use]
 extern crate diesel;
 #[macro_use]
 extern crate diesel_migrations;
 extern crate dotenv;
 extern crate jsonwebtoken;
-#[macro_use(error)]
+extern crate libc;
+#[macro_use(error, info)]
 extern crate log;
+extern crate postgres;
 extern crate r2d2;
+extern crate regex;
 extern crate reqwest;
 #[macro_use]
 extern crate rocket;
@@ -18,26 +22,47 @@ extern crate rocket_cors;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde_json;
+extern crate sha2;
+extern crate tar;
+extern crate zip;
 
 use dotenv::dotenv;
+use jsonwebtoken::EncodingKey;
 use rocket::http::Method;
 use rocket_cors::{AllowedHeaders, AllowedOrigins, Cors, CorsOptions};
 
+mod access_tokens;
 mod assignments;
+mod audit;
 mod auth;
+mod collaborators;
 mod config;
 mod connection;
+mod enrollments;
+mod execution;
+mod extensions;
 mod files;
+mod jobs;
+mod regrades;
 mod schema;
+mod scratch_runs;
 mod submissions;
+mod test_cases;
+mod users;
 
 fn make_cors() -> Cors {
     CorsOptions {
         allowed_origins: AllowedOrigins::All,
-        allowed_methods: vec![Method::Options, Method::Get, Method::Post, Method::Patch]
-            .into_iter()
-            .map(From::from)
-            .collect(),
+        allowed_methods: vec![
+            Method::Options,
+            Method::Get,
+            Method::Post,
+            Method::Patch,
+            Method::Delete,
+        ]
+        .into_iter()
+        .map(From::from)
+        .collect(),
         allowed_headers: AllowedHeaders::All,
         allow_credentials: true,
         ..Default::default()
@@ -48,11 +73,32 @@ fn make_cors() -> Cors {
 
 fn main() {
     dotenv().ok();
-    let mut rkt = rocket::ignite().manage(connection::init_pool());
+    let pool = connection::init_pool();
+    jobs::sweeper::spawn(pool.clone());
+    regrades::worker::spawn(pool.clone());
+    jobs::worker::spawn(
+        pool.clone(),
+        EncodingKey::from_secret(&config::callback_secret()),
+        config::public_base_url(),
+        execution::from_env(),
+    );
+    let key_store = auth::jwks::KeyStore::from_env();
+    auth::jwks::spawn_refresh(key_store.clone());
+    let file_events = files::events::FileEvents::from_env();
+    files::events::listen(file_events.clone());
+    let mut rkt = rocket::ignite().manage(pool).manage(file_events);
     rkt = submissions::router::create_routes(rkt);
     rkt = files::router::create_routes(rkt);
     rkt = assignments::router::create_routes(rkt);
+    rkt = test_cases::router::create_routes(rkt);
+    rkt = extensions::router::create_routes(rkt);
+    rkt = collaborators::router::create_routes(rkt);
+    rkt = enrollments::router::create_routes(rkt);
+    rkt = regrades::router::create_routes(rkt);
+    rkt = scratch_runs::router::create_routes(rkt);
+    rkt = audit::router::create_routes(rkt);
+    rkt = access_tokens::router::create_routes(rkt);
     rkt.attach(make_cors())
-        .attach(config::AppState::manage())
+        .attach(config::AppState::manage(key_store))
         .launch();
 }
@@ -0,0 +1,69 @@
+use std::str::FromStr;
+
+use rocket::http::Status;
+use rocket::response::status;
+use rocket_contrib::json::Json;
+use uuid::Uuid;
+
+use crate::audit::{AuditContext, RequestId};
+use crate::auth::Auth;
+use crate::connection::{to_status_created, DbConn};
+use crate::regrades;
+use crate::regrades::{Regrade, RegradeRequest};
+
+#[post(
+    "/<assignment_id>/regrade",
+    format = "application/json",
+    data = "<regrade_request>"
+)]
+pub fn insert(
+    auth: Auth,
+    request_id: RequestId,
+    assignment_id: String,
+    regrade_request: Json<RegradeRequest>,
+    connection: DbConn,
+) -> Result<status::Created<Json<Regrade>>, rocket::http::Status> {
+    let audit = AuditContext::user(&auth, request_id);
+    let result: Result<Json<Regrade>, rocket::http::Status> = Uuid::from_str(&assignment_id)
+        .map_err(|_| Status::BadRequest)
+        .and_then(|uuid| {
+            regrades::repository::insert(
+                uuid,
+                regrade_request.into_inner(),
+                auth,
+                &audit,
+                &connection,
+            )
+            .into()
+        });
+    result.map(|regrade| {
+        let path = format!("/assignments/{}/regrades/", regrade.assignment_id);
+        to_status_created(regrade.id, &path, regrade)
+    })
+}
+
+#[get("/<assignment_id>/regrades")]
+pub fn all(
+    auth: Auth,
+    assignment_id: String,
+    connection: DbConn,
+) -> Result<Json<Vec<Regrade>>, rocket::http::Status> {
+    Uuid::from_str(&assignment_id)
+        .map_err(|_| Status::BadRequest)
+        .and_then(|uuid| regrades::repository::all_by_assignment_id(uuid, auth, &connection).into())
+}
+
+#[get("/<assignment_id>/regrades/<id>")]
+pub fn get(
+    auth: Auth,
+    assignment_id: String,
+    id: String,
+    connection: DbConn,
+) -> Result<Json<Regrade>, rocket::http::Status> {
+    Uuid::from_str(&assignment_id)
+        .and_then(|assignment_id| Uuid::from_str(&id).map(|id| (assignment_id, id)))
+        .map_err(|_| Status::BadRequest)
+        .and_then(|(assignment_id, id)| {
+            regrades::repository::get(assignment_id, id, auth, &connection).into()
+        })
+}
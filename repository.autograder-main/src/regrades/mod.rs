@@ -0,0 +1,93 @@
+use std::io::Write;
+
+use chrono::NaiveDateTime;
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Text;
+use uuid::Uuid;
+
+use crate::assignments::Assignment;
+
+use super::schema::regrades;
+
+pub mod handler;
+pub mod repository;
+pub mod router;
+pub mod worker;
+
+/// How the files of an assignment are graded again.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, AsExpression, FromSqlRow)]
+#[serde(rename_all = "snake_case")]
+#[sql_type = "Text"]
+pub enum RegradeMode {
+    /// Validate the stored outputs of the last runs against the current
+    /// expected outputs.
+    Revalidate,
+    /// Run the files again.
+    Rerun,
+}
+
+impl Default for RegradeMode {
+    fn default() -> Self {
+        RegradeMode::Revalidate
+    }
+}
+
+impl RegradeMode {
+    fn as_str(&self) -> &'static str {
+        match self {
+            RegradeMode::Revalidate => "revalidate",
+            RegradeMode::Rerun => "rerun",
+        }
+    }
+}
+
+impl ToSql<Text, Pg> for RegradeMode {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        <str as ToSql<Text, Pg>>::to_sql(self.as_str(), out)
+    }
+}
+
+impl FromSql<Text, Pg> for RegradeMode {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match <String as FromSql<Text, Pg>>::from_sql(bytes)?.as_str() {
+            "revalidate" => Ok(RegradeMode::Revalidate),
+            "rerun" => Ok(RegradeMode::Rerun),
+            other => Err(format!("unknown regrade mode: {}", other).into()),
+        }
+    }
+}
+
+/// Regrade of the active files of an assignment, which are processed in the
+/// background.
+#[derive(Identifiable, Queryable, Associations, PartialEq, Debug, Serialize)]
+#[belongs_to(Assignment)]
+pub struct Regrade {
+    pub id: Uuid,
+    pub assignment_id: Uuid,
+    pub user_id: Uuid,
+    pub mode: RegradeMode,
+    pub total: i32,
+    pub processed: i32,
+    pub failed: i32,
+    pub last_error: Option<String>,
+    pub created: NaiveDateTime,
+    pub updated: NaiveDateTime,
+    pub finished_at: Option<NaiveDateTime>,
+}
+
+#[derive(Deserialize)]
+pub struct RegradeRequest {
+    #[serde(default)]
+    pub mode: RegradeMode,
+}
+
+#[derive(Insertable)]
+#[table_name = "regrades"]
+pub struct InsertableRegrade {
+    pub assignment_id: Uuid,
+    pub user_id: Uuid,
+    pub mode: RegradeMode,
+    pub total: i32,
+}
@@ -0,0 +1,194 @@
+use chrono::Utc;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::assignments;
+use crate::audit;
+use crate::audit::AuditContext;
+use crate::auth::policy::{Action, Resource};
+use crate::auth::Auth;
+use crate::connection::RepositoryError::{NotFound, Rejected};
+use crate::connection::{RepositoryError, RepositoryQueryResult};
+use crate::files;
+use crate::regrades::{InsertableRegrade, Regrade, RegradeMode, RegradeRequest};
+use crate::schema::{regrade_files, regrades, submissions};
+
+/// Start a regrade of the active file of every submission of an assignment.
+/// Only one regrade of an assignment can run at a time.
+pub fn insert(
+    assignment_id: Uuid,
+    regrade_request: RegradeRequest,
+    auth: Auth,
+    audit: &AuditContext,
+    connection: &PgConnection,
+) -> RepositoryQueryResult<Regrade> {
+    if let Err(error) = assignments::repository::authorize(
+        assignment_id,
+        &auth,
+        Action::Create,
+        Resource::Regrade,
+        connection,
+    ) {
+        return RepositoryQueryResult::Err(error);
+    }
+    connection
+        .transaction::<_, RepositoryError<diesel::result::Error>, _>(|| {
+            let running: i64 = regrades::table
+                .filter(regrades::assignment_id.eq(assignment_id))
+                .filter(regrades::finished_at.is_null())
+                .count()
+                .get_result(connection)?;
+            if running > 0 {
+                return Err(Rejected(
+                    "a regrade of the assignment is running".to_owned(),
+                ));
+            }
+            let file_ids: Vec<Option<Uuid>> = submissions::table
+                .filter(submissions::assignment_id.eq(assignment_id))
+                .filter(submissions::active_file_id.is_not_null())
+                .select(submissions::active_file_id)
+                .load(connection)?;
+            let regrade: Regrade = diesel::insert_into(regrades::table)
+                .values(&InsertableRegrade {
+                    assignment_id,
+                    user_id: auth.user_id,
+                    mode: regrade_request.mode,
+                    total: file_ids.len() as i32,
+                })
+                .get_result(connection)?;
+            let regrade_files: Vec<_> = file_ids
+                .into_iter()
+                .flatten()
+                .map(|file_id| {
+                    (
+                        regrade_files::regrade_id.eq(regrade.id),
+                        regrade_files::file_id.eq(file_id),
+                    )
+                })
+                .collect();
+            diesel::insert_into(regrade_files::table)
+                .values(regrade_files)
+                .execute(connection)?;
+            audit::repository::record(
+                audit,
+                "create",
+                Resource::Regrade,
+                regrade.id,
+                None,
+                Some(&regrade),
+                connection,
+            )?;
+            Ok(regrade)
+        })
+        .into()
+}
+
+/// Regrades of an assignment, newest first.
+pub fn all_by_assignment_id(
+    assignment_id: Uuid,
+    auth: Auth,
+    connection: &PgConnection,
+) -> RepositoryQueryResult<Vec<Regrade>> {
+    if let Err(error) = assignments::repository::authorize(
+        assignment_id,
+        &auth,
+        Action::List,
+        Resource::Regrade,
+        connection,
+    ) {
+        return RepositoryQueryResult::Err(error);
+    }
+    regrades::table
+        .filter(regrades::assignment_id.eq(assignment_id))
+        .order(regrades::created.desc())
+        .load(connection)
+        .into()
+}
+
+pub fn get(
+    assignment_id: Uuid,
+    id: Uuid,
+    auth: Auth,
+    connection: &PgConnection,
+) -> RepositoryQueryResult<Regrade> {
+    if let Err(error) = assignments::repository::authorize(
+        assignment_id,
+        &auth,
+        Action::Read,
+        Resource::Regrade,
+        connection,
+    ) {
+        return RepositoryQueryResult::Err(error);
+    }
+    match regrades::table
+        .find(id)
+        .filter(regrades::assignment_id.eq(assignment_id))
+        .first(connection)
+    {
+        Ok(regrade) => RepositoryQueryResult::Ok(regrade),
+        Err(_) => RepositoryQueryResult::Err(NotFound),
+    }
+}
+
+/// Process the next files of the oldest unfinished regrade, skipping regrades
+/// another instance of the service is processing. A file that can't be
+/// regraded is counted as failed. Returns the number of processed files.
+pub fn process_batch(limit: i64, connection: &PgConnection) -> QueryResult<usize> {
+    connection.transaction(|| {
+        let regrade: Regrade = match regrades::table
+            .filter(regrades::finished_at.is_null())
+            .order(regrades::created.asc())
+            .for_update()
+            .skip_locked()
+            .first(connection)
+            .optional()?
+        {
+            Some(regrade) => regrade,
+            None => return Ok(0),
+        };
+        let file_ids: Vec<Uuid> = regrade_files::table
+            .filter(regrade_files::regrade_id.eq(regrade.id))
+            .filter(regrade_files::done.eq(false))
+            .select(regrade_files::file_id)
+            .limit(limit)
+            .load(connection)?;
+        let mut failed = 0;
+        let mut last_error = regrade.last_error;
+        let audit = AuditContext::on_behalf_of(regrade.user_id);
+        for file_id in &file_ids {
+            let result = match regrade.mode {
+                RegradeMode::Revalidate => {
+                    files::repository::revalidate(*file_id, &audit, connection)
+                }
+                RegradeMode::Rerun => files::repository::requeue(*file_id, &audit, connection),
+            };
+            if let Err(err) = result {
+                failed += 1;
+                last_error = Some(format!("file {}: {}", file_id, err));
+            }
+        }
+        diesel::update(
+            regrade_files::table
+                .filter(regrade_files::regrade_id.eq(regrade.id))
+                .filter(regrade_files::file_id.eq_any(file_ids.clone())),
+        )
+        .set(regrade_files::done.eq(true))
+        .execute(connection)?;
+        let now = Utc::now().naive_utc();
+        let finished_at = if (file_ids.len() as i64) < limit {
+            Some(now)
+        } else {
+            None
+        };
+        diesel::update(regrades::table.find(regrade.id))
+            .set((
+                regrades::processed.eq(regrade.processed + file_ids.len() as i32),
+                regrades::failed.eq(regrade.failed + failed),
+                regrades::last_error.eq(last_error),
+                regrades::updated.eq(now),
+                regrades::finished_at.eq(finished_at),
+            ))
+            .execute(connection)?;
+        Ok(file_ids.len())
+    })
+}
@@ -0,0 +1,10 @@
+use rocket::Rocket;
+
+use crate::regrades::handler;
+
+pub fn create_routes(rocket: Rocket) -> Rocket {
+    rocket.mount(
+        "/assignments",
+        routes![handler::insert, handler::all, handler::get],
+    )
+}
@@ -0,0 +1,38 @@
+use std::thread;
+use std::time::Duration;
+
+use diesel::PgConnection;
+
+use crate::connection::Pool;
+use crate::regrades;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const BATCH_SIZE: i64 = 50;
+
+/// Start the background thread that processes started regrades.
+pub fn spawn(pool: Pool) {
+    thread::Builder::new()
+        .name("regrade-worker".to_owned())
+        .spawn(move || loop {
+            match pool.get() {
+                Ok(connection) => process(&connection),
+                Err(err) => error!("Regrade worker has no database connection: {}", err),
+            }
+            thread::sleep(POLL_INTERVAL);
+        })
+        .expect("could not start regrade worker");
+}
+
+/// Process batches until there is no full batch left.
+fn process(connection: &PgConnection) {
+    loop {
+        match regrades::repository::process_batch(BATCH_SIZE, connection) {
+            Ok(processed) if processed as i64 == BATCH_SIZE => {}
+            Ok(_) => return,
+            Err(err) => {
+                error!("Could not process regrade: {}", err);
+                return;
+            }
+        }
+    }
+}
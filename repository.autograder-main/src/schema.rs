@@ -1,9 +1,77 @@
+table! {
+    access_tokens (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        name -> Text,
+        token_hash -> Text,
+        scopes -> Array<Text>,
+        is_superuser -> Bool,
+        is_teacher -> Bool,
+        is_student -> Bool,
+        email -> Text,
+        created -> Timestamp,
+        expires_at -> Timestamp,
+        last_used_at -> Nullable<Timestamp>,
+        revoked_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    assignment_collaborators (assignment_id, user_id) {
+        assignment_id -> Uuid,
+        user_id -> Uuid,
+        role -> Text,
+        created -> Timestamp,
+        updated -> Timestamp,
+    }
+}
+
 table! {
     assignments (id) {
         id -> Uuid,
         user_id -> Uuid,
-        encoded_input -> Bytea,
-        encoded_output -> Bytea,
+        updated -> Timestamp,
+        comparison_mode -> Text,
+        absolute_tolerance -> Nullable<Float8>,
+        relative_tolerance -> Nullable<Float8>,
+        opens_at -> Nullable<Timestamp>,
+        due_at -> Nullable<Timestamp>,
+        closes_at -> Nullable<Timestamp>,
+        late_penalty -> Float8,
+        max_attempts -> Nullable<Int2>,
+        run_timeout_seconds -> Nullable<Int4>,
+        max_run_retries -> Int2,
+    }
+}
+
+table! {
+    audit_events (id) {
+        id -> Uuid,
+        actor_kind -> Text,
+        actor_id -> Nullable<Uuid>,
+        action -> Text,
+        resource_type -> Text,
+        resource_id -> Uuid,
+        before_digest -> Nullable<Text>,
+        after_digest -> Nullable<Text>,
+        request_id -> Nullable<Text>,
+        created -> Timestamp,
+    }
+}
+
+table! {
+    enrollments (assignment_id, user_id) {
+        assignment_id -> Uuid,
+        user_id -> Uuid,
+        created -> Timestamp,
+    }
+}
+
+table! {
+    extensions (assignment_id, user_id) {
+        assignment_id -> Uuid,
+        user_id -> Uuid,
+        due_at -> Timestamp,
         updated -> Timestamp,
     }
 }
@@ -14,9 +82,100 @@ table! {
         submission_id -> Uuid,
         updated -> Timestamp,
         encoded_text -> Bytea,
-        scheduled -> Bool,
-        validated -> Bool,
-        encoded_output -> Nullable<Bytea>,
+        score -> Float8,
+        max_score -> Float8,
+        late -> Bool,
+        status -> Text,
+        queued_at -> Nullable<Timestamp>,
+        started_at -> Nullable<Timestamp>,
+        finished_at -> Nullable<Timestamp>,
+        run_retries -> Int2,
+        status_reason -> Nullable<Text>,
+        created -> Timestamp,
+    }
+}
+
+table! {
+    regrade_files (regrade_id, file_id) {
+        regrade_id -> Uuid,
+        file_id -> Uuid,
+        done -> Bool,
+    }
+}
+
+table! {
+    regrades (id) {
+        id -> Uuid,
+        assignment_id -> Uuid,
+        user_id -> Uuid,
+        mode -> Text,
+        total -> Int4,
+        processed -> Int4,
+        failed -> Int4,
+        last_error -> Nullable<Text>,
+        created -> Timestamp,
+        updated -> Timestamp,
+        finished_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    run_results (file_id) {
+        file_id -> Uuid,
+        encoded_stdout -> Bytea,
+        encoded_stderr -> Bytea,
+        exit_code -> Nullable<Int4>,
+        signal -> Nullable<Int4>,
+        duration_ms -> Nullable<Int8>,
+        memory_kb -> Nullable<Int8>,
+        updated -> Timestamp,
+    }
+}
+
+table! {
+    schedule_jobs (id) {
+        id -> Uuid,
+        file_id -> Nullable<Uuid>,
+        status -> Text,
+        attempts -> Int2,
+        next_attempt_at -> Timestamp,
+        last_error -> Nullable<Text>,
+        created -> Timestamp,
+        updated -> Timestamp,
+        delivered_at -> Nullable<Timestamp>,
+        scratch_run_id -> Nullable<Uuid>,
+        lease_token -> Nullable<Uuid>,
+    }
+}
+
+table! {
+    scratch_runs (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        assignment_id -> Uuid,
+        extension -> Text,
+        encoded_text -> Bytea,
+        encoded_input -> Bytea,
+        status -> Text,
+        status_reason -> Nullable<Text>,
+        encoded_stdout -> Bytea,
+        encoded_stderr -> Bytea,
+        exit_code -> Nullable<Int4>,
+        signal -> Nullable<Int4>,
+        duration_ms -> Nullable<Int8>,
+        memory_kb -> Nullable<Int8>,
+        created -> Timestamp,
+        updated -> Timestamp,
+        finished_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    source_files (id) {
+        id -> Uuid,
+        file_id -> Uuid,
+        path -> Text,
+        encoded_text -> Bytea,
     }
 }
 
@@ -28,10 +187,83 @@ table! {
         extension -> Text,
         created -> Timestamp,
         update_count -> Int2,
+        active_file_id -> Nullable<Uuid>,
+    }
+}
+
+table! {
+    test_case_results (id) {
+        id -> Uuid,
+        file_id -> Uuid,
+        test_case_id -> Uuid,
+        encoded_output -> Bytea,
+        passed -> Bool,
+        updated -> Timestamp,
+        encoded_stderr -> Bytea,
+        exit_code -> Nullable<Int4>,
+        signal -> Nullable<Int4>,
+        duration_ms -> Nullable<Int8>,
+        memory_kb -> Nullable<Int8>,
+    }
+}
+
+table! {
+    test_cases (id) {
+        id -> Uuid,
+        assignment_id -> Uuid,
+        position -> Int2,
+        name -> Text,
+        encoded_input -> Bytea,
+        encoded_output -> Bytea,
+        updated -> Timestamp,
+        weight -> Float8,
+    }
+}
+
+table! {
+    users (user_id) {
+        user_id -> Uuid,
+        is_superuser -> Bool,
+        is_teacher -> Bool,
+        is_student -> Bool,
+        email -> Text,
+        updated -> Timestamp,
     }
 }
 
+joinable!(assignment_collaborators -> assignments (assignment_id));
+joinable!(enrollments -> assignments (assignment_id));
+joinable!(extensions -> assignments (assignment_id));
 joinable!(files -> submissions (submission_id));
+joinable!(regrade_files -> files (file_id));
+joinable!(regrade_files -> regrades (regrade_id));
+joinable!(regrades -> assignments (assignment_id));
+joinable!(run_results -> files (file_id));
+joinable!(schedule_jobs -> files (file_id));
+joinable!(schedule_jobs -> scratch_runs (scratch_run_id));
+joinable!(scratch_runs -> assignments (assignment_id));
+joinable!(source_files -> files (file_id));
 joinable!(submissions -> assignments (assignment_id));
+joinable!(test_case_results -> files (file_id));
+joinable!(test_case_results -> test_cases (test_case_id));
+joinable!(test_cases -> assignments (assignment_id));
 
-allow_tables_to_appear_in_same_query!(assignments, files, submissions,);
+allow_tables_to_appear_in_same_query!(
+    access_tokens,
+    assignment_collaborators,
+    assignments,
+    audit_events,
+    enrollments,
+    extensions,
+    files,
+    regrade_files,
+    regrades,
+    run_results,
+    schedule_jobs,
+    scratch_runs,
+    source_files,
+    submissions,
+    test_case_results,
+    test_cases,
+    users,
+);
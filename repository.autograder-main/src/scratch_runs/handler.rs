@@ -0,0 +1,95 @@
+use std::str::FromStr;
+
+use rocket::http::Status;
+use rocket::response::status;
+use rocket_contrib::json::Json;
+use uuid::Uuid;
+
+use crate::audit::{AuditContext, RequestId};
+use crate::auth::{Auth, RunnerAuth};
+use crate::connection::{to_status_created, DbConn};
+use crate::files::{ScheduleOutputFile, StatusUpdate};
+use crate::scratch_runs;
+use crate::scratch_runs::{InsertableScratchRun, ScratchRun};
+
+#[get("/")]
+pub fn all(auth: Auth, connection: DbConn) -> Result<Json<Vec<ScratchRun>>, rocket::http::Status> {
+    scratch_runs::repository::all(auth, &connection).into()
+}
+
+#[get("/<id>")]
+pub fn get(
+    auth: Auth,
+    id: String,
+    connection: DbConn,
+) -> Result<Json<ScratchRun>, rocket::http::Status> {
+    Uuid::from_str(&id)
+        .map_err(|_| Status::BadRequest)
+        .and_then(|uuid| scratch_runs::repository::get(uuid, auth, &connection).into())
+}
+
+#[post("/", format = "application/json", data = "<insertable_scratch_run>")]
+pub fn insert(
+    auth: Auth,
+    request_id: RequestId,
+    insertable_scratch_run: Json<InsertableScratchRun>,
+    connection: DbConn,
+) -> Result<status::Created<Json<ScratchRun>>, rocket::http::Status> {
+    let audit = AuditContext::user(&auth, request_id);
+    let result: Result<Json<ScratchRun>, rocket::http::Status> = scratch_runs::repository::insert(
+        insertable_scratch_run.into_inner(),
+        auth,
+        &audit,
+        &connection,
+    )
+    .into();
+    result.map(|scratch_run| to_status_created(scratch_run.id, "/scratch_runs/", scratch_run))
+}
+
+#[patch("/<id>/status", format = "application/json", data = "<status_update>")]
+pub fn patch_status(
+    runner_auth: RunnerAuth,
+    request_id: RequestId,
+    id: String,
+    status_update: Json<StatusUpdate>,
+    connection: DbConn,
+) -> Result<Json<ScratchRun>, rocket::http::Status> {
+    Uuid::from_str(&id)
+        .map_err(|_| Status::BadRequest)
+        .and_then(|uuid| {
+            scratch_runs::repository::update_status_by_runner(
+                uuid,
+                &runner_auth,
+                status_update.into_inner(),
+                &AuditContext::runner(Some(request_id)),
+                &connection,
+            )
+            .into()
+        })
+}
+
+#[patch(
+    "/<id>/output",
+    format = "application/json",
+    data = "<schedule_output_file>"
+)]
+pub fn patch_output(
+    runner_auth: RunnerAuth,
+    request_id: RequestId,
+    id: String,
+    schedule_output_file: Json<ScheduleOutputFile>,
+    connection: DbConn,
+) -> Result<Json<ScratchRun>, rocket::http::Status> {
+    Uuid::from_str(&id)
+        .map_err(|_| Status::BadRequest)
+        .and_then(|uuid| {
+            scratch_runs::repository::update_output(
+                uuid,
+                runner_auth,
+                schedule_output_file.into_inner(),
+                &AuditContext::runner(Some(request_id)),
+                &connection,
+            )
+            .into()
+        })
+}
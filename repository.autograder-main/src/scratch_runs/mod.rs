@@ -0,0 +1,113 @@
+use chrono::NaiveDateTime;
+use uuid::Uuid;
+
+use crate::assignments::Assignment;
+use crate::connection::{deserialize_base64, serialize_base64};
+use crate::files::status::FileStatus;
+use crate::files::{ScheduleOutputFile, ScheduleOutputTestCase};
+
+use super::schema::scratch_runs;
+
+pub mod handler;
+pub mod repository;
+pub mod router;
+
+/// Run of code against input of the student's choice. A scratch run is no
+/// submission: it has no test cases, is not graded and does not count as an
+/// attempt. It passes if it ran without an error.
+#[derive(Identifiable, Queryable, Associations, PartialEq, Debug, Serialize)]
+#[belongs_to(Assignment)]
+pub struct ScratchRun {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub assignment_id: Uuid,
+    pub extension: String,
+    #[serde(serialize_with = "serialize_base64")]
+    pub encoded_text: Vec<u8>,
+    #[serde(serialize_with = "serialize_base64")]
+    pub encoded_input: Vec<u8>,
+    pub status: FileStatus,
+    pub status_reason: Option<String>,
+    #[serde(serialize_with = "serialize_base64")]
+    pub encoded_stdout: Vec<u8>,
+    #[serde(serialize_with = "serialize_base64")]
+    pub encoded_stderr: Vec<u8>,
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+    pub duration_ms: Option<i64>,
+    pub memory_kb: Option<i64>,
+    pub created: NaiveDateTime,
+    pub updated: NaiveDateTime,
+    pub finished_at: Option<NaiveDateTime>,
+}
+
+#[derive(Deserialize, Insertable)]
+#[table_name = "scratch_runs"]
+pub struct InsertableScratchRun {
+    #[serde(skip)]
+    pub user_id: Uuid,
+    pub assignment_id: Uuid,
+    pub extension: String,
+    #[serde(deserialize_with = "deserialize_base64")]
+    pub encoded_text: Vec<u8>,
+    #[serde(deserialize_with = "deserialize_base64")]
+    #[serde(default)]
+    pub encoded_input: Vec<u8>,
+}
+
+/// Output of a scratch run. The run has a single test case with the input of
+/// the student, whose output is the output of the run.
+#[derive(AsChangeset)]
+#[table_name = "scratch_runs"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct ScratchRunOutput {
+    pub status: FileStatus,
+    pub encoded_stdout: Vec<u8>,
+    pub encoded_stderr: Vec<u8>,
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+    pub duration_ms: Option<i64>,
+    pub memory_kb: Option<i64>,
+    pub updated: NaiveDateTime,
+    pub finished_at: Option<NaiveDateTime>,
+}
+
+impl ScratchRunOutput {
+    /// The stderr of the whole run, for example of the compiler, comes before
+    /// the stderr of the test case.
+    pub fn new(id: Uuid, schedule_output_file: ScheduleOutputFile, updated: NaiveDateTime) -> Self {
+        let status = schedule_output_file.status.unwrap_or(FileStatus::Passed);
+        let mut encoded_stderr = schedule_output_file.encoded_stderr.unwrap_or_default();
+        let result: Option<ScheduleOutputTestCase> = schedule_output_file
+            .results
+            .into_iter()
+            .find(|result| result.test_case_id == id);
+        match result {
+            Some(result) => {
+                encoded_stderr.extend(result.encoded_stderr.unwrap_or_default());
+                ScratchRunOutput {
+                    status,
+                    encoded_stdout: result.encoded_output,
+                    encoded_stderr,
+                    exit_code: result.exit_code.or(schedule_output_file.exit_code),
+                    signal: result.signal.or(schedule_output_file.signal),
+                    duration_ms: result.duration_ms.or(schedule_output_file.duration_ms),
+                    memory_kb: result.memory_kb.or(schedule_output_file.memory_kb),
+                    updated,
+                    finished_at: Some(updated),
+                }
+            }
+            None => ScratchRunOutput {
+                status,
+                encoded_stdout: schedule_output_file.encoded_stdout.unwrap_or_default(),
+                encoded_stderr,
+                exit_code: schedule_output_file.exit_code,
+                signal: schedule_output_file.signal,
+                duration_ms: schedule_output_file.duration_ms,
+                memory_kb: schedule_output_file.memory_kb,
+                updated,
+                finished_at: Some(updated),
+            },
+        }
+    }
+}
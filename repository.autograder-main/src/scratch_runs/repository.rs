@@ -0,0 +1,315 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::audit;
+use crate::audit::AuditContext;
+use crate::auth::policy::{Action, Relation, Resource};
+use crate::auth::{policy, Auth, RunnerAuth};
+use crate::connection::RepositoryError::{NotFound, QueryError, Rejected, Unauthorized};
+use crate::connection::{RepositoryError, RepositoryQueryResult};
+use crate::files::status::FileStatus;
+use crate::files::{ScheduleInputFile, ScheduleOutputFile, ScheduleTestCase, StatusUpdate};
+use crate::jobs;
+use crate::jobs::JobStatus;
+use crate::schema::{assignments, scratch_runs};
+use crate::scratch_runs::{InsertableScratchRun, ScratchRun, ScratchRunOutput};
+
+/// Scratch runs kept per user. The oldest runs of a user are deleted when a
+/// new one would exceed it.
+pub const MAX_SCRATCH_RUNS_PER_USER: i64 = 20;
+
+/// Store a scratch run of the user and queue it. Students enrolled in the
+/// assignment and its collaborators can try code with it.
+pub fn insert(
+    mut insertable_scratch_run: InsertableScratchRun,
+    auth: Auth,
+    audit: &AuditContext,
+    connection: &PgConnection,
+) -> RepositoryQueryResult<ScratchRun> {
+    if let Err(error) = crate::assignments::repository::authorize(
+        insertable_scratch_run.assignment_id,
+        &auth,
+        Action::Create,
+        Resource::ScratchRun,
+        connection,
+    ) {
+        return RepositoryQueryResult::Err(error);
+    }
+    insertable_scratch_run.user_id = auth.user_id;
+    connection
+        .transaction::<_, diesel::result::Error, _>(|| {
+            let scratch_run: ScratchRun = diesel::insert_into(scratch_runs::table)
+                .values(&insertable_scratch_run)
+                .get_result(connection)?;
+            jobs::repository::enqueue_scratch_run(scratch_run.id, connection)?;
+            audit::repository::record(
+                audit,
+                "create",
+                Resource::ScratchRun,
+                scratch_run.id,
+                None,
+                Some(&scratch_run),
+                connection,
+            )?;
+            let expired: Vec<ScratchRun> = scratch_runs::table
+                .filter(scratch_runs::user_id.eq(auth.user_id))
+                .order(scratch_runs::created.desc())
+                .offset(MAX_SCRATCH_RUNS_PER_USER)
+                .load(connection)?;
+            for expired in &expired {
+                diesel::delete(scratch_runs::table.find(expired.id)).execute(connection)?;
+                audit::repository::record(
+                    audit,
+                    "delete",
+                    Resource::ScratchRun,
+                    expired.id,
+                    Some(expired),
+                    None,
+                    connection,
+                )?;
+            }
+            Ok(scratch_run)
+        })
+        .into()
+}
+
+/// Scratch runs of the user, newest first.
+pub fn all(auth: Auth, connection: &PgConnection) -> RepositoryQueryResult<Vec<ScratchRun>> {
+    if let Err(error) =
+        policy::authorize(&auth, Relation::Author, Action::List, Resource::ScratchRun)
+    {
+        return RepositoryQueryResult::Err(error);
+    }
+    scratch_runs::table
+        .filter(scratch_runs::user_id.eq(auth.user_id))
+        .order(scratch_runs::created.desc())
+        .load(connection)
+        .into()
+}
+
+pub fn get(id: Uuid, auth: Auth, connection: &PgConnection) -> RepositoryQueryResult<ScratchRun> {
+    let scratch_run = match scratch_runs::table.find(id).first::<ScratchRun>(connection) {
+        Ok(scratch_run) => scratch_run,
+        Err(_) => return RepositoryQueryResult::Err(NotFound),
+    };
+    let relation = if scratch_run.user_id == auth.user_id {
+        Relation::Author
+    } else {
+        match crate::assignments::repository::relation_of(
+            scratch_run.assignment_id,
+            &auth,
+            connection,
+        ) {
+            Ok(relation) => relation,
+            Err(err) => return RepositoryQueryResult::Err(QueryError(err)),
+        }
+    };
+    match policy::authorize(&auth, relation, Action::Read, Resource::ScratchRun) {
+        Ok(()) => RepositoryQueryResult::Ok(scratch_run),
+        Err(error) => RepositoryQueryResult::Err(error),
+    }
+}
+
+/// Mark a scratch run as running when the runner the callback token was
+/// issued to picks it up.
+pub fn update_status_by_runner(
+    id: Uuid,
+    runner_auth: &RunnerAuth,
+    status_update: StatusUpdate,
+    audit: &AuditContext,
+    connection: &PgConnection,
+) -> RepositoryQueryResult<ScratchRun> {
+    if !runner_auth.is_for_scratch_run(id) {
+        return RepositoryQueryResult::Err(Unauthorized(
+            "callback token was issued for another scratch run".to_owned(),
+        ));
+    }
+    match status_update.status {
+        FileStatus::Running => connection
+            .transaction::<_, RepositoryError<diesel::result::Error>, _>(|| {
+                let before = lock_for_status_change(id, FileStatus::Running, connection)?;
+                let after: ScratchRun = diesel::update(scratch_runs::table.find(id))
+                    .set((
+                        scratch_runs::status.eq(FileStatus::Running),
+                        scratch_runs::updated.eq(Utc::now().naive_utc()),
+                    ))
+                    .get_result(connection)?;
+                audit::repository::record(
+                    audit,
+                    "update_status",
+                    Resource::ScratchRun,
+                    id,
+                    Some(&before),
+                    Some(&after),
+                    connection,
+                )?;
+                Ok(after)
+            })
+            .into(),
+        status => RepositoryQueryResult::Err(Rejected(format!(
+            "runner can't set status {}",
+            status.as_str()
+        ))),
+    }
+}
+
+/// Store the output of a scratch run. A runner that could not run it reports
+/// the error status, otherwise the run passes.
+pub fn update_output(
+    id: Uuid,
+    runner_auth: RunnerAuth,
+    schedule_output_file: ScheduleOutputFile,
+    audit: &AuditContext,
+    connection: &PgConnection,
+) -> RepositoryQueryResult<ScratchRun> {
+    if !runner_auth.is_for_scratch_run(id) {
+        return RepositoryQueryResult::Err(Unauthorized(
+            "callback token was issued for another scratch run".to_owned(),
+        ));
+    }
+    let output = ScratchRunOutput::new(id, schedule_output_file, Utc::now().naive_utc());
+    if !output.status.is_finished() || output.status == FileStatus::Failed {
+        return RepositoryQueryResult::Err(Rejected(format!(
+            "runner can't report status {}",
+            output.status.as_str()
+        )));
+    }
+    connection
+        .transaction::<_, RepositoryError<diesel::result::Error>, _>(|| {
+            let before = lock_for_status_change(id, output.status, connection)?;
+            let after: ScratchRun = diesel::update(scratch_runs::table.find(id))
+                .set(output)
+                .get_result(connection)?;
+            audit::repository::record(
+                audit,
+                "update_output",
+                Resource::ScratchRun,
+                id,
+                Some(&before),
+                Some(&after),
+                connection,
+            )?;
+            Ok(after)
+        })
+        .into()
+}
+
+/// End scratch runs that got no result within the run timeout of their
+/// assignment with a timeout, as they are never run again. Like files, a
+/// queued scratch run is overdue from its delivery on and a running one from
+/// its start. Returns the number of ended scratch runs.
+pub fn end_overdue(
+    default_timeout_seconds: i64,
+    now: NaiveDateTime,
+    audit: &AuditContext,
+    connection: &PgConnection,
+) -> QueryResult<usize> {
+    let runs: Vec<(Uuid, FileStatus, NaiveDateTime, Option<i32>)> = scratch_runs::table
+        .inner_join(assignments::table)
+        .filter(scratch_runs::status.eq_any(vec![FileStatus::Queued, FileStatus::Running]))
+        .select((
+            scratch_runs::id,
+            scratch_runs::status,
+            scratch_runs::updated,
+            assignments::run_timeout_seconds,
+        ))
+        .load(connection)?;
+    let mut ended = 0;
+    for (id, status, updated, run_timeout_seconds) in runs {
+        let started_at = match status {
+            FileStatus::Running => Some(updated),
+            _ => jobs::repository::latest_by_scratch_run_id(id, connection)?
+                .filter(|job| job.status == JobStatus::Delivered)
+                .and_then(|job| job.delivered_at),
+        };
+        let timeout = run_timeout_seconds.map_or(default_timeout_seconds, i64::from);
+        if !started_at.map_or(false, |started_at| {
+            started_at + Duration::seconds(timeout) < now
+        }) {
+            continue;
+        }
+        let timed_out = connection.transaction::<_, diesel::result::Error, _>(|| {
+            let before: ScratchRun = scratch_runs::table
+                .find(id)
+                .for_update()
+                .first(connection)?;
+            // left alone if the runner reported it since it was loaded
+            if before.status != status || before.updated != updated {
+                return Ok(false);
+            }
+            let after: ScratchRun = diesel::update(scratch_runs::table.find(id))
+                .set((
+                    scratch_runs::status.eq(FileStatus::Timeout),
+                    scratch_runs::status_reason.eq(format!("no result within {} seconds", timeout)),
+                    scratch_runs::updated.eq(now),
+                    scratch_runs::finished_at.eq(now),
+                ))
+                .get_result(connection)?;
+            audit::repository::record(
+                audit,
+                "timeout",
+                Resource::ScratchRun,
+                id,
+                Some(&before),
+                Some(&after),
+                connection,
+            )?;
+            Ok(true)
+        })?;
+        if timed_out {
+            ended += 1;
+        }
+    }
+    Ok(ended)
+}
+
+/// Lock a scratch run until the end of the transaction and check that it can
+/// move to the given status. A scratch run is never run again.
+fn lock_for_status_change(
+    id: Uuid,
+    status: FileStatus,
+    connection: &PgConnection,
+) -> Result<ScratchRun, RepositoryError<diesel::result::Error>> {
+    let scratch_run: ScratchRun = scratch_runs::table
+        .find(id)
+        .for_update()
+        .first(connection)?;
+    if !scratch_run.status.is_finished() && scratch_run.status.can_change_to(status) {
+        Ok(scratch_run)
+    } else {
+        Err(Rejected(format!(
+            "scratch run can't change from {} to {}",
+            scratch_run.status.as_str(),
+            status.as_str()
+        )))
+    }
+}
+
+/// Scratch run as it is sent to the scheduling service, with the input of the
+/// student as its only test case.
+pub fn get_schedule_file(
+    id: Uuid,
+    callback_token: String,
+    public_base_url: &str,
+    connection: &PgConnection,
+) -> QueryResult<ScheduleInputFile> {
+    scratch_runs::table
+        .find(id)
+        .first::<ScratchRun>(connection)
+        .map(|scratch_run| ScheduleInputFile {
+            id: scratch_run.id,
+            extension: scratch_run.extension,
+            assignment_id: scratch_run.assignment_id,
+            encoded_text: scratch_run.encoded_text,
+            files: Vec::new(),
+            test_cases: vec![ScheduleTestCase {
+                id: scratch_run.id,
+                name: "input".to_owned(),
+                encoded_input: scratch_run.encoded_input,
+            }],
+            callback_token,
+            callback_path: format!("/scratch_runs/{}", scratch_run.id),
+            callback_url: format!("{}/scratch_runs/{}", public_base_url, scratch_run.id),
+        })
+}
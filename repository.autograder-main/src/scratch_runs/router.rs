@@ -0,0 +1,16 @@
+use rocket::Rocket;
+
+use crate::scratch_runs::handler;
+
+pub fn create_routes(rocket: Rocket) -> Rocket {
+    rocket.mount(
+        "/scratch_runs",
+        routes![
+            handler::all,
+            handler::get,
+            handler::insert,
+            handler::patch_status,
+            handler::patch_output
+        ],
+    )
+}
@@ -1,18 +1,23 @@
+use std::io::Read;
 use std::str::FromStr;
 
-use rocket::http::Status;
+use rocket::http::{ContentType, Status};
 use rocket::response::status;
+use rocket::Data;
 use rocket_contrib::json::Json;
 use uuid::Uuid;
 
 use connection::DbConn;
 
+use crate::audit::{AuditContext, RequestId};
 use crate::auth::Auth;
-use crate::connection::to_status_created;
-use crate::files;
-use crate::files::InsertableFile;
+use crate::connection::{to_status_created, Page, PageRequest};
+use crate::files::{FileVersion, InsertableSourceFile};
 use crate::submissions;
-use crate::submissions::{InsertableCode, InsertableSubmission, Submission, SubmissionWithFile};
+use crate::submissions::project::{ArchiveFormat, MAX_PROJECT_SIZE};
+use crate::submissions::{
+    ActiveFile, InsertableCode, InsertableSubmission, Submission, SubmissionWithFile,
+};
 
 use super::super::connection;
 
@@ -53,39 +58,166 @@ pub fn all_submissions_for_assignment(
         })
 }
 
+#[get("/<id>")]
+pub fn get(
+    auth: Auth,
+    id: String,
+    connection: DbConn,
+) -> Result<Json<SubmissionWithFile>, rocket::http::Status> {
+    Uuid::from_str(&id)
+        .map_err(|_| Status::BadRequest)
+        .and_then(|uuid| submissions::repository::get_with_file(uuid, auth, &connection).into())
+}
+
+#[get("/<id>/files?<page>&<size>")]
+pub fn all_files(
+    auth: Auth,
+    id: String,
+    page: Option<i64>,
+    size: Option<i64>,
+    connection: DbConn,
+) -> Result<Json<Page<FileVersion>>, rocket::http::Status> {
+    Uuid::from_str(&id)
+        .map_err(|_| Status::BadRequest)
+        .and_then(|uuid| {
+            submissions::repository::all_files(
+                uuid,
+                auth,
+                PageRequest::new(page, size),
+                &connection,
+            )
+            .into()
+        })
+}
+
+#[patch(
+    "/<id>/active_file",
+    format = "application/json",
+    data = "<active_file>"
+)]
+pub fn set_active_file(
+    auth: Auth,
+    request_id: RequestId,
+    id: String,
+    active_file: Json<ActiveFile>,
+    connection: DbConn,
+) -> Result<Json<SubmissionWithFile>, rocket::http::Status> {
+    let audit = AuditContext::user(&auth, request_id);
+    Uuid::from_str(&id)
+        .map_err(|_| Status::BadRequest)
+        .and_then(|uuid| {
+            submissions::repository::set_active_file(
+                uuid,
+                active_file.file_id,
+                auth,
+                &audit,
+                &connection,
+            )
+            .into()
+        })
+}
+
 #[post("/", format = "application/json", data = "<insertable_code>")]
 pub fn insert(
     auth: Auth,
+    request_id: RequestId,
     insertable_code: Json<InsertableCode>,
     connection: DbConn,
 ) -> Result<status::Created<Json<SubmissionWithFile>>, rocket::http::Status> {
     let insertable_code_parsed = insertable_code.into_inner();
-    let result: Result<Json<Submission>, rocket::http::Status> = submissions::repository::insert(
-        InsertableSubmission::from(&insertable_code_parsed),
-        auth,
-        &connection,
-    )
-    .into();
-    result
-        .map(|submission| submission.into_inner())
+    let insertable_submission = InsertableSubmission::from(&insertable_code_parsed);
+    base64::decode(&insertable_code_parsed.encoded_text)
         .map_err(|_| Status::BadRequest)
-        .and_then(|submission| {
-            InsertableFile::from_insertable_code(&submission, &insertable_code_parsed)
-                .map(|insertable_file| (submission, insertable_file))
+        .and_then(|encoded_text| {
+            submissions::project::validate(insertable_code_parsed.files)
+                .map(|source_files| (encoded_text, source_files))
                 .map_err(|_| Status::BadRequest)
         })
-        .and_then(|(submission, insertable_file)| {
-            files::repository::insert(insertable_file, &connection)
-                .map(|file| (submission, file))
-                .map_err(|_| Status::BadRequest)
+        .and_then(|(encoded_text, source_files)| {
+            insert_version(
+                auth,
+                request_id,
+                insertable_submission,
+                encoded_text,
+                source_files,
+                &connection,
+            )
+        })
+}
+
+/// Submit a project as a zip or tar archive, whose regular files become the
+/// files of the submitted version.
+#[post("/archive?<assignment_id>&<user_id>&<extension>", data = "<archive>")]
+#[allow(clippy::too_many_arguments)]
+pub fn insert_archive(
+    auth: Auth,
+    request_id: RequestId,
+    assignment_id: String,
+    user_id: String,
+    extension: String,
+    content_type: &ContentType,
+    archive: Data,
+    connection: DbConn,
+) -> Result<status::Created<Json<SubmissionWithFile>>, rocket::http::Status> {
+    let format =
+        ArchiveFormat::from_content_type(content_type).ok_or(Status::UnsupportedMediaType)?;
+    let insertable_submission = Uuid::from_str(&assignment_id)
+        .and_then(|assignment_id| {
+            Uuid::from_str(&user_id).map(|user_id| InsertableSubmission {
+                assignment_id,
+                user_id,
+                extension,
+            })
         })
-        .map(SubmissionWithFile::from)
-        .map(Json)
-        .map(|submission_with_file| {
-            to_status_created(
-                submission_with_file.file_id,
-                "/files/",
-                submission_with_file,
+        .map_err(|_| Status::BadRequest)?;
+    let mut bytes = Vec::new();
+    archive
+        .open()
+        .take(MAX_PROJECT_SIZE + 1)
+        .read_to_end(&mut bytes)
+        .map_err(|_| Status::BadRequest)?;
+    if bytes.len() as u64 > MAX_PROJECT_SIZE {
+        return Err(Status::PayloadTooLarge);
+    }
+    submissions::project::extract(bytes, format)
+        .map_err(|_| Status::BadRequest)
+        .and_then(|source_files| {
+            insert_version(
+                auth,
+                request_id,
+                insertable_submission,
+                Vec::new(),
+                source_files,
+                &connection,
             )
         })
 }
+
+/// Insert or resubmit the submission and store the code as its new version.
+fn insert_version(
+    auth: Auth,
+    request_id: RequestId,
+    insertable_submission: InsertableSubmission,
+    encoded_text: Vec<u8>,
+    source_files: Vec<InsertableSourceFile>,
+    connection: &DbConn,
+) -> Result<status::Created<Json<SubmissionWithFile>>, rocket::http::Status> {
+    let audit = AuditContext::user(&auth, request_id);
+    let result: Result<Json<SubmissionWithFile>, rocket::http::Status> =
+        submissions::repository::insert(
+            insertable_submission,
+            encoded_text,
+            source_files,
+            auth,
+            &audit,
+            connection,
+        )
+        .into();
+    result.map(|submission_with_file| {
+        to_status_created(
+            submission_with_file.file_id,
+            "/files/",
+            submission_with_file,
+        )
+    })
+}
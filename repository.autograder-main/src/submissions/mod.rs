@@ -1,11 +1,13 @@
 use chrono::NaiveDateTime;
 use uuid::Uuid;
 
-use crate::files::File;
+use crate::files::status::FileStatus;
+use crate::files::{File, InsertableSourceFile};
 
 use super::schema::submissions;
 
 pub mod handler;
+pub mod project;
 pub mod repository;
 pub mod router;
 
@@ -19,6 +21,7 @@ pub struct Submission {
     pub extension: String,
     pub created: NaiveDateTime,
     pub update_count: i16,
+    pub active_file_id: Option<Uuid>,
 }
 
 #[derive(Serialize, Identifiable)]
@@ -29,28 +32,56 @@ pub struct SubmissionWithFile {
     pub user_id: Uuid,
     pub extension: String,
     pub update_count: i16,
+    pub attempts_used: i16,
+    pub max_attempts: Option<i16>,
+    pub remaining_attempts: Option<i16>,
     pub file_id: Uuid,
+    pub score: f64,
+    pub max_score: f64,
+    pub late: bool,
+    pub status: FileStatus,
 }
 
-impl From<(Submission, File)> for SubmissionWithFile {
-    fn from(file_and_sub: (Submission, File)) -> Self {
+/// Submission with its active file and the maximum attempts of its assignment.
+impl From<(Submission, File, Option<i16>)> for SubmissionWithFile {
+    fn from(file_and_sub: (Submission, File, Option<i16>)) -> Self {
+        let attempts_used = file_and_sub.0.update_count + 1;
         SubmissionWithFile {
             id: file_and_sub.0.id,
             assignment_id: file_and_sub.0.assignment_id,
             user_id: file_and_sub.0.user_id,
             extension: file_and_sub.0.extension.clone(),
             update_count: file_and_sub.0.update_count,
+            attempts_used,
+            max_attempts: file_and_sub.2,
+            remaining_attempts: file_and_sub
+                .2
+                .map(|max_attempts| (max_attempts - attempts_used).max(0)),
             file_id: file_and_sub.1.id,
+            score: file_and_sub.1.score,
+            max_score: file_and_sub.1.max_score,
+            late: file_and_sub.1.late,
+            status: file_and_sub.1.status,
         }
     }
 }
 
+#[derive(Deserialize)]
+pub struct ActiveFile {
+    pub file_id: Uuid,
+}
+
+/// Code as sent by the client, either a single file in `encoded_text` or a
+/// project with several `files`.
 #[derive(Deserialize)]
 pub struct InsertableCode {
     pub assignment_id: Uuid,
     pub user_id: Uuid,
     pub extension: String,
+    #[serde(default)]
     pub encoded_text: String,
+    #[serde(default)]
+    pub files: Vec<InsertableSourceFile>,
 }
 
 #[derive(Insertable)]
@@ -0,0 +1,342 @@
+use std::collections::HashSet;
+use std::io::{Cursor, Read};
+
+use rocket::http::ContentType;
+
+use crate::files::InsertableSourceFile;
+
+/// Largest archive and largest sum of file sizes accepted for a project.
+pub const MAX_PROJECT_SIZE: u64 = 10 * 1024 * 1024;
+const MAX_PROJECT_FILES: usize = 256;
+
+#[derive(Clone, Copy)]
+pub enum ArchiveFormat {
+    Zip,
+    Tar,
+}
+
+impl ArchiveFormat {
+    pub fn from_content_type(content_type: &ContentType) -> Option<Self> {
+        if content_type.is_zip() {
+            Some(ArchiveFormat::Zip)
+        } else if content_type.is_tar() {
+            Some(ArchiveFormat::Tar)
+        } else {
+            None
+        }
+    }
+}
+
+/// Check the files of a project and normalize their paths. Paths have to be
+/// relative, may not leave the project directory and have to be unique, and
+/// no file may be a directory of another one.
+pub fn validate(
+    source_files: Vec<InsertableSourceFile>,
+) -> Result<Vec<InsertableSourceFile>, String> {
+    if source_files.len() > MAX_PROJECT_FILES {
+        return Err(too_many_files());
+    }
+    let total_size: usize = source_files
+        .iter()
+        .map(|source_file| source_file.encoded_text.len())
+        .sum();
+    if total_size as u64 > MAX_PROJECT_SIZE {
+        return Err(too_large());
+    }
+    let mut paths = HashSet::new();
+    let source_files = source_files
+        .into_iter()
+        .map(|mut source_file| {
+            source_file.path = normalize_path(&source_file.path)
+                .ok_or_else(|| format!("invalid path {}", source_file.path))?;
+            if !paths.insert(source_file.path.clone()) {
+                return Err(format!("duplicate path {}", source_file.path));
+            }
+            Ok(source_file)
+        })
+        .collect::<Result<Vec<InsertableSourceFile>, String>>()?;
+    for source_file in &source_files {
+        let mut directory = source_file.path.as_str();
+        while let Some(end) = directory.rfind('/') {
+            directory = &directory[..end];
+            if paths.contains(directory) {
+                return Err(format!(
+                    "path {} is a file and a directory of {}",
+                    directory, source_file.path
+                ));
+            }
+        }
+    }
+    Ok(source_files)
+}
+
+/// Regular files of a zip or tar archive. Directories, links and other special
+/// entries are skipped. An archive without regular files is no project.
+pub fn extract(
+    archive: Vec<u8>,
+    format: ArchiveFormat,
+) -> Result<Vec<InsertableSourceFile>, String> {
+    let source_files = match format {
+        ArchiveFormat::Zip => extract_zip(archive),
+        ArchiveFormat::Tar => extract_tar(archive),
+    }?;
+    if source_files.is_empty() {
+        return Err("the archive contains no files".to_owned());
+    }
+    validate(source_files)
+}
+
+fn extract_zip(archive: Vec<u8>) -> Result<Vec<InsertableSourceFile>, String> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(archive)).map_err(|err| err.to_string())?;
+    let mut extraction = Extraction::default();
+    for index in 0..archive.len() {
+        let entry = archive.by_index(index).map_err(|err| err.to_string())?;
+        if !entry.is_file() {
+            continue;
+        }
+        let path = entry.name().to_owned();
+        extraction.read_entry(path, entry)?;
+    }
+    Ok(extraction.source_files)
+}
+
+fn extract_tar(archive: Vec<u8>) -> Result<Vec<InsertableSourceFile>, String> {
+    let mut archive = tar::Archive::new(Cursor::new(archive));
+    let mut extraction = Extraction::default();
+    for entry in archive.entries().map_err(|err| err.to_string())? {
+        let entry = entry.map_err(|err| err.to_string())?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry
+            .path()
+            .map_err(|err| err.to_string())?
+            .to_string_lossy()
+            .into_owned();
+        extraction.read_entry(path, entry)?;
+    }
+    Ok(extraction.source_files)
+}
+
+/// Files read from an archive so far. Reading stops as soon as the archive has
+/// more files or more bytes than a project can have, so a small archive can't
+/// be unpacked into a huge one.
+#[derive(Default)]
+struct Extraction {
+    source_files: Vec<InsertableSourceFile>,
+    total_size: u64,
+}
+
+impl Extraction {
+    fn read_entry<R: Read>(&mut self, path: String, entry: R) -> Result<(), String> {
+        if self.source_files.len() >= MAX_PROJECT_FILES {
+            return Err(too_many_files());
+        }
+        let mut encoded_text = Vec::new();
+        entry
+            .take(MAX_PROJECT_SIZE - self.total_size + 1)
+            .read_to_end(&mut encoded_text)
+            .map_err(|err| err.to_string())?;
+        self.total_size += encoded_text.len() as u64;
+        if self.total_size > MAX_PROJECT_SIZE {
+            return Err(too_large());
+        }
+        self.source_files
+            .push(InsertableSourceFile::new(path, encoded_text));
+        Ok(())
+    }
+}
+
+fn too_many_files() -> String {
+    format!("a project can have at most {} files", MAX_PROJECT_FILES)
+}
+
+fn too_large() -> String {
+    format!("a project can be at most {} bytes", MAX_PROJECT_SIZE)
+}
+
+fn normalize_path(path: &str) -> Option<String> {
+    if path.starts_with('/') || path.contains('\\') {
+        return None;
+    }
+    let components: Vec<&str> = path
+        .split('/')
+        .filter(|component| !component.is_empty() && *component != ".")
+        .collect();
+    if components.is_empty() || components.contains(&"..") {
+        return None;
+    }
+    Some(components.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    fn source_file(path: &str, encoded_text: &[u8]) -> InsertableSourceFile {
+        InsertableSourceFile::new(path.to_owned(), encoded_text.to_vec())
+    }
+
+    fn tar_of(entries: &[(&str, Vec<u8>)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, content) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, path, content.as_slice())
+                .unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    fn zip_of(entries: &[(&str, Vec<u8>)]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (path, content) in entries {
+            writer
+                .start_file(*path, zip::write::FileOptions::default())
+                .unwrap();
+            writer.write_all(content).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn paths_are_normalized() {
+        assert_eq!(normalize_path("src/main.c"), Some("src/main.c".to_owned()));
+        assert_eq!(
+            normalize_path("./src//main.c"),
+            Some("src/main.c".to_owned())
+        );
+        assert_eq!(normalize_path("src/"), Some("src".to_owned()));
+    }
+
+    #[test]
+    fn paths_outside_the_project_are_invalid() {
+        for path in &[
+            "",
+            ".",
+            "./",
+            "/etc/passwd",
+            "../main.c",
+            "src/../../main.c",
+            "src/../main.c",
+            "src\\main.c",
+            "..\\main.c",
+        ] {
+            assert_eq!(normalize_path(path), None, "{:?}", path);
+        }
+    }
+
+    #[test]
+    fn validate_normalizes_the_paths() {
+        let source_files = validate(vec![
+            source_file("./main.c", b"int main;"),
+            source_file("lib//util.h", b""),
+        ])
+        .unwrap();
+        let paths: Vec<&str> = source_files
+            .iter()
+            .map(|source_file| source_file.path.as_str())
+            .collect();
+        assert_eq!(paths, vec!["main.c", "lib/util.h"]);
+    }
+
+    #[test]
+    fn validate_rejects_invalid_and_duplicate_paths() {
+        assert!(validate(vec![source_file("../main.c", b"")]).is_err());
+        assert!(validate(vec![source_file("/main.c", b"")]).is_err());
+        assert!(validate(vec![
+            source_file("src/main.c", b""),
+            source_file("./src//main.c", b""),
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn validate_rejects_files_that_are_directories() {
+        assert!(validate(vec![
+            source_file("src", b""),
+            source_file("src/main.c", b"")
+        ])
+        .is_err());
+        assert!(validate(vec![
+            source_file("src/lib/util.h", b""),
+            source_file("./src/lib", b""),
+        ])
+        .is_err());
+        assert!(validate(vec![
+            source_file("src/main.c", b""),
+            source_file("src.c", b""),
+            source_file("srcs/main.c", b""),
+        ])
+        .is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_too_many_files_and_bytes() {
+        let too_many: Vec<InsertableSourceFile> = (0..=MAX_PROJECT_FILES)
+            .map(|index| source_file(&format!("{}.c", index), b""))
+            .collect();
+        assert_eq!(validate(too_many).err(), Some(too_many_files()));
+        let half = vec![0; MAX_PROJECT_SIZE as usize / 2 + 1];
+        assert_eq!(
+            validate(vec![source_file("a", &half), source_file("b", &half)]).err(),
+            Some(too_large())
+        );
+    }
+
+    #[test]
+    fn archives_are_extracted() {
+        let entries = vec![
+            ("src/main.c", b"int main;".to_vec()),
+            ("./README", b"readme".to_vec()),
+        ];
+        for archive in &[
+            extract(tar_of(&entries), ArchiveFormat::Tar),
+            extract(zip_of(&entries), ArchiveFormat::Zip),
+        ] {
+            let source_files = archive.as_ref().unwrap();
+            let paths: Vec<&str> = source_files
+                .iter()
+                .map(|source_file| source_file.path.as_str())
+                .collect();
+            assert_eq!(paths, vec!["src/main.c", "README"]);
+            assert_eq!(source_files[0].encoded_text, b"int main;");
+        }
+    }
+
+    #[test]
+    fn archives_without_files_are_rejected() {
+        assert!(extract(tar_of(&[]), ArchiveFormat::Tar).is_err());
+        assert!(extract(zip_of(&[]), ArchiveFormat::Zip).is_err());
+        let conflicting = vec![("src", Vec::new()), ("src/main.c", Vec::new())];
+        assert!(extract(tar_of(&conflicting), ArchiveFormat::Tar).is_err());
+        assert!(extract(zip_of(&conflicting), ArchiveFormat::Zip).is_err());
+    }
+
+    #[test]
+    fn extraction_stops_at_too_many_files() {
+        let entries: Vec<(String, Vec<u8>)> = (0..=MAX_PROJECT_FILES)
+            .map(|index| (format!("{}.c", index), Vec::new()))
+            .collect();
+        let entries: Vec<(&str, Vec<u8>)> = entries
+            .iter()
+            .map(|(path, content)| (path.as_str(), content.clone()))
+            .collect();
+        assert_eq!(extract_tar(tar_of(&entries)).err(), Some(too_many_files()));
+        assert_eq!(extract_zip(zip_of(&entries)).err(), Some(too_many_files()));
+    }
+
+    #[test]
+    fn extraction_stops_at_too_many_bytes() {
+        let half = vec![b'a'; MAX_PROJECT_SIZE as usize / 2 + 1];
+        let entries = vec![("a", half.clone()), ("b", half)];
+        assert_eq!(extract_tar(tar_of(&entries)).err(), Some(too_large()));
+        // compresses to a small archive
+        assert_eq!(extract_zip(zip_of(&entries)).err(), Some(too_large()));
+    }
+}
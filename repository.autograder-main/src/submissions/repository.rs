@@ -2,18 +2,28 @@ use chrono::Utc;
 use diesel::prelude::*;
 use uuid::Uuid;
 
+use crate::assignments;
 use crate::assignments::Assignment;
-use crate::auth::Auth;
-use crate::connection::RepositoryError::{NotFound, Unauthorized};
-use crate::connection::{RepositoryError, RepositoryQueryResult, RepositoryResult};
-use crate::schema::{assignments, submissions};
-use crate::submissions::{InsertableSubmission, Submission};
+use crate::audit;
+use crate::audit::AuditContext;
+use crate::auth::policy::{Action, Relation, Resource};
+use crate::auth::{policy, Auth};
+use crate::connection::RepositoryError::{NotFound, QueryError, Rejected};
+use crate::connection::{Page, PageRequest, RepositoryError, RepositoryQueryResult};
+use crate::extensions;
+use crate::files;
+use crate::files::{File, FileVersion, InsertableFile, InsertableSourceFile};
+use crate::schema::{assignments as assignments_schema, files as files_schema, submissions};
+use crate::submissions::{InsertableSubmission, Submission, SubmissionWithFile};
 
 pub fn all(auth: Auth, connection: &PgConnection) -> RepositoryQueryResult<Vec<Submission>> {
-    if !auth.is_superuser {
-        return RepositoryQueryResult::Err(RepositoryError::Unauthorized(
-            "user is no superuser".to_owned(),
-        ));
+    if let Err(error) = policy::authorize(
+        &auth,
+        Relation::Unrelated,
+        Action::List,
+        Resource::Submission,
+    ) {
+        return RepositoryQueryResult::Err(error);
     }
     submissions::table
         .order(submissions::created.desc())
@@ -26,55 +36,129 @@ pub fn all_by_assignment_id(
     auth: Auth,
     connection: &PgConnection,
 ) -> RepositoryQueryResult<Vec<Submission>> {
-    let assignment_result: QueryResult<Assignment> = assignments::table
-        .find(assignment_id)
-        .get_result(connection);
-    match assignment_result {
-        Ok(assignment) => {
-            if !(assignment.user_id == auth.user_id || auth.is_superuser) {
-                return RepositoryQueryResult::Err(Unauthorized(
-                    "user can't view assignment".to_owned(),
-                ));
-            }
-            submissions::table
-                .order(submissions::created.desc())
-                .filter(submissions::assignment_id.eq(assignment_id))
-                .load::<Submission>(connection)
-                .into()
-        }
-        Err(_) => RepositoryQueryResult::Err(NotFound),
+    if let Err(error) = assignments::repository::authorize(
+        assignment_id,
+        &auth,
+        Action::List,
+        Resource::Submission,
+        connection,
+    ) {
+        return RepositoryQueryResult::Err(error);
     }
+    submissions::table
+        .order(submissions::created.desc())
+        .filter(submissions::assignment_id.eq(assignment_id))
+        .load::<Submission>(connection)
+        .into()
 }
 
+/// Insert or resubmit the submission and store the code as its new version in
+/// one transaction. A resubmitted submission stays locked until its version is
+/// stored, so concurrent submissions of a student are counted one by one.
 pub fn insert(
     insertable_submission: InsertableSubmission,
+    encoded_text: Vec<u8>,
+    source_files: Vec<InsertableSourceFile>,
     auth: Auth,
+    audit: &AuditContext,
     connection: &PgConnection,
-) -> RepositoryQueryResult<Submission> {
-    match get_by_unique(
-        insertable_submission.assignment_id,
+) -> RepositoryQueryResult<SubmissionWithFile> {
+    let assignment_result: QueryResult<Assignment> = assignments_schema::table
+        .find(insertable_submission.assignment_id)
+        .get_result(connection);
+    let assignment = match assignment_result {
+        Ok(assignment) => assignment,
+        Err(_) => return RepositoryQueryResult::Err(NotFound),
+    };
+    let relation = if insertable_submission.user_id == auth.user_id {
+        match assignments::repository::relation_of(assignment.id, &auth, connection) {
+            Ok(relation) => relation,
+            Err(err) => return RepositoryQueryResult::Err(QueryError(err)),
+        }
+    } else {
+        Relation::Unrelated
+    };
+    if let Err(error) = policy::authorize(&auth, relation, Action::Create, Resource::Submission) {
+        return RepositoryQueryResult::Err(error);
+    }
+    let extension = match extensions::repository::find(
+        assignment.id,
         insertable_submission.user_id,
-        auth,
         connection,
     ) {
-        RepositoryResult::Ok(mut submission) => {
-            submission.created = Utc::now().naive_utc();
-            submission.update_count += 1;
-            diesel::update(submissions::table.find(submission.id))
-                .set(&submission)
-                .get_result(connection)
-                .into()
-        }
-        RepositoryResult::Err(error) => match error {
-            Unauthorized(_) => {
-                RepositoryQueryResult::Err(Unauthorized("user can't insert submission".to_owned()))
-            }
-            _ => diesel::insert_into(submissions::table)
+        Ok(extension) => extension,
+        Err(err) => return RepositoryQueryResult::Err(QueryError(err)),
+    };
+    if let Some(reason) =
+        assignment.rejects_submission_at(Utc::now().naive_utc(), extension.as_ref())
+    {
+        return RepositoryQueryResult::Err(Rejected(reason));
+    }
+    connection
+        .transaction::<_, RepositoryError<diesel::result::Error>, _>(|| {
+            let inserted: Option<Submission> = diesel::insert_into(submissions::table)
                 .values(&insertable_submission)
+                .on_conflict((submissions::assignment_id, submissions::user_id))
+                .do_nothing()
                 .get_result(connection)
-                .into(),
-        },
-    }
+                .optional()?;
+            let submission = match inserted {
+                Some(submission) => {
+                    audit::repository::record(
+                        audit,
+                        "create",
+                        Resource::Submission,
+                        submission.id,
+                        None,
+                        Some(&submission),
+                        connection,
+                    )?;
+                    submission
+                }
+                None => {
+                    let before: Submission = submissions::table
+                        .filter(submissions::assignment_id.eq(insertable_submission.assignment_id))
+                        .filter(submissions::user_id.eq(insertable_submission.user_id))
+                        .for_update()
+                        .first(connection)?;
+                    if let Some(reason) = assignment.rejects_resubmission(before.update_count) {
+                        return Err(Rejected(reason));
+                    }
+                    let after: Submission = diesel::update(submissions::table.find(before.id))
+                        .set((
+                            submissions::created.eq(Utc::now().naive_utc()),
+                            submissions::update_count.eq(before.update_count + 1),
+                        ))
+                        .get_result(connection)?;
+                    audit::repository::record(
+                        audit,
+                        "resubmit",
+                        Resource::Submission,
+                        after.id,
+                        Some(&before),
+                        Some(&after),
+                        connection,
+                    )?;
+                    after
+                }
+            };
+            let file = files::repository::insert(
+                InsertableFile {
+                    submission_id: submission.id,
+                    encoded_text,
+                    late: false,
+                },
+                source_files,
+                audit,
+                connection,
+            )?;
+            Ok(SubmissionWithFile::from((
+                submission,
+                file,
+                assignment.max_attempts,
+            )))
+        })
+        .into()
 }
 
 pub fn get_by_unique(
@@ -83,15 +167,161 @@ pub fn get_by_unique(
     auth: Auth,
     connection: &PgConnection,
 ) -> RepositoryQueryResult<Submission> {
-    if !(auth.user_id == user_id || auth.is_superuser) {
-        // TODO reafactor with call to assignment service and check if able to create assignment
-        return RepositoryQueryResult::Err(RepositoryError::Unauthorized(
-            "user is not allowed to query file".to_owned(),
-        ));
+    if let Err(error) = authorize(assignment_id, user_id, &auth, Action::Read, connection) {
+        return RepositoryQueryResult::Err(error);
+    }
+    match find_by_unique(assignment_id, user_id, connection) {
+        Ok(Some(submission)) => RepositoryQueryResult::Ok(submission),
+        Ok(None) => RepositoryQueryResult::Err(NotFound),
+        Err(err) => RepositoryQueryResult::Err(QueryError(err)),
     }
+}
+
+fn find_by_unique(
+    assignment_id: Uuid,
+    user_id: Uuid,
+    connection: &PgConnection,
+) -> QueryResult<Option<Submission>> {
     submissions::table
         .filter(submissions::assignment_id.eq(assignment_id))
         .filter(submissions::user_id.eq(user_id))
         .first(connection)
-        .into()
+        .optional()
+}
+
+/// Submission with its active file.
+pub fn get_with_file(
+    id: Uuid,
+    auth: Auth,
+    connection: &PgConnection,
+) -> RepositoryQueryResult<SubmissionWithFile> {
+    let submission = match viewable(id, auth, connection) {
+        Ok(submission) => submission,
+        Err(error) => return RepositoryQueryResult::Err(error),
+    };
+    with_active_file(submission, connection)
+}
+
+/// All versions of a submission without their content, newest first.
+pub fn all_files(
+    id: Uuid,
+    auth: Auth,
+    page_request: PageRequest,
+    connection: &PgConnection,
+) -> RepositoryQueryResult<Page<FileVersion>> {
+    match viewable(id, auth, connection) {
+        Ok(submission) => {
+            files::repository::all_versions(submission.id, page_request, connection).into()
+        }
+        Err(error) => RepositoryQueryResult::Err(error),
+    }
+}
+
+/// Mark a version of a submission as the one that is graded.
+pub fn set_active_file(
+    id: Uuid,
+    file_id: Uuid,
+    auth: Auth,
+    audit: &AuditContext,
+    connection: &PgConnection,
+) -> RepositoryQueryResult<SubmissionWithFile> {
+    let submission: Submission = match submissions::table.find(id).get_result(connection) {
+        Ok(submission) => submission,
+        Err(_) => return RepositoryQueryResult::Err(NotFound),
+    };
+    if let Err(error) = authorize(
+        submission.assignment_id,
+        submission.user_id,
+        &auth,
+        Action::Update,
+        connection,
+    ) {
+        return RepositoryQueryResult::Err(error);
+    }
+    let file_exists: QueryResult<i64> = files_schema::table
+        .filter(files_schema::id.eq(file_id))
+        .filter(files_schema::submission_id.eq(id))
+        .count()
+        .get_result(connection);
+    match file_exists {
+        Ok(0) => return RepositoryQueryResult::Err(NotFound),
+        Err(err) => return RepositoryQueryResult::Err(QueryError(err)),
+        _ => {}
+    }
+    let updated: QueryResult<Submission> = connection.transaction(|| {
+        let after: Submission = diesel::update(submissions::table.find(id))
+            .set(submissions::active_file_id.eq(file_id))
+            .get_result(connection)?;
+        audit::repository::record(
+            audit,
+            "set_active_file",
+            Resource::Submission,
+            id,
+            Some(&submission),
+            Some(&after),
+            connection,
+        )?;
+        Ok(after)
+    });
+    match updated {
+        Ok(submission) => with_active_file(submission, connection),
+        Err(err) => RepositoryQueryResult::Err(QueryError(err)),
+    }
+}
+
+fn viewable(
+    id: Uuid,
+    auth: Auth,
+    connection: &PgConnection,
+) -> Result<Submission, RepositoryError<diesel::result::Error>> {
+    let submission: Submission = submissions::table
+        .find(id)
+        .get_result(connection)
+        .map_err(|_| NotFound)?;
+    authorize(
+        submission.assignment_id,
+        submission.user_id,
+        &auth,
+        Action::Read,
+        connection,
+    )?;
+    Ok(submission)
+}
+
+/// Check with the policy that the user can do the action on the submission of
+/// a student for an assignment.
+fn authorize(
+    assignment_id: Uuid,
+    user_id: Uuid,
+    auth: &Auth,
+    action: Action,
+    connection: &PgConnection,
+) -> Result<(), RepositoryError<diesel::result::Error>> {
+    match assignments::repository::relation_of(assignment_id, auth, connection) {
+        Ok(Relation::Viewer) | Ok(Relation::Enrolled) | Ok(Relation::Unrelated)
+            if user_id == auth.user_id =>
+        {
+            policy::authorize(auth, Relation::Author, action, Resource::Submission)
+        }
+        Ok(relation) => policy::authorize(auth, relation, action, Resource::Submission),
+        Err(_) => Err(NotFound),
+    }
+}
+
+fn with_active_file(
+    submission: Submission,
+    connection: &PgConnection,
+) -> RepositoryQueryResult<SubmissionWithFile> {
+    let active_file_id = match submission.active_file_id {
+        Some(active_file_id) => active_file_id,
+        None => return RepositoryQueryResult::Err(NotFound),
+    };
+    let file: QueryResult<File> = files_schema::table
+        .find(active_file_id)
+        .get_result(connection);
+    file.and_then(|file| {
+        assignments::repository::max_attempts(submission.assignment_id, connection)
+            .map(|max_attempts| SubmissionWithFile::from((submission, file, max_attempts)))
+    })
+    .into()
 }
@@ -8,8 +8,12 @@ pub fn create_routes(rocket: Rocket) -> Rocket {
         routes![
             handler::all,
             handler::insert,
+            handler::insert_archive,
             handler::get_by_unique,
-            handler::all_submissions_for_assignment
+            handler::all_submissions_for_assignment,
+            handler::get,
+            handler::all_files,
+            handler::set_active_file
         ],
     )
 }
@@ -0,0 +1,115 @@
+use std::str::FromStr;
+
+use rocket::http::Status;
+use rocket::response::status;
+use rocket_contrib::json::Json;
+use uuid::Uuid;
+
+use crate::audit::{AuditContext, RequestId};
+use crate::auth::Auth;
+use crate::connection::{to_status_created, DbConn};
+use crate::test_cases;
+use crate::test_cases::{InsertableTestCase, TestCase, UpdatableTestCase};
+
+#[get("/<assignment_id>/test_cases")]
+pub fn all(
+    auth: Auth,
+    assignment_id: String,
+    connection: DbConn,
+) -> Result<Json<Vec<TestCase>>, rocket::http::Status> {
+    Uuid::from_str(&assignment_id)
+        .map_err(|_| Status::BadRequest)
+        .and_then(|uuid| {
+            test_cases::repository::all_by_assignment_id(uuid, auth, &connection).into()
+        })
+}
+
+#[post(
+    "/<assignment_id>/test_cases",
+    format = "application/json",
+    data = "<insertable_test_case>"
+)]
+pub fn insert(
+    auth: Auth,
+    request_id: RequestId,
+    assignment_id: String,
+    insertable_test_case: Json<InsertableTestCase>,
+    connection: DbConn,
+) -> Result<status::Created<Json<TestCase>>, rocket::http::Status> {
+    let audit = AuditContext::user(&auth, request_id);
+    let result: Result<Json<TestCase>, rocket::http::Status> = Uuid::from_str(&assignment_id)
+        .map_err(|_| Status::BadRequest)
+        .and_then(|uuid| {
+            test_cases::repository::insert(
+                uuid,
+                insertable_test_case.into_inner(),
+                auth,
+                &audit,
+                &connection,
+            )
+            .into()
+        });
+    result.map(|test_case| {
+        let path = format!("/assignments/{}/test_cases/", test_case.assignment_id);
+        to_status_created(test_case.id, &path, test_case)
+    })
+}
+
+#[get("/<assignment_id>/test_cases/<id>")]
+pub fn get(
+    auth: Auth,
+    assignment_id: String,
+    id: String,
+    connection: DbConn,
+) -> Result<Json<TestCase>, rocket::http::Status> {
+    parse_ids(&assignment_id, &id).and_then(|(assignment_id, id)| {
+        test_cases::repository::get(assignment_id, id, auth, &connection).into()
+    })
+}
+
+#[patch(
+    "/<assignment_id>/test_cases/<id>",
+    format = "application/json",
+    data = "<updatable_test_case>"
+)]
+pub fn update(
+    auth: Auth,
+    request_id: RequestId,
+    assignment_id: String,
+    id: String,
+    updatable_test_case: Json<UpdatableTestCase>,
+    connection: DbConn,
+) -> Result<Json<TestCase>, rocket::http::Status> {
+    let audit = AuditContext::user(&auth, request_id);
+    parse_ids(&assignment_id, &id).and_then(|(assignment_id, id)| {
+        test_cases::repository::update(
+            assignment_id,
+            id,
+            auth,
+            updatable_test_case.into_inner(),
+            &audit,
+            &connection,
+        )
+        .into()
+    })
+}
+
+#[delete("/<assignment_id>/test_cases/<id>")]
+pub fn delete(
+    auth: Auth,
+    request_id: RequestId,
+    assignment_id: String,
+    id: String,
+    connection: DbConn,
+) -> Result<Json<TestCase>, rocket::http::Status> {
+    let audit = AuditContext::user(&auth, request_id);
+    parse_ids(&assignment_id, &id).and_then(|(assignment_id, id)| {
+        test_cases::repository::delete(assignment_id, id, auth, &audit, &connection).into()
+    })
+}
+
+fn parse_ids(assignment_id: &str, id: &str) -> Result<(Uuid, Uuid), Status> {
+    Uuid::from_str(assignment_id)
+        .and_then(|assignment_id| Uuid::from_str(id).map(|id| (assignment_id, id)))
+        .map_err(|_| Status::BadRequest)
+}
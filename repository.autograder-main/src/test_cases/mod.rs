@@ -0,0 +1,62 @@
+use chrono::NaiveDateTime;
+use uuid::Uuid;
+
+use crate::assignments::Assignment;
+use crate::connection::{deserialize_base64, deserialize_optional_base64, serialize_base64};
+
+use super::schema::test_cases;
+
+pub mod handler;
+pub mod repository;
+pub mod router;
+
+#[derive(Identifiable, Queryable, Associations, PartialEq, Debug, Serialize)]
+#[belongs_to(Assignment)]
+pub struct TestCase {
+    pub id: Uuid,
+    pub assignment_id: Uuid,
+    pub position: i16,
+    pub name: String,
+    #[serde(serialize_with = "serialize_base64")]
+    pub encoded_input: Vec<u8>,
+    #[serde(serialize_with = "serialize_base64")]
+    pub encoded_output: Vec<u8>,
+    pub updated: NaiveDateTime,
+    pub weight: f64,
+}
+
+/// Test case as sent by the client. The assignment id is taken from the path,
+/// the position defaults to the end of the assignment's test cases and the
+/// weight to one point.
+#[derive(Deserialize, Insertable)]
+#[table_name = "test_cases"]
+pub struct InsertableTestCase {
+    #[serde(skip)]
+    pub assignment_id: Uuid,
+    #[serde(default)]
+    pub position: Option<i16>,
+    pub name: String,
+    #[serde(deserialize_with = "deserialize_base64")]
+    pub encoded_input: Vec<u8>,
+    #[serde(deserialize_with = "deserialize_base64")]
+    pub encoded_output: Vec<u8>,
+    #[serde(default)]
+    pub weight: Option<f64>,
+}
+
+#[derive(Deserialize, AsChangeset)]
+#[table_name = "test_cases"]
+pub struct UpdatableTestCase {
+    #[serde(default)]
+    pub position: Option<i16>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(deserialize_with = "deserialize_optional_base64")]
+    #[serde(default)]
+    pub encoded_input: Option<Vec<u8>>,
+    #[serde(deserialize_with = "deserialize_optional_base64")]
+    #[serde(default)]
+    pub encoded_output: Option<Vec<u8>>,
+    #[serde(default)]
+    pub weight: Option<f64>,
+}
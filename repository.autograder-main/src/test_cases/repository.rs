@@ -0,0 +1,238 @@
+use chrono::Utc;
+use diesel::dsl::max;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+use crate::assignments;
+use crate::assignments::comparison::check_expected_outputs;
+use crate::audit;
+use crate::audit::AuditContext;
+use crate::auth::policy::{Action, Resource};
+use crate::auth::Auth;
+use crate::connection::{RepositoryError, RepositoryQueryResult};
+use crate::schema::test_cases;
+use crate::test_cases::{InsertableTestCase, TestCase, UpdatableTestCase};
+
+pub fn all_by_assignment_id(
+    assignment_id: Uuid,
+    auth: Auth,
+    connection: &PgConnection,
+) -> RepositoryQueryResult<Vec<TestCase>> {
+    if let Err(error) = assignments::repository::authorize(
+        assignment_id,
+        &auth,
+        Action::List,
+        Resource::TestCase,
+        connection,
+    ) {
+        return RepositoryQueryResult::Err(error);
+    }
+    test_cases::table
+        .filter(test_cases::assignment_id.eq(assignment_id))
+        .order((test_cases::position.asc(), test_cases::name.asc()))
+        .load::<TestCase>(connection)
+        .into()
+}
+
+pub fn get(
+    assignment_id: Uuid,
+    id: Uuid,
+    auth: Auth,
+    connection: &PgConnection,
+) -> RepositoryQueryResult<TestCase> {
+    if let Err(error) = assignments::repository::authorize(
+        assignment_id,
+        &auth,
+        Action::Read,
+        Resource::TestCase,
+        connection,
+    ) {
+        return RepositoryQueryResult::Err(error);
+    }
+    match find(assignment_id, id, connection) {
+        Ok(test_case) => RepositoryQueryResult::Ok(test_case),
+        Err(_) => RepositoryQueryResult::Err(RepositoryError::NotFound),
+    }
+}
+
+pub fn insert(
+    assignment_id: Uuid,
+    mut insertable_test_case: InsertableTestCase,
+    auth: Auth,
+    audit: &AuditContext,
+    connection: &PgConnection,
+) -> RepositoryQueryResult<TestCase> {
+    if let Err(error) = assignments::repository::authorize(
+        assignment_id,
+        &auth,
+        Action::Create,
+        Resource::TestCase,
+        connection,
+    ) {
+        return RepositoryQueryResult::Err(error);
+    }
+    if let Err(error) = check_expected_output(
+        assignment_id,
+        &insertable_test_case.encoded_output,
+        connection,
+    ) {
+        return RepositoryQueryResult::Err(error);
+    }
+    insertable_test_case.assignment_id = assignment_id;
+    connection
+        .transaction::<_, RepositoryError<diesel::result::Error>, _>(|| {
+            if insertable_test_case.position.is_none() {
+                let last_position: Option<i16> = test_cases::table
+                    .filter(test_cases::assignment_id.eq(assignment_id))
+                    .select(max(test_cases::position))
+                    .first(connection)?;
+                insertable_test_case.position = Some(last_position.map_or(0, |pos| pos + 1));
+            }
+            let test_case: TestCase = diesel::insert_into(test_cases::table)
+                .values(&insertable_test_case)
+                .get_result(connection)?;
+            audit::repository::record(
+                audit,
+                "create",
+                Resource::TestCase,
+                test_case.id,
+                None,
+                Some(&test_case),
+                connection,
+            )?;
+            Ok(test_case)
+        })
+        .into()
+}
+
+/// Insert the test cases of a newly created assignment. Test cases without a
+/// position are ordered as they were sent. Authorization has to be checked by
+/// the caller.
+pub fn insert_all(
+    assignment_id: Uuid,
+    insertable_test_cases: Vec<InsertableTestCase>,
+    connection: &PgConnection,
+) -> QueryResult<usize> {
+    let insertable_test_cases: Vec<InsertableTestCase> = insertable_test_cases
+        .into_iter()
+        .enumerate()
+        .map(|(index, mut insertable_test_case)| {
+            insertable_test_case.assignment_id = assignment_id;
+            insertable_test_case.position = insertable_test_case.position.or(Some(index as i16));
+            insertable_test_case
+        })
+        .collect();
+    diesel::insert_into(test_cases::table)
+        .values(&insertable_test_cases)
+        .execute(connection)
+}
+
+pub fn update(
+    assignment_id: Uuid,
+    id: Uuid,
+    auth: Auth,
+    updatable_test_case: UpdatableTestCase,
+    audit: &AuditContext,
+    connection: &PgConnection,
+) -> RepositoryQueryResult<TestCase> {
+    if let Err(error) = assignments::repository::authorize(
+        assignment_id,
+        &auth,
+        Action::Update,
+        Resource::TestCase,
+        connection,
+    ) {
+        return RepositoryQueryResult::Err(error);
+    }
+    connection
+        .transaction::<_, RepositoryError<diesel::result::Error>, _>(|| {
+            let before = lock(assignment_id, id, connection)?;
+            if let Some(encoded_output) = &updatable_test_case.encoded_output {
+                check_expected_output(assignment_id, encoded_output, connection)?;
+            }
+            let after: TestCase = diesel::update(test_cases::table.find(id))
+                .set((
+                    &updatable_test_case,
+                    test_cases::updated.eq(Utc::now().naive_utc()),
+                ))
+                .get_result(connection)?;
+            audit::repository::record(
+                audit,
+                "update",
+                Resource::TestCase,
+                id,
+                Some(&before),
+                Some(&after),
+                connection,
+            )?;
+            Ok(after)
+        })
+        .into()
+}
+
+pub fn delete(
+    assignment_id: Uuid,
+    id: Uuid,
+    auth: Auth,
+    audit: &AuditContext,
+    connection: &PgConnection,
+) -> RepositoryQueryResult<TestCase> {
+    if let Err(error) = assignments::repository::authorize(
+        assignment_id,
+        &auth,
+        Action::Delete,
+        Resource::TestCase,
+        connection,
+    ) {
+        return RepositoryQueryResult::Err(error);
+    }
+    connection
+        .transaction::<_, RepositoryError<diesel::result::Error>, _>(|| {
+            lock(assignment_id, id, connection)?;
+            let deleted: TestCase =
+                diesel::delete(test_cases::table.find(id)).get_result(connection)?;
+            audit::repository::record(
+                audit,
+                "delete",
+                Resource::TestCase,
+                id,
+                Some(&deleted),
+                None,
+                connection,
+            )?;
+            Ok(deleted)
+        })
+        .into()
+}
+
+/// Reject an expected output the assignment can't compare outputs with.
+fn check_expected_output(
+    assignment_id: Uuid,
+    encoded_output: &[u8],
+    connection: &PgConnection,
+) -> Result<(), RepositoryError<diesel::result::Error>> {
+    let comparison_mode = assignments::repository::comparison_mode(assignment_id, connection)?;
+    check_expected_outputs(comparison_mode, vec![encoded_output]).map_err(RepositoryError::Invalid)
+}
+
+/// Lock a test case of the assignment until the end of the transaction.
+fn lock(
+    assignment_id: Uuid,
+    id: Uuid,
+    connection: &PgConnection,
+) -> Result<TestCase, RepositoryError<diesel::result::Error>> {
+    test_cases::table
+        .find(id)
+        .filter(test_cases::assignment_id.eq(assignment_id))
+        .for_update()
+        .get_result(connection)
+        .optional()?
+        .ok_or(RepositoryError::NotFound)
+}
+
+fn find(assignment_id: Uuid, id: Uuid, connection: &PgConnection) -> QueryResult<TestCase> {
+    test_cases::table
+        .find(id)
+        .filter(test_cases::assignment_id.eq(assignment_id))
+        .get_result(connection)
+}
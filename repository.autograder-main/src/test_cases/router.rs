@@ -0,0 +1,16 @@
+use rocket::Rocket;
+
+use crate::test_cases::handler;
+
+pub fn create_routes(rocket: Rocket) -> Rocket {
+    rocket.mount(
+        "/assignments",
+        routes![
+            handler::all,
+            handler::insert,
+            handler::get,
+            handler::update,
+            handler::delete
+        ],
+    )
+}
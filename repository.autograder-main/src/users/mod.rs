@@ -0,0 +1,30 @@
+use chrono::NaiveDateTime;
+use uuid::Uuid;
+
+use super::schema::users;
+
+pub mod repository;
+
+/// Roles of a user from the last token of the core service they used. Personal
+/// access tokens act with these roles at most, so a user who loses a role
+/// loses it for the tokens they issued before as well.
+#[derive(Identifiable, Queryable, PartialEq, Debug)]
+#[primary_key(user_id)]
+pub struct User {
+    pub user_id: Uuid,
+    pub is_superuser: bool,
+    pub is_teacher: bool,
+    pub is_student: bool,
+    pub email: String,
+    pub updated: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "users"]
+pub struct InsertableUser {
+    pub user_id: Uuid,
+    pub is_superuser: bool,
+    pub is_teacher: bool,
+    pub is_student: bool,
+    pub email: String,
+}
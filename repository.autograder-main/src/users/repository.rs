@@ -0,0 +1,45 @@
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+use uuid::Uuid;
+
+use crate::auth::Auth;
+use crate::schema::users;
+use crate::users::{InsertableUser, User};
+
+/// Store the roles of the user of a token of the core service. The user is
+/// only written when the roles or the email changed.
+pub fn record_roles(auth: &Auth, connection: &PgConnection) -> QueryResult<()> {
+    if let Some(user) = find(auth.user_id, connection)? {
+        if user.is_superuser == auth.is_superuser
+            && user.is_teacher == auth.is_teacher
+            && user.is_student == auth.is_student
+            && user.email == auth.email
+        {
+            return Ok(());
+        }
+    }
+    diesel::insert_into(users::table)
+        .values(&InsertableUser {
+            user_id: auth.user_id,
+            is_superuser: auth.is_superuser,
+            is_teacher: auth.is_teacher,
+            is_student: auth.is_student,
+            email: auth.email.clone(),
+        })
+        .on_conflict(users::user_id)
+        .do_update()
+        .set((
+            users::is_superuser.eq(excluded(users::is_superuser)),
+            users::is_teacher.eq(excluded(users::is_teacher)),
+            users::is_student.eq(excluded(users::is_student)),
+            users::email.eq(excluded(users::email)),
+            users::updated.eq(Utc::now().naive_utc()),
+        ))
+        .execute(connection)
+        .map(|_| ())
+}
+
+pub fn find(user_id: Uuid, connection: &PgConnection) -> QueryResult<Option<User>> {
+    users::table.find(user_id).get_result(connection).optional()
+}